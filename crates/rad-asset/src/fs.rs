@@ -0,0 +1,1322 @@
+use std::{
+	collections::BTreeMap,
+	fs,
+	io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+	ops::Deref,
+	path::{Component, Path, PathBuf},
+	sync::{
+		mpsc::{self, Receiver, Sender},
+		Arc,
+	},
+	time::{Duration, SystemTime},
+};
+
+use bincode::{Decode, Encode};
+use bytemuck::{Pod, Zeroable};
+use notify_debouncer_full::{
+	new_debouncer,
+	notify::{EventKind, RecommendedWatcher, RecursiveMode},
+	DebounceEventResult,
+	Debouncer,
+	FileIdMap,
+};
+use parking_lot::{Mutex, RwLock};
+use rad_core::{
+	asset::{
+		aref::{AssetId, UntypedAssetId},
+		chunk::{ChunkReader, ChunkWriter},
+		Asset,
+		AssetError,
+		AssetRead,
+		AssetSource,
+		AssetWrite,
+		CookKey,
+	},
+	Engine,
+};
+use rad_world::Uuid;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::{trace_span, warn};
+use walkdir::{DirEntry, WalkDir};
+use xxhash_rust::xxh3::Xxh3;
+use zstd::{Decoder, Encoder};
+
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct AssetHeader {
+	pub id: UntypedAssetId,
+	pub ty: Uuid,
+	/// The `Asset::VERSION` the asset was written with.
+	pub version: u32,
+}
+
+/// Where an imported asset came from, so it can be reimported in place.
+#[derive(Clone, Encode, Decode)]
+pub struct ImportInfo {
+	/// The path of the imported file, relative to the project root if it is inside the project.
+	pub source: PathBuf,
+	/// The hash of the source when the asset was imported.
+	pub source_hash: u64,
+	/// The settings of the import, encoded by the importer.
+	pub settings: Vec<u8>,
+	/// Identifies the part of the source the asset was imported from, to match it up on reimport.
+	pub key: String,
+}
+
+/// The version of the `.radass` container format. Version 2 added chunked payloads, and version 3 the hash of the
+/// payload.
+const FORMAT_VERSION: u32 = 3;
+/// Follows the ID and type in versioned files. Files written before the format was versioned have the zstd frame
+/// magic there instead.
+const HEADER_MAGIC: [u8; 4] = *b"RADA";
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// The payload is a chunk container written by `AssetWrite::write_chunks`, instead of a zstd stream. The chunks are
+/// already compressed, so it is stored as is to keep it seekable.
+const FLAG_CHUNKED: u32 = 1 << 0;
+
+/// The on-disk header of a `.radass` file.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct FileHeader {
+	id: UntypedAssetId,
+	ty: Uuid,
+	magic: [u8; 4],
+	format: u32,
+	version: u32,
+	flags: u32,
+	/// The `xxh3_64` hash of the payload, see `AssetRead::content_hash`.
+	content_hash: u64,
+}
+
+/// The length of the start of `FileHeader` that is the same in unversioned files, up to and including the magic that
+/// tells them apart.
+const HEADER_PREFIX: usize = std::mem::offset_of!(FileHeader, format);
+
+impl FileHeader {
+	/// The length of the header in files of `format`.
+	fn len(format: u32) -> usize {
+		if format < 3 {
+			std::mem::offset_of!(FileHeader, content_hash)
+		} else {
+			size_of::<FileHeader>()
+		}
+	}
+}
+
+/// The header of a cached cooked asset, used to check if it is stale.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct CookHeader {
+	base_hash: u64,
+	version: u32,
+	flags: u32,
+}
+
+#[derive(Default)]
+pub struct Dir {
+	dirs: BTreeMap<String, Dir>,
+	assets: BTreeMap<String, AssetHeader>,
+}
+
+impl Dir {
+	fn add_dir(&mut self, rel_path: &Path) -> &mut Dir {
+		let mut dir = self;
+		for part in rel_path.iter() {
+			dir = dir.dirs.entry(part.to_string_lossy().into_owned()).or_default();
+		}
+		dir
+	}
+
+	pub fn get_dir(&self, rel_path: &Path) -> Option<&Dir> {
+		let mut dir = self;
+		for part in rel_path.iter() {
+			dir = dir.dirs.get(part.to_string_lossy().as_ref())?;
+		}
+		Some(dir)
+	}
+
+	pub fn dirs(&self) -> impl ExactSizeIterator<Item = (&String, &Dir)> + '_ { self.dirs.iter() }
+
+	pub fn assets(&self) -> impl ExactSizeIterator<Item = (&String, &AssetHeader)> + '_ { self.assets.iter() }
+
+	fn get_dir_mut(&mut self, rel_path: &Path) -> Option<&mut Dir> {
+		let mut dir = self;
+		for part in rel_path.iter() {
+			dir = dir.dirs.get_mut(part.to_string_lossy().as_ref())?;
+		}
+		Some(dir)
+	}
+
+	fn add_asset(&mut self, rel_path: &Path, asset: AssetHeader) {
+		self.add_dir(rel_path.parent().unwrap())
+			.assets
+			.insert(rel_path.file_name().unwrap().to_string_lossy().into_owned(), asset);
+	}
+
+	fn remove_asset(&mut self, rel_path: &Path) {
+		if let (Some(parent), Some(name)) = (rel_path.parent(), rel_path.file_name())
+			&& let Some(dir) = self.get_dir_mut(parent)
+		{
+			dir.assets.remove(name.to_string_lossy().as_ref());
+		}
+	}
+
+	fn remove_dir(&mut self, rel_path: &Path) {
+		if let (Some(parent), Some(name)) = (rel_path.parent(), rel_path.file_name())
+			&& let Some(dir) = self.get_dir_mut(parent)
+		{
+			dir.dirs.remove(name.to_string_lossy().as_ref());
+		}
+	}
+}
+
+/// A change to the assets in the project, see `FsAssetSystem::subscribe`.
+#[derive(Clone)]
+pub enum AssetEvent {
+	Created(AssetHeader),
+	Modified(AssetHeader),
+	Moved {
+		header: AssetHeader,
+		from: PathBuf,
+		to: PathBuf,
+	},
+	Removed(AssetHeader),
+}
+
+/// The dependencies of an asset, as of when its file was last modified.
+struct CachedDeps {
+	modified: SystemTime,
+	deps: Vec<(UntypedAssetId, Uuid)>,
+}
+
+#[derive(Default)]
+pub struct FsAssetSystem {
+	root: RwLock<Option<PathBuf>>,
+	assets: RwLock<FxHashMap<UntypedAssetId, PathBuf>>,
+	by_path: RwLock<FxHashMap<PathBuf, AssetHeader>>,
+	by_type: RwLock<FxHashMap<Uuid, FxHashSet<UntypedAssetId>>>,
+	dir: RwLock<Dir>,
+	deps: RwLock<FxHashMap<UntypedAssetId, CachedDeps>>,
+	watcher: Mutex<Option<Debouncer<RecommendedWatcher, FileIdMap>>>,
+	subscribers: Mutex<Vec<Sender<AssetEvent>>>,
+}
+
+impl FsAssetSystem {
+	pub fn new(root: Option<PathBuf>) -> Arc<Self> {
+		let this = Arc::new(Self {
+			root: RwLock::new(root),
+			..Default::default()
+		});
+		this.rescan();
+		this.watch();
+		this
+	}
+
+	pub fn root(&self) -> impl Deref<Target = Option<PathBuf>> + '_ { self.root.read() }
+
+	pub fn open(self: &Arc<Self>, root: PathBuf) {
+		*self.root.write() = Some(root);
+		self.rescan();
+		self.watch();
+	}
+
+	/// Receive an event whenever an asset is created, modified, moved, or removed. Events are sent from the watcher
+	/// thread.
+	pub fn subscribe(&self) -> Receiver<AssetEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.lock().push(tx);
+		rx
+	}
+
+	/// Reload the loaded views of assets in `Engine::get` whenever their files are modified. The engine must be built
+	/// first.
+	pub fn hot_reload(&self) {
+		let engine = Engine::get();
+		let events = self.subscribe();
+		let _ = std::thread::Builder::new()
+			.name("asset hot reload".to_string())
+			.spawn(move || {
+				for event in events {
+					if let AssetEvent::Modified(header) = event
+						&& let Err(e) = engine.enter(|| engine.reload_asset(header.id))
+					{
+						warn!("failed to reload asset {}: {:?}", header.id, e);
+					}
+				}
+			});
+	}
+
+	pub fn create<T: Asset>(&self, rel_path: &Path, id: AssetId<T>) -> Result<FsAssetWrite, io::Error> {
+		let s = trace_span!("create asset", path = %rel_path.display(), id = %id);
+		let _e = s.enter();
+
+		check_rel_path(rel_path)?;
+		let path = self
+			.abs_path(rel_path)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no system opened"))?;
+		fs::create_dir_all(path.parent().unwrap())?;
+
+		let view = FsAssetWrite::create(&path, id);
+		self.update(
+			&path,
+			AssetHeader {
+				id: id.to_untyped(),
+				ty: T::UUID,
+				version: T::VERSION,
+			},
+		);
+
+		view
+	}
+
+	pub fn dir(&self) -> impl Deref<Target = Dir> + '_ { self.dir.read() }
+
+	/// All the assets in the system, and their types.
+	pub fn assets(&self) -> Vec<(UntypedAssetId, Uuid)> {
+		self.by_type
+			.read()
+			.iter()
+			.flat_map(|(&ty, ids)| ids.iter().map(move |&id| (id, ty)))
+			.collect()
+	}
+
+	/// All the assets of type `ty`.
+	pub fn assets_of_type(&self, ty: Uuid) -> Vec<UntypedAssetId> {
+		self.by_type
+			.read()
+			.get(&ty)
+			.map(|x| x.iter().copied().collect())
+			.unwrap_or_default()
+	}
+
+	/// The asset at a project-relative path, without the extension.
+	pub fn lookup(&self, rel_path: &Path) -> Option<AssetHeader> {
+		let path = self.abs_path(rel_path)?;
+		self.by_path.read().get(&path).copied()
+	}
+
+	/// The asset at a project-relative path, if it is a `T`.
+	pub fn lookup_typed<T: Asset>(&self, rel_path: &Path) -> Option<AssetId<T>> {
+		self.lookup(rel_path)
+			.filter(|x| x.ty == T::UUID)
+			.map(|x| unsafe { x.id.typed() })
+	}
+
+	/// The project-relative path of an asset, without the extension.
+	pub fn rel_path_of(&self, id: UntypedAssetId) -> Option<PathBuf> { self.path(id).and_then(|x| self.rel_path(&x)) }
+
+	/// Move an asset to a project-relative path, creating any missing directories.
+	pub fn move_asset(&self, id: UntypedAssetId, to: &Path) -> Result<(), io::Error> {
+		let s = trace_span!("move asset", id = %id, to = %to.display());
+		let _e = s.enter();
+
+		let (from, header) = self.locate(id)?;
+		let to = self.free_path(to)?;
+		fs::create_dir_all(to.parent().unwrap())?;
+		fs::rename(&from, &to)?;
+		self.update(&to, header);
+		Ok(())
+	}
+
+	/// Rename an asset, keeping it in the same directory.
+	pub fn rename(&self, id: UntypedAssetId, name: &str) -> Result<(), io::Error> {
+		let rel = self
+			.rel_path_of(id)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "asset not found"))?;
+		self.move_asset(id, &rel.with_file_name(name))
+	}
+
+	/// Copy an asset to a project-relative path under a new ID, returning the ID of the copy.
+	pub fn duplicate(&self, id: UntypedAssetId, to: &Path) -> Result<UntypedAssetId, io::Error> {
+		let s = trace_span!("duplicate asset", id = %id, to = %to.display());
+		let _e = s.enter();
+
+		let (from, header) = self.locate(id)?;
+		let to = self.free_path(to)?;
+		let mut data = fs::read(&from)?;
+		// Both versioned and unversioned files start with the ID.
+		let new = data
+			.get_mut(..size_of::<UntypedAssetId>())
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an asset file"))?;
+		new.copy_from_slice(Uuid::new_v4().as_bytes());
+		let new: UntypedAssetId = bytemuck::pod_read_unaligned(new);
+
+		fs::create_dir_all(to.parent().unwrap())?;
+		fs::write(&to, data)?;
+		self.update(&to, AssetHeader { id: new, ..header });
+		Ok(new)
+	}
+
+	/// Delete an asset from disk, along with anything cooked from it. Fails if other assets still reference it.
+	pub fn delete(&self, id: UntypedAssetId) -> Result<(), io::Error> {
+		let s = trace_span!("delete asset", id = %id);
+		let _e = s.enter();
+
+		let (path, _) = self.locate(id)?;
+		let dependents = self.dependents(id);
+		if !dependents.is_empty() {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("asset is still referenced by {} other assets", dependents.len()),
+			));
+		}
+		fs::remove_file(&path)?;
+		self.remove(&path);
+		if let Some(import) = self.import_path(id) {
+			match fs::remove_file(import) {
+				Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+				_ => {},
+			}
+		}
+		self.invalidate_cooked(id)
+	}
+
+	/// Where an asset was imported from, if it was imported.
+	pub fn import_info(&self, id: UntypedAssetId) -> Option<ImportInfo> {
+		let data = fs::read(self.import_path(id)?).ok()?;
+		match bincode::decode_from_slice(&data, bincode::config::standard()) {
+			Ok((info, _)) => Some(info),
+			Err(e) => {
+				warn!("failed to read import info of {id}: {e:?}");
+				None
+			},
+		}
+	}
+
+	/// Record where an asset was imported from. This is kept by ID, so it follows the asset when it is moved.
+	pub fn set_import_info(&self, id: UntypedAssetId, info: &ImportInfo) -> Result<(), io::Error> {
+		let path = self
+			.import_path(id)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no system opened"))?;
+		let data = bincode::encode_to_vec(info, bincode::config::standard()).map_err(io::Error::other)?;
+		fs::create_dir_all(path.parent().unwrap())?;
+		fs::write(path, data)
+	}
+
+	/// All the assets imported from `source`.
+	pub fn imported_from(&self, source: &Path) -> Vec<(UntypedAssetId, ImportInfo)> {
+		let Some(dir) = self.import_dir() else {
+			return Vec::new();
+		};
+		let Ok(entries) = fs::read_dir(dir) else {
+			return Vec::new();
+		};
+		entries
+			.filter_map(|x| x.ok()?.file_name().to_str()?.parse().ok())
+			.filter(|&id| self.path(id).is_some())
+			.filter_map(|id| Some((id, self.import_info(id)?)))
+			.filter(|(_, info)| info.source == source)
+			.collect()
+	}
+
+	/// The path of an asset's file.
+	pub fn path(&self, id: UntypedAssetId) -> Option<PathBuf> { self.assets.read().get(&id).cloned() }
+
+	/// Read the header of an asset's file.
+	pub fn header(&self, id: UntypedAssetId) -> Result<AssetHeader, io::Error> {
+		let path = self
+			.path(id)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "asset not found"))?;
+		Ok(FsAssetRead::open(&path)?.header())
+	}
+
+	/// The assets directly referenced by an asset, and their types. These are cached until the asset's file is
+	/// modified.
+	pub fn dependencies(&self, id: UntypedAssetId) -> Result<Vec<(UntypedAssetId, Uuid)>, AssetError> {
+		let ty = self.header(id)?.ty;
+		self.dependencies_of(id, ty)
+	}
+
+	/// The assets that directly reference an asset.
+	pub fn dependents(&self, id: UntypedAssetId) -> Vec<(UntypedAssetId, Uuid)> {
+		let engine = Engine::get();
+		self.assets()
+			.into_par_iter()
+			.filter(|&(x, ty)| {
+				engine
+					.enter(|| self.dependencies_of(x, ty))
+					.is_ok_and(|deps| deps.iter().any(|&(d, _)| d == id))
+			})
+			.collect()
+	}
+
+	fn dependencies_of(&self, id: UntypedAssetId, ty: Uuid) -> Result<Vec<(UntypedAssetId, Uuid)>, AssetError> {
+		let path = self.path(id).ok_or(AssetError::NotFound { id, ty })?;
+		let modified = fs::metadata(path)?.modified()?;
+		if let Some(x) = self.deps.read().get(&id)
+			&& x.modified == modified
+		{
+			return Ok(x.deps.clone());
+		}
+
+		let deps = Engine::get().dependencies(id, ty)?;
+		self.deps.write().insert(
+			id,
+			CachedDeps {
+				modified,
+				deps: deps.clone(),
+			},
+		);
+		Ok(deps)
+	}
+
+	/// Rescan the whole project for assets. The project is watched for changes, so this is only needed to recover if
+	/// the watcher misses something.
+	pub fn rescan(&self) {
+		let s = trace_span!("rescan assets");
+		let _e = s.enter();
+
+		let r = self.root.read().clone();
+		let Some(ref root) = r else {
+			return;
+		};
+
+		let new = Self {
+			root: RwLock::new(r.clone()),
+			..Default::default()
+		};
+		for entry in Self::walk(root).filter_map(|x| x.ok()) {
+			let path = entry.path();
+			let is_file = path.is_file();
+			if is_file && path.extension().and_then(|x| x.to_str()) == Some("radass") {
+				if let Ok(mut view) = FsAssetRead::open(entry.path()) {
+					new.add_asset_abs(path, view.header());
+				}
+			} else if !is_file {
+				new.add_dir_abs(path);
+			}
+		}
+		let old = std::mem::replace(&mut *self.by_path.write(), new.by_path.into_inner());
+		let old_assets = std::mem::replace(&mut *self.assets.write(), new.assets.into_inner());
+		*self.by_type.write() = new.by_type.into_inner();
+		*self.dir.write() = new.dir.into_inner();
+
+		let mut events = Vec::new();
+		let assets = self.assets.read();
+		self.deps.write().retain(|id, _| assets.contains_key(id));
+		for (path, &header) in self.by_path.read().iter() {
+			match old_assets.get(&header.id) {
+				Some(from) if from == path => {},
+				Some(from) => events.push(AssetEvent::Moved {
+					header,
+					from: from.clone(),
+					to: path.clone(),
+				}),
+				None => events.push(AssetEvent::Created(header)),
+			}
+		}
+		events.extend(
+			old.into_values()
+				.filter(|x| !assets.contains_key(&x.id))
+				.map(AssetEvent::Removed),
+		);
+		drop(assets);
+		for event in events {
+			self.broadcast(event);
+		}
+	}
+
+	fn walk(root: &Path) -> impl Iterator<Item = walkdir::Result<DirEntry>> {
+		// Skip hidden directories, they hold the cook cache and other bookkeeping.
+		WalkDir::new(root)
+			.into_iter()
+			.filter_entry(|x| x.depth() == 0 || !x.file_name().to_string_lossy().starts_with('.'))
+	}
+
+	fn watch(self: &Arc<Self>) {
+		let Some(root) = self.root.read().clone() else {
+			return;
+		};
+
+		let this = Arc::downgrade(self);
+		let watcher = new_debouncer(Duration::from_secs_f32(0.5), None, move |res: DebounceEventResult| {
+			let Some(this) = this.upgrade() else {
+				return;
+			};
+			match res {
+				Ok(evs) => {
+					for ev in evs {
+						if ev.need_rescan() {
+							this.rescan();
+						} else if !matches!(ev.kind, EventKind::Access(_)) {
+							// Renames list the source first, sync the destination first so it's seen as a move.
+							for path in ev.paths.iter().rev() {
+								this.sync(path);
+							}
+						}
+					}
+				},
+				Err(e) => {
+					warn!("asset watcher failed: {:?}", e);
+					this.rescan();
+				},
+			}
+		});
+
+		match watcher {
+			Ok(mut watcher) => {
+				if let Err(e) = watcher.watch(&root, RecursiveMode::Recursive) {
+					warn!("failed to watch `{}`: {:?}", root.display(), e);
+				}
+				*self.watcher.lock() = Some(watcher);
+			},
+			Err(e) => warn!("failed to create asset watcher: {:?}", e),
+		}
+	}
+
+	/// Bring the system up to date with a path that changed on disk.
+	fn sync(&self, path: &Path) {
+		let Some(rel) = self.rel_path(path) else {
+			return;
+		};
+		if rel.iter().any(|x| x.to_string_lossy().starts_with('.')) {
+			return;
+		}
+
+		if path.is_dir() {
+			for entry in Self::walk(path).filter_map(|x| x.ok()) {
+				if entry.file_type().is_dir() {
+					self.add_dir_abs(entry.path());
+				} else {
+					self.sync_file(entry.path());
+				}
+			}
+		} else if path.is_file() {
+			self.sync_file(path);
+		} else {
+			self.remove(path);
+		}
+	}
+
+	fn sync_file(&self, path: &Path) {
+		if path.extension().and_then(|x| x.to_str()) != Some("radass") {
+			return;
+		}
+		// The file might be half-written, in which case there will be another event once it's done.
+		if let Ok(mut view) = FsAssetRead::open(path) {
+			self.update(path, view.header());
+		}
+	}
+
+	/// Record the asset at `path`, replacing whatever was there before.
+	fn update(&self, path: &Path, header: AssetHeader) {
+		let replaced = self.by_path.read().get(path).copied();
+		if replaced.is_some_and(|x| x.id != header.id) {
+			self.remove(path);
+		}
+
+		let event = match self.remove_asset(header.id) {
+			Some((from, _)) if from == path => AssetEvent::Modified(header),
+			Some((from, _)) => AssetEvent::Moved {
+				header,
+				from,
+				to: path.to_owned(),
+			},
+			None => AssetEvent::Created(header),
+		};
+		self.add_asset_abs(path, header);
+		self.broadcast(event);
+	}
+
+	/// Forget the asset at `path`, or every asset under it if it was a directory.
+	fn remove(&self, path: &Path) {
+		let ids: Vec<_> = {
+			let by_path = self.by_path.read();
+			match by_path.get(path) {
+				Some(x) => vec![x.id],
+				None => by_path
+					.iter()
+					.filter(|(x, _)| x.starts_with(path))
+					.map(|(_, x)| x.id)
+					.collect(),
+			}
+		};
+		for id in ids {
+			if let Some((_, header)) = self.remove_asset(id) {
+				self.broadcast(AssetEvent::Removed(header));
+			}
+		}
+
+		if path.extension().and_then(|x| x.to_str()) != Some("radass")
+			&& let Some(rel) = self.rel_path(path)
+		{
+			self.dir.write().remove_dir(&rel);
+		}
+	}
+
+	fn locate(&self, id: UntypedAssetId) -> Result<(PathBuf, AssetHeader), io::Error> {
+		let not_found = || io::Error::new(io::ErrorKind::NotFound, "asset not found");
+		let path = self.path(id).ok_or_else(not_found)?;
+		let header = self.by_path.read().get(&path).copied().ok_or_else(not_found)?;
+		Ok((path, header))
+	}
+
+	/// The absolute path of `rel_path`, if nothing is there yet.
+	fn free_path(&self, rel_path: &Path) -> Result<PathBuf, io::Error> {
+		check_rel_path(rel_path)?;
+		let path = self
+			.abs_path(rel_path)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no system opened"))?;
+		if path.exists() {
+			return Err(io::Error::new(
+				io::ErrorKind::AlreadyExists,
+				format!("`{}` already exists", rel_path.display()),
+			));
+		}
+		Ok(path)
+	}
+
+	fn broadcast(&self, event: AssetEvent) { self.subscribers.lock().retain(|x| x.send(event.clone()).is_ok()); }
+
+	fn add_asset_abs(&self, abs_path: &Path, asset: AssetHeader) {
+		self.assets.write().insert(asset.id, abs_path.to_owned());
+		self.by_path.write().insert(abs_path.to_owned(), asset);
+		self.by_type.write().entry(asset.ty).or_default().insert(asset.id);
+		self.dir.write().add_asset(&self.rel_path(abs_path).unwrap(), asset);
+	}
+
+	fn remove_asset(&self, id: UntypedAssetId) -> Option<(PathBuf, AssetHeader)> {
+		let path = self.assets.write().remove(&id)?;
+		let asset = self.by_path.write().remove(&path)?;
+		if let Some(ids) = self.by_type.write().get_mut(&asset.ty) {
+			ids.remove(&id);
+		}
+		if let Some(rel) = self.rel_path(&path) {
+			self.dir.write().remove_asset(&rel);
+		}
+		self.deps.write().remove(&id);
+		Some((path, asset))
+	}
+
+	// fn add_dir(&self, rel_path: &Path) { self.dir.write().add_dir(rel_path); }
+
+	fn add_dir_abs(&self, abs_path: &Path) { self.dir.write().add_dir(&self.rel_path(abs_path).unwrap()); }
+
+	fn rel_path(&self, abs_path: &Path) -> Option<PathBuf> {
+		self.root
+			.read()
+			.as_ref()
+			.and_then(|x| abs_path.strip_prefix(x).ok().map(|x| x.with_extension("")))
+	}
+
+	fn abs_path(&self, rel_path: &Path) -> Option<PathBuf> {
+		self.root
+			.read()
+			.as_ref()
+			.map(|x| x.join(rel_path).with_added_extension("radass"))
+	}
+
+	fn cook_dir(&self) -> Option<PathBuf> { self.root.read().as_ref().map(|x| x.join(".cooked")) }
+
+	fn import_dir(&self) -> Option<PathBuf> { self.root.read().as_ref().map(|x| x.join(".imports")) }
+
+	fn import_path(&self, id: UntypedAssetId) -> Option<PathBuf> { self.import_dir().map(|x| x.join(id.to_string())) }
+
+	fn cooked_path(&self, id: UntypedAssetId, ty: Uuid) -> Option<PathBuf> {
+		self.cook_dir().map(|x| {
+			x.join(id.to_string())
+				.join(ty.to_string())
+				.with_added_extension("radcook")
+		})
+	}
+}
+
+impl AssetSource for FsAssetSystem {
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, AssetError> {
+		let s = trace_span!("load asset", id = %id, ty = %ty);
+		let _e = s.enter();
+
+		let assets = self.assets.read();
+		let path = assets.get(&id).ok_or(AssetError::NotFound { id, ty })?;
+		let mut view = FsAssetRead::open(path)?;
+		// TODO: cooka
+		let found = view.header().ty;
+		if found != ty {
+			return Err(AssetError::TypeMismatch {
+				id,
+				expected: ty,
+				found,
+			});
+		}
+		Ok(Box::new(view))
+	}
+
+	fn load_cooked(&self, key: CookKey) -> Result<Box<dyn AssetRead>, AssetError> {
+		let not_found = AssetError::NotFound { id: key.id, ty: key.ty };
+		let path = self.cooked_path(key.id, key.ty).ok_or_else(|| not_found.clone())?;
+		match FsAssetRead::open_cooked(&path, key) {
+			Ok(read) => Ok(Box::new(read)),
+			// Missing and stale cooked data.
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Err(not_found),
+			Err(e) => Err(e.into()),
+		}
+	}
+
+	fn store_cooked(&self, key: CookKey) -> Result<Box<dyn AssetWrite>, io::Error> {
+		let s = trace_span!("store cooked asset", id = %key.id, ty = %key.ty);
+		let _e = s.enter();
+
+		let path = self
+			.cooked_path(key.id, key.ty)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no system opened"))?;
+		fs::create_dir_all(path.parent().unwrap())?;
+		Ok(Box::new(FsAssetWrite::create_cooked(&path, key)?))
+	}
+
+	fn invalidate_cooked(&self, id: UntypedAssetId) -> Result<(), io::Error> {
+		let Some(dir) = self.cook_dir() else {
+			return Ok(());
+		};
+		match fs::remove_dir_all(dir.join(id.to_string())) {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+			_ => Ok(()),
+		}
+	}
+
+	fn purge_cooked(&self) -> Result<(), io::Error> {
+		let Some(dir) = self.cook_dir() else {
+			return Ok(());
+		};
+		match fs::remove_dir_all(dir) {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+			_ => Ok(()),
+		}
+	}
+
+	fn rewrite(&self, id: UntypedAssetId, ty: Uuid, version: u32) -> Result<Box<dyn AssetWrite>, io::Error> {
+		let s = trace_span!("rewrite asset", id = %id, ty = %ty);
+		let _e = s.enter();
+
+		let path = self
+			.assets
+			.read()
+			.get(&id)
+			.cloned()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "asset not found"))?;
+		let header = AssetHeader { id, ty, version };
+		let write = FsAssetWrite::create_header(&path, header)?;
+		self.by_path.write().insert(path.clone(), header);
+		if let Some(rel_path) = self.rel_path(&path) {
+			self.dir.write().add_asset(&rel_path, header);
+		}
+		Ok(Box::new(write))
+	}
+}
+
+/// Check that `rel_path` is relative and stays inside the project, before anything is done with it.
+fn check_rel_path(rel_path: &Path) -> Result<(), io::Error> {
+	let mut components = rel_path.components().peekable();
+	if components.peek().is_some() && components.all(|x| matches!(x, Component::Normal(_))) {
+		Ok(())
+	} else {
+		Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("`{}` is not a path inside the project", rel_path.display()),
+		))
+	}
+}
+
+pub struct FsAssetRead {
+	header: AssetHeader,
+	/// Cooked assets are not versioned.
+	versioned: bool,
+	content_hash: Option<u64>,
+	read: FsPayload,
+}
+
+enum FsPayload {
+	Stream(Decoder<'static, BufReader<io::Chain<&'static [u8], fs::File>>>),
+	Chunked(BufReader<fs::File>),
+}
+
+impl Read for FsAssetRead {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		match &mut self.read {
+			FsPayload::Stream(x) => x.read(buf),
+			FsPayload::Chunked(x) => x.read(buf),
+		}
+	}
+}
+impl AssetRead for FsAssetRead {
+	fn version(&self) -> Option<u32> { self.versioned.then_some(self.header.version) }
+
+	fn content_hash(&self) -> Option<u64> { self.content_hash }
+
+	fn chunks(self: Box<Self>) -> Result<ChunkReader, io::Error> {
+		match self.read {
+			FsPayload::Chunked(x) => ChunkReader::open(x),
+			// Migrated assets are rewritten as a stream.
+			FsPayload::Stream(mut x) => {
+				let mut bytes = Vec::new();
+				x.read_to_end(&mut bytes)?;
+				ChunkReader::open(io::Cursor::new(bytes))
+			},
+		}
+	}
+}
+
+impl FsAssetRead {
+	fn open(path: &Path) -> Result<Self, io::Error> {
+		let mut file = fs::OpenOptions::new().read(true).open(path)?;
+		let mut header = FileHeader::zeroed();
+		file.read_exact(&mut bytemuck::bytes_of_mut(&mut header)[..HEADER_PREFIX])?;
+		// Unversioned files start the zstd stream right after the type, so the magic has to be given back.
+		let prefix: &'static [u8] = match header.magic {
+			HEADER_MAGIC => {
+				let format_end = std::mem::offset_of!(FileHeader, version);
+				file.read_exact(&mut bytemuck::bytes_of_mut(&mut header)[HEADER_PREFIX..format_end])?;
+				if header.format > FORMAT_VERSION {
+					return Err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						format!(
+							"asset format version {} is newer than the supported version {FORMAT_VERSION}",
+							header.format
+						),
+					));
+				}
+				let len = FileHeader::len(header.format);
+				file.read_exact(&mut bytemuck::bytes_of_mut(&mut header)[format_end..len])?;
+				&[]
+			},
+			ZSTD_MAGIC => &ZSTD_MAGIC,
+			_ => return Err(io::Error::new(io::ErrorKind::InvalidData, "not an asset file")),
+		};
+		Ok(Self {
+			header: AssetHeader {
+				id: header.id,
+				ty: header.ty,
+				version: header.version,
+			},
+			versioned: true,
+			content_hash: (header.magic == HEADER_MAGIC && header.format >= 3).then_some(header.content_hash),
+			read: FsPayload::new(header.flags, prefix, file)?,
+		})
+	}
+
+	fn open_cooked(path: &Path, key: CookKey) -> Result<Self, io::Error> {
+		let mut file = fs::OpenOptions::new().read(true).open(path)?;
+		let mut header = CookHeader::zeroed();
+		file.read_exact(bytemuck::bytes_of_mut(&mut header))?;
+		if header.base_hash != key.base_hash || header.version != key.version {
+			return Err(io::Error::new(io::ErrorKind::NotFound, "cooked asset is stale"));
+		}
+		Ok(Self {
+			header: AssetHeader {
+				id: key.id,
+				ty: key.ty,
+				version: 0,
+			},
+			versioned: false,
+			content_hash: None,
+			read: FsPayload::new(header.flags, &[], file)?,
+		})
+	}
+
+	fn header(&mut self) -> AssetHeader { self.header }
+}
+
+impl FsPayload {
+	fn new(flags: u32, prefix: &'static [u8], file: fs::File) -> Result<Self, io::Error> {
+		Ok(if flags & FLAG_CHUNKED != 0 {
+			Self::Chunked(BufReader::new(file))
+		} else {
+			Self::Stream(Decoder::new(prefix.chain(file))?)
+		})
+	}
+}
+
+pub struct FsAssetWrite {
+	/// The file with just the header written, until the payload is started.
+	file: Option<fs::File>,
+	write: Option<Encoder<'static, fs::File>>,
+	/// Where the flags are in the header.
+	flags_offset: u64,
+	/// Where the hash of the payload is in the header, if it has one.
+	hash_offset: Option<u64>,
+	hash: Xxh3,
+	/// The temporary file being written, and where to move it once it's complete.
+	rename: Option<(PathBuf, PathBuf)>,
+	/// Writing the payload failed, so the file must not be moved into place.
+	failed: bool,
+}
+impl Write for FsAssetWrite {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self.stream()?.write(buf) {
+			Ok(len) => {
+				self.hash.update(&buf[..len]);
+				Ok(len)
+			},
+			Err(e) => {
+				self.failed = true;
+				Err(e)
+			},
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match &mut self.write {
+			Some(x) => x.flush(),
+			None => Ok(()),
+		}
+	}
+}
+impl AssetWrite for FsAssetWrite {
+	fn write_chunks(&mut self, chunks: ChunkWriter) -> Result<(), io::Error> {
+		let Some(mut file) = self.file.take() else {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"asset payload was already written",
+			));
+		};
+		file.seek(SeekFrom::Start(self.flags_offset))?;
+		file.write_all(bytemuck::bytes_of(&FLAG_CHUNKED))?;
+		file.seek(SeekFrom::End(0))?;
+		let mut file = BufWriter::new(file);
+		chunks.write(&mut HashWrite(&mut file, &mut self.hash))?;
+		let file = file.into_inner().map_err(|e| e.into_error())?;
+		self.finish(file)
+	}
+}
+
+impl Drop for FsAssetWrite {
+	fn drop(&mut self) {
+		// Nothing was written, but the payload must still be a valid (empty) stream.
+		if self.file.is_some() {
+			let _ = self.stream();
+		}
+		if let Some(write) = self.write.take()
+			&& !self.failed
+			&& let Ok(file) = write.finish()
+		{
+			let _ = self.finish(file);
+		}
+		// The file was never completed.
+		if let Some((tmp, _)) = self.rename.take() {
+			let _ = fs::remove_file(tmp);
+		}
+	}
+}
+
+impl FsAssetWrite {
+	fn create<T: Asset>(path: &Path, id: AssetId<T>) -> Result<Self, io::Error> {
+		Self::create_header(
+			path,
+			AssetHeader {
+				id: id.to_untyped(),
+				ty: T::UUID,
+				version: T::VERSION,
+			},
+		)
+	}
+
+	fn create_header(path: &Path, header: AssetHeader) -> Result<Self, io::Error> {
+		let mut file = fs::OpenOptions::new()
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.open(path)?;
+		let header = FileHeader {
+			id: header.id,
+			ty: header.ty,
+			magic: HEADER_MAGIC,
+			format: FORMAT_VERSION,
+			version: header.version,
+			flags: 0,
+			content_hash: 0,
+		};
+		file.write_all(bytemuck::bytes_of(&header))?;
+		Ok(Self {
+			file: Some(file),
+			write: None,
+			flags_offset: std::mem::offset_of!(FileHeader, flags) as _,
+			hash_offset: Some(std::mem::offset_of!(FileHeader, content_hash) as _),
+			hash: Xxh3::new(),
+			rename: None,
+			failed: false,
+		})
+	}
+
+	/// Cooked assets are written to a temporary file that is only moved into place once it's complete, so a crash
+	/// can't leave a torn cache entry behind.
+	fn create_cooked(path: &Path, key: CookKey) -> Result<Self, io::Error> {
+		let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+		let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+		let header = CookHeader {
+			base_hash: key.base_hash,
+			version: key.version,
+			flags: 0,
+		};
+		file.write_all(bytemuck::bytes_of(&header))?;
+		Ok(Self {
+			file: Some(file),
+			write: None,
+			flags_offset: std::mem::offset_of!(CookHeader, flags) as _,
+			hash_offset: None,
+			hash: Xxh3::new(),
+			rename: Some((tmp, path.to_owned())),
+			failed: false,
+		})
+	}
+
+	/// The zstd stream of the payload, started on the first write.
+	fn stream(&mut self) -> Result<&mut Encoder<'static, fs::File>, io::Error> {
+		if let Some(file) = self.file.take() {
+			self.write = Some(Encoder::new(file, 5)?);
+		}
+		self.write
+			.as_mut()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "asset was written as chunks"))
+	}
+
+	/// Fill in the hash of the payload, and move the file into place if it was written elsewhere.
+	fn finish(&mut self, mut file: fs::File) -> Result<(), io::Error> {
+		if let Some(offset) = self.hash_offset {
+			file.seek(SeekFrom::Start(offset))?;
+			file.write_all(bytemuck::bytes_of(&self.hash.digest()))?;
+		}
+		drop(file);
+		if let Some((tmp, path)) = self.rename.take() {
+			fs::rename(tmp, path)?;
+		}
+		Ok(())
+	}
+}
+
+/// Hashes everything written through it.
+struct HashWrite<'a, W>(W, &'a mut Xxh3);
+
+impl<W: Write> Write for HashWrite<'_, W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let len = self.0.write(buf)?;
+		self.1.update(&buf[..len]);
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> io::Result<()> { self.0.flush() }
+}
+
+#[cfg(test)]
+mod tests {
+	use rad_core::{asset::BincodeAsset, ScopedEngine};
+
+	use super::*;
+
+	#[derive(Encode, Decode)]
+	struct Data(u32);
+
+	impl BincodeAsset for Data {
+		const UUID: Uuid = rad_core::uuid!("3e9c7b21-5d84-4f0a-b6c2-8a1f4e7d9c35");
+		const VERSION: u32 = 3;
+	}
+
+	const DATA: Uuid = <Data as Asset>::UUID;
+
+	/// Refers to `Data` assets.
+	#[derive(Encode, Decode)]
+	struct Refs(Vec<UntypedAssetId>);
+
+	impl BincodeAsset for Refs {
+		const REFERENCES_ASSETS: bool = true;
+		const UUID: Uuid = rad_core::uuid!("a4d18f6e-2b7c-4e93-8f05-c6e1b9a7d248");
+
+		fn dependencies(&self) -> Vec<(UntypedAssetId, Uuid)> { self.0.iter().map(|&x| (x, DATA)).collect() }
+	}
+
+	/// An asset system opened on an empty directory, which is removed when it is dropped.
+	struct TempSystem(Arc<FsAssetSystem>);
+
+	impl Deref for TempSystem {
+		type Target = FsAssetSystem;
+
+		fn deref(&self) -> &Self::Target { &self.0 }
+	}
+
+	impl Drop for TempSystem {
+		fn drop(&mut self) {
+			if let Some(root) = self.root().as_ref() {
+				let _ = fs::remove_dir_all(root);
+			}
+		}
+	}
+
+	fn system(name: &str) -> TempSystem {
+		let root = std::env::temp_dir().join(format!("rad-asset-{name}-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&root);
+		fs::create_dir_all(&root).unwrap();
+		TempSystem(FsAssetSystem::new(Some(root)))
+	}
+
+	fn engine(sys: &TempSystem) -> ScopedEngine {
+		let mut engine = Engine::builder();
+		engine.asset_source(sys.0.clone());
+		engine.asset::<Data>();
+		engine.asset::<Refs>();
+		engine.build_scoped()
+	}
+
+	fn create<T: Asset<Root = T>>(sys: &FsAssetSystem, path: &str, asset: &T) -> UntypedAssetId {
+		let id = AssetId::<T>::new();
+		asset.save(&mut sys.create(Path::new(path), id).unwrap()).unwrap();
+		id.to_untyped()
+	}
+
+	fn chunks() -> ChunkWriter {
+		let mut chunks = ChunkWriter::new();
+		chunks.add("a", b"first").unwrap();
+		chunks.add("", &[7; 1000]).unwrap();
+		chunks
+	}
+
+	fn check_chunks(read: Box<dyn AssetRead>) {
+		let chunks = read.chunks().unwrap();
+		assert_eq!(chunks.read_named("a").unwrap(), b"first");
+		assert_eq!(chunks.read(1).unwrap(), [7; 1000]);
+	}
+
+	#[test]
+	fn chunked() {
+		let sys = system("chunked");
+		let id = AssetId::<Data>::new();
+		let mut write = sys.create(Path::new("a"), id).unwrap();
+		write.write_chunks(chunks()).unwrap();
+		assert!(write.write_all(b"more").is_err());
+		drop(write);
+
+		// The flag is patched into the header without disturbing the rest of it.
+		let header = sys.header(id.to_untyped()).unwrap();
+		assert_eq!((header.id, header.ty, header.version), (id.to_untyped(), DATA, 3));
+		let read = sys.load(id.to_untyped(), DATA).unwrap();
+		assert_eq!(read.version(), Some(3));
+		check_chunks(read);
+
+		let key = CookKey {
+			id: id.to_untyped(),
+			ty: DATA,
+			base_hash: 1,
+			version: 2,
+		};
+		sys.store_cooked(key).unwrap().write_chunks(chunks()).unwrap();
+		check_chunks(sys.load_cooked(key).unwrap());
+		// Cooked data is moved into place once it's complete, without leaving the temporary file behind.
+		let cooked = sys.cooked_path(key.id, key.ty).unwrap();
+		assert_eq!(fs::read_dir(cooked.parent().unwrap()).unwrap().count(), 1);
+
+		// Migrated assets are rewritten as a stream, which holds the container.
+		let mut container = Vec::new();
+		chunks().write(&mut container).unwrap();
+		sys.rewrite(id.to_untyped(), DATA, 4)
+			.unwrap()
+			.write_all(&container)
+			.unwrap();
+		let read = sys.load(id.to_untyped(), DATA).unwrap();
+		assert_eq!(read.content_hash(), Some(xxhash_rust::xxh3::xxh3_64(&container)));
+		check_chunks(read);
+		assert_eq!(sys.lookup(Path::new("a")).unwrap().version, 4);
+
+		// Assets that were never written still hold an empty stream.
+		let empty = AssetId::<Data>::new();
+		drop(sys.create(Path::new("b"), empty).unwrap());
+		let mut bytes = Vec::new();
+		sys.load(empty.to_untyped(), DATA)
+			.unwrap()
+			.read_to_end(&mut bytes)
+			.unwrap();
+		assert!(bytes.is_empty());
+	}
+
+	#[test]
+	fn old_format() {
+		let sys = system("old_format");
+		let id = AssetId::<Data>::new();
+		let header = FileHeader {
+			id: id.to_untyped(),
+			ty: DATA,
+			magic: HEADER_MAGIC,
+			format: 2,
+			version: 3,
+			flags: 0,
+			content_hash: 0,
+		};
+		let mut data = bytemuck::bytes_of(&header)[..FileHeader::len(2)].to_vec();
+		zstd::stream::copy_encode(&b"payload"[..], &mut data, 5).unwrap();
+		fs::write(sys.root().as_ref().unwrap().join("old.radass"), data).unwrap();
+		sys.rescan();
+
+		let mut read = sys.load(id.to_untyped(), DATA).unwrap();
+		assert_eq!((read.version(), read.content_hash()), (Some(3), None));
+		let mut bytes = Vec::new();
+		read.read_to_end(&mut bytes).unwrap();
+		assert_eq!(bytes, b"payload");
+	}
+
+	#[test]
+	fn edit() {
+		let sys = system("edit");
+		let id = create(&sys, "a/b", &Data(1));
+		assert_eq!(sys.lookup(Path::new("a/b")).unwrap().id, id);
+
+		sys.rename(id, "c").unwrap();
+		assert_eq!(sys.rel_path_of(id).unwrap(), Path::new("a/c"));
+		assert!(sys.lookup(Path::new("a/b")).is_none());
+
+		sys.move_asset(id, Path::new("d/e")).unwrap();
+		assert_eq!(sys.dir().get_dir(Path::new("d")).unwrap().assets().count(), 1);
+		assert_eq!(sys.dir().get_dir(Path::new("a")).unwrap().assets().count(), 0);
+
+		let copy = sys.duplicate(id, Path::new("d/f")).unwrap();
+		assert_ne!(copy, id);
+		assert_eq!(sys.header(copy).unwrap().id, copy);
+		assert_eq!(
+			sys.duplicate(id, Path::new("d/f")).unwrap_err().kind(),
+			io::ErrorKind::AlreadyExists
+		);
+		assert_eq!(sys.assets().len(), 2);
+	}
+
+	#[test]
+	fn outside_project() {
+		let sys = system("outside");
+		let id = create(&sys, "a", &Data(1));
+		let outside = std::env::temp_dir().join(format!("rad-asset-outside-{}", std::process::id()));
+
+		let invalid = |x: io::Error| x.kind() == io::ErrorKind::InvalidInput;
+		for to in [
+			outside.as_path(),
+			Path::new("../a"),
+			Path::new("b/../../a"),
+			Path::new(""),
+		] {
+			assert!(invalid(sys.move_asset(id, to).unwrap_err()));
+			assert!(invalid(sys.duplicate(id, to).unwrap_err()));
+			assert!(sys.create(to, AssetId::<Data>::new()).is_err());
+		}
+		assert!(invalid(sys.rename(id, "../b").unwrap_err()));
+
+		assert_eq!(sys.rel_path_of(id).unwrap(), Path::new("a"));
+		assert_eq!(sys.assets().len(), 1);
+		assert!(!outside.with_added_extension("radass").exists());
+	}
+
+	#[test]
+	fn delete() {
+		let sys = system("delete");
+		let _engine = engine(&sys);
+		let data = create(&sys, "data", &Data(1));
+		let refs = create(&sys, "refs", &Refs(vec![data]));
+		assert_eq!(sys.dependents(data), [(refs, <Refs as Asset>::UUID)]);
+		// `Data` can't reference other assets, so it isn't loaded to look for references.
+		let stats = Engine::get().asset_stats();
+		let (_, data_stats) = stats.iter().find(|x| x.0 == std::any::type_name::<Data>()).unwrap();
+		assert_eq!(data_stats.loads, 0);
+
+		let err = sys.delete(data).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+		assert!(sys.path(data).unwrap().exists());
+
+		sys.delete(refs).unwrap();
+		sys.delete(data).unwrap();
+		assert!(sys.assets().is_empty());
+		assert!(!sys.root().as_ref().unwrap().join("data.radass").exists());
+	}
+}
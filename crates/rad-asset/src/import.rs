@@ -1,7 +1,7 @@
 use std::{
 	collections::hash_map::Entry,
-	fs::File,
-	io::{self, BufReader},
+	fs,
+	io,
 	path::{Path, PathBuf},
 	sync::{
 		Arc,
@@ -9,6 +9,7 @@ use std::{
 	},
 };
 
+use bincode::{Decode, Encode};
 use gltf::{
 	Document,
 	Gltf,
@@ -19,7 +20,11 @@ use gltf::{
 use parking_lot::Mutex;
 use rad_core::{
 	Engine,
-	asset::{Asset, aref::AssetId},
+	asset::{
+		Asset,
+		Uuid,
+		aref::{AssetId, UntypedAssetId},
+	},
 };
 use rad_graph::ash::vk;
 use rad_renderer::{
@@ -35,18 +40,58 @@ use rad_renderer::{
 	},
 	vek::{Mat4, Quaternion, Vec2, Vec3, Vec4},
 };
-use rad_world::{World, transform::Transform};
+use rad_world::{World, bevy_ecs::entity::Entity, prefab::PrefabId, transform::Transform};
 use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
 use rustc_hash::FxHashMap;
 use tracing::{Level, span, trace_span};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::fs::{FsAssetSystem, ImportInfo};
 
-use crate::asset::fs::FsAssetSystem;
+/// Settings of a glTF import, kept with the imported assets so that reimports use the same ones.
+#[derive(Clone, Default, Encode, Decode)]
+pub struct GltfImportSettings {
+	/// The project directory to import new assets into.
+	pub dir: PathBuf,
+}
+
+/// What an import did to the assets of previous imports of the same file.
+#[derive(Default)]
+pub struct ImportReport {
+	/// Assets that were not in the previous import.
+	pub added: Vec<UntypedAssetId>,
+	/// Assets from the previous import that were updated in place.
+	pub updated: Vec<UntypedAssetId>,
+	/// Assets from the previous import that were left as they are, since neither the file nor the settings changed.
+	pub unchanged: Vec<UntypedAssetId>,
+	/// Assets from the previous import that are no longer in the file. They are left alone, since worlds might still
+	/// reference them.
+	pub removed: Vec<UntypedAssetId>,
+}
 
 pub struct GltfImporter {
 	gltf: Document,
 	base: PathBuf,
 	buffers: Vec<buffer::Data>,
 	image_cache: Mutex<FxHashMap<(usize, bool), AssetId<ImageAsset>>>,
+	/// The file, relative to the project root if it is inside it.
+	source: PathBuf,
+	/// The hash of the file and its buffers.
+	source_hash: u64,
+	settings: GltfImportSettings,
+	keys: Keys,
+	/// The assets of the previous import of the file by key, and whether they are up to date with the file.
+	previous: FxHashMap<String, (UntypedAssetId, bool)>,
+	report: Mutex<ImportReport>,
+}
+
+/// The keys of the elements of the file, by index.
+struct Keys {
+	images: Vec<String>,
+	materials: Vec<String>,
+	meshes: Vec<String>,
+	nodes: Vec<String>,
+	scenes: Vec<String>,
 }
 
 #[derive(Copy, Clone)]
@@ -63,34 +108,48 @@ impl ImportProgress {
 }
 
 impl GltfImporter {
+	/// Start importing a file. Assets that were imported from the same file before are updated in place.
 	pub fn initialize(path: &Path) -> Option<Result<Self, io::Error>> {
+		Self::initialize_with(path, GltfImportSettings::default())
+	}
+
+	pub fn initialize_with(path: &Path, settings: GltfImportSettings) -> Option<Result<Self, io::Error>> {
 		if path.extension().and_then(|x| x.to_str()) != Some("gltf") {
 			return None;
 		}
 
 		let s = span!(Level::TRACE, "load gltf");
 		let _e = s.enter();
-		let base = path.parent().unwrap_or_else(|| Path::new("."));
-		let file = match File::open(path) {
-			Ok(x) => x,
-			Err(e) => return Some(Err(e)),
-		};
-		let Gltf { document: gltf, blob } = match Gltf::from_reader(BufReader::new(file)) {
-			Ok(x) => x,
-			Err(e) => return Some(Err(io::Error::other(e))),
-		};
+		Some(Self::new(path, settings))
+	}
 
-		Some(Self::new(base, gltf, blob).map_err(io::Error::other))
+	/// Start reimporting the file that `id` was imported from, with the same settings.
+	pub fn reimport(id: UntypedAssetId) -> Result<Self, io::Error> {
+		let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		let info = sys
+			.import_info(id)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("asset {id} was not imported")))?;
+		let (settings, _) =
+			bincode::decode_from_slice(&info.settings, bincode::config::standard()).map_err(io::Error::other)?;
+		let source = match sys.root().as_ref() {
+			Some(root) => root.join(&info.source),
+			None => info.source,
+		};
+		Self::initialize_with(&source, settings).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("`{}` is not a glTF file", source.display()),
+			)
+		})?
 	}
 
-	pub fn import(self, progress: impl Fn(f32) + Send + Sync) -> Result<(), io::Error> {
+	pub fn import(self, progress: impl Fn(f32) + Send + Sync) -> Result<ImportReport, io::Error> {
 		let total = ImportProgress {
 			materials: self.gltf.materials().count() as _,
 			meshes: self.gltf.meshes().count() as _,
 			scenes: self.gltf.scenes().count() as _,
 		};
 		progress(0.0);
-		let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
 
 		let prog = AtomicUsize::new(0);
 		let materials: Vec<_> = self.gltf.materials().collect();
@@ -101,7 +160,8 @@ impl GltfImporter {
 			materials
 				.into_par_iter()
 				.map(|mat| {
-					let id = AssetId::new();
+					let key = self.keys.materials[mat.index().unwrap()].clone();
+					let id = self.id::<Material>(&key);
 					let name = mat.name().map(|x| x.to_string()).unwrap_or_else(|| id.to_string());
 					let s = trace_span!("import material", name = name);
 					let _e = s.enter();
@@ -112,7 +172,7 @@ impl GltfImporter {
 						let _e = s.enter();
 						let m = mat.pbr_metallic_roughness();
 						let es = mat.emissive_strength().unwrap_or(1.0);
-						Material {
+						let material = Material {
 							base_color: m
 								.base_color_texture()
 								.map(|x| self.image(x.texture().source(), true))
@@ -133,8 +193,8 @@ impl GltfImporter {
 								.map(|x| self.image(x.texture().source(), true))
 								.transpose()?,
 							emissive_factor: mat.emissive_factor().map(|x| x * es).into(),
-						}
-						.save(&mut sys.create(&path, id)?)?;
+						};
+						self.save(key, id, &path, &material)?;
 					}
 
 					let old = prog.fetch_add(1, Ordering::Relaxed);
@@ -150,9 +210,10 @@ impl GltfImporter {
 					Ok::<_, io::Error>(id)
 				})
 				.chain(Some({
-					let id = AssetId::new();
+					let key = "material/default".to_string();
+					let id = self.id::<Material>(&key);
 					let path = Path::new("materials").join("default");
-					self.default_material().save(&mut sys.create(&path, id)?)?;
+					self.save(key, id, &path, &self.default_material())?;
 					Ok(id)
 				}))
 				.collect::<Result<_, _>>()?
@@ -167,19 +228,30 @@ impl GltfImporter {
 			meshes
 				.into_par_iter()
 				.map(|mesh| {
+					let mesh_key = &self.keys.meshes[mesh.index()];
 					let name = mesh.name().map(|x| x.to_string());
 					let s = trace_span!("import mesh", name = name);
 					let _e = s.enter();
 
-					let gltf_prims = mesh.primitives();
-					let prims = self.conv_to_meshes(mesh).map_err(io::Error::other)?;
-					let c = prims.len();
+					let gltf_prims: Vec<_> = mesh.primitives().collect();
+					let c = gltf_prims.len();
+					// Converting is the slow part, so it is skipped if none of the primitives need to be saved.
+					let prims: Vec<_> = if (0..c).all(|i| self.unchanged(&format!("{mesh_key}/{i}"))) {
+						(0..c).map(|_| None).collect()
+					} else {
+						self.conv_to_meshes(mesh)
+							.map_err(io::Error::other)?
+							.into_iter()
+							.map(Some)
+							.collect()
+					};
 					let ids = prims
 						.into_iter()
 						.enumerate()
 						.zip(gltf_prims)
 						.map(|((i, m), p)| {
-							let id = AssetId::new();
+							let key = format!("{mesh_key}/{i}");
+							let id = self.id::<Mesh>(&key);
 							let name = name.clone().unwrap_or_else(|| id.to_string());
 							let name = if c == 1 {
 								name.to_string()
@@ -190,7 +262,10 @@ impl GltfImporter {
 							let _e = s.enter();
 
 							let path = Path::new("meshes").join(&name);
-							m.save(&mut sys.create(&path, id)?)?;
+							match m {
+								Some(m) => self.save(key, id, &path, &m)?,
+								None => self.keep(id.to_untyped()),
+							}
 							Ok::<_, io::Error>((id, materials[p.material().index().unwrap_or(materials.len() - 1)]))
 						})
 						.collect::<Result<Vec<_>, _>>()?;
@@ -216,17 +291,18 @@ impl GltfImporter {
 			let _e = s.enter();
 
 			self.gltf.scenes().par_bridge().try_for_each(|scene| {
-				let id = AssetId::<World>::new();
+				let key = self.keys.scenes[scene.index()].clone();
+				let id = self.id::<World>(&key);
 				let name = scene.name().map(|x| x.to_string()).unwrap_or_else(|| id.to_string());
 				let s = trace_span!("import scene", name = name);
 				let _e = s.enter();
 
 				let path = Path::new("scenes").join(&name);
-				let scene = self.scene(&name, scene, &meshes).map_err(io::Error::other)?;
+				let scene = self.scene(id, &name, scene, &meshes).map_err(io::Error::other)?;
 				{
 					let s = trace_span!("save");
 					let _e = s.enter();
-					scene.save(&mut sys.create(&path, id)?)?;
+					self.save(key, id, &path, &scene)?;
 				}
 
 				let old = prog.fetch_add(1, Ordering::Relaxed);
@@ -239,12 +315,29 @@ impl GltfImporter {
 					.ratio(total),
 				);
 
-				Ok(())
-			})
+				Ok::<_, io::Error>(())
+			})?;
 		}
+
+		let mut report = self.report.into_inner();
+		report.removed = self
+			.previous
+			.into_values()
+			.map(|(x, _)| x)
+			.filter(|x| !report.updated.contains(x) && !report.unchanged.contains(x))
+			.collect();
+		Ok(report)
 	}
 
-	fn new(base: &Path, gltf: Document, mut blob: Option<Vec<u8>>) -> Result<Self, gltf::Error> {
+	fn new(path: &Path, settings: GltfImportSettings) -> Result<Self, io::Error> {
+		let path = path.canonicalize()?;
+		let base = path.parent().unwrap_or_else(|| Path::new("."));
+		let bytes = fs::read(&path)?;
+		let Gltf {
+			document: gltf,
+			mut blob,
+		} = Gltf::from_slice(&bytes).map_err(io::Error::other)?;
+
 		let buffers = gltf
 			.buffers()
 			.map(|buffer| {
@@ -258,50 +351,152 @@ impl GltfImporter {
 				}
 				Ok(data)
 			})
-			.collect::<Result<Vec<_>, _>>()?;
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(io::Error::other)?;
+
+		let mut hash = Xxh3::new();
+		hash.update(&bytes);
+		for buffer in buffers.iter() {
+			hash.update(buffer);
+		}
+
+		let source_hash = hash.digest();
+
+		let keys = Keys {
+			images: keys(
+				"image",
+				gltf.images().map(|x| {
+					x.name().or(match x.source() {
+						Source::Uri { uri, .. } => Some(uri),
+						_ => None,
+					})
+				}),
+			),
+			materials: keys("material", gltf.materials().map(|x| x.name())),
+			meshes: keys("mesh", gltf.meshes().map(|x| x.name())),
+			nodes: keys("node", gltf.nodes().map(|x| x.name())),
+			scenes: keys("scene", gltf.scenes().map(|x| x.name())),
+		};
+
+		let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		// Kept relative to the project, so that moving the project along with the file does not break reimports.
+		let source = sys
+			.root()
+			.as_ref()
+			.and_then(|root| Some(path.strip_prefix(root.canonicalize().ok()?).ok()?.to_path_buf()))
+			.unwrap_or_else(|| path.clone());
+		let encoded = bincode::encode_to_vec(&settings, bincode::config::standard()).map_err(io::Error::other)?;
+		let previous = sys
+			.imported_from(&source)
+			.into_iter()
+			.map(|(id, info)| {
+				let unchanged = info.source_hash == source_hash && info.settings == encoded;
+				(info.key, (id, unchanged))
+			})
+			.collect();
+
 		Ok(Self {
 			gltf,
 			base: base.to_path_buf(),
 			buffers,
 			image_cache: Mutex::new(FxHashMap::default()),
+			source,
+			source_hash,
+			settings,
+			keys,
+			previous,
+			report: Mutex::new(ImportReport::default()),
 		})
 	}
 
+	/// The ID to import the part of the file identified by `key` as, which is kept from the previous import.
+	fn id<T: Asset>(&self, key: &str) -> AssetId<T> {
+		self.previous
+			.get(key)
+			.map(|&(x, _)| unsafe { x.typed() })
+			.unwrap_or_default()
+	}
+
+	/// Whether the asset imported from the part of the file identified by `key` is up to date, and doesn't need to be
+	/// imported again.
+	fn unchanged(&self, key: &str) -> bool { self.previous.get(key).is_some_and(|&(_, x)| x) }
+
+	/// Leave an up to date asset from the previous import as it is.
+	fn keep(&self, id: UntypedAssetId) { self.report.lock().unchanged.push(id); }
+
+	/// Save an imported asset. Assets from the previous import are overwritten wherever they were moved to.
+	fn save<T: Asset>(&self, key: String, id: AssetId<T>, path: &Path, asset: &T) -> Result<(), io::Error> {
+		if self.unchanged(&key) {
+			self.keep(id.to_untyped());
+			return Ok(());
+		}
+
+		let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		let existing = sys.rel_path_of(id.to_untyped());
+		let path = existing.clone().unwrap_or_else(|| self.settings.dir.join(path));
+		asset.save(&mut sys.create(&path, id)?)?;
+		sys.set_import_info(
+			id.to_untyped(),
+			&ImportInfo {
+				source: self.source.clone(),
+				source_hash: self.source_hash,
+				settings: bincode::encode_to_vec(&self.settings, bincode::config::standard())
+					.map_err(io::Error::other)?,
+				key,
+			},
+		)?;
+
+		let mut report = self.report.lock();
+		if existing.is_some() {
+			report.updated.push(id.to_untyped());
+		} else {
+			report.added.push(id.to_untyped());
+		}
+		Ok(())
+	}
+
 	fn scene(
-		&self, name: &str, scene: gltf::Scene, meshes: &[Vec<(AssetId<Mesh>, AssetId<Material>)>],
+		&self, id: AssetId<World>, name: &str, scene: gltf::Scene, meshes: &[Vec<(AssetId<Mesh>, AssetId<Material>)>],
 	) -> Result<World, gltf::Error> {
 		let s = span!(Level::INFO, "importing scene", name = name);
 		let _e = s.enter();
 
 		let mut out = World::new();
 		for node in scene.nodes() {
-			self.node(node, Mat4::identity(), meshes, &mut out);
+			self.node(id, node, None, meshes, &mut out);
 		}
 
 		Ok(out)
 	}
 
 	fn node(
-		&self, node: gltf::Node, transform: Mat4<f32>, meshes: &[Vec<(AssetId<Mesh>, AssetId<Material>)>],
-		out: &mut World,
+		&self, scene: AssetId<World>, node: gltf::Node, parent: Option<Entity>,
+		meshes: &[Vec<(AssetId<Mesh>, AssetId<Material>)>], out: &mut World,
 	) {
 		// let name = node.name().unwrap_or("unnamed node").to_string();
 
-		let this_transform = Mat4::from_col_arrays(node.transform().matrix());
-		let transform = transform * this_transform;
+		let mut transform = Mat4::from_col_arrays(node.transform().matrix());
+		if parent.is_none() {
+			// gltf is X- right, Y up, Z in
+			// we are X right, Y in, Z up
+			// Children are relative to their parent, so only the roots need to change basis.
+			let basis_change = Mat4::new(
+				1.0, 0.0, 0.0, 0.0, //
+				0.0, 0.0, -1.0, 0.0, //
+				0.0, 1.0, 0.0, 0.0, //
+				0.0, 0.0, 0.0, 1.0, //
+			);
+			transform = basis_change * transform;
+		}
 
 		let mut entity = out.spawn_empty();
-
-		// gltf is X- right, Y up, Z in
-		// we are X right, Y in, Z up
-		let basis_change = Mat4::new(
-			1.0, 0.0, 0.0, 0.0, //
-			0.0, 0.0, -1.0, 0.0, //
-			0.0, 1.0, 0.0, 0.0, //
-			0.0, 0.0, 0.0, 1.0, //
-		);
+		// Derived from the scene and node, so prefab overrides of the entity survive reimporting.
+		let mut hash = Xxh3::new();
+		hash.update(bytemuck::bytes_of(&scene.to_untyped()));
+		hash.update(self.keys.nodes[node.index()].as_bytes());
+		entity.insert(PrefabId(Uuid::from_u128(hash.digest128())));
 		let (p, r, s) = gltf::scene::Transform::Matrix {
-			matrix: (basis_change * transform).into_col_arrays(),
+			matrix: transform.into_col_arrays(),
 		}
 		.decomposed();
 		entity.insert(Transform {
@@ -332,18 +527,30 @@ impl GltfImporter {
 			});
 		}
 
+		let entity = entity.id();
+		out.set_parent(entity, parent).unwrap();
 		for child in node.children() {
-			self.node(child, transform, meshes, out);
+			self.node(scene, child, Some(entity), meshes, out);
 		}
 	}
 
 	fn image(&self, image: gltf::Image, srgb: bool) -> Result<AssetId<ImageAsset>, io::Error> {
+		let key = format!(
+			"{}/{}",
+			self.keys.images[image.index()],
+			if srgb { "srgb" } else { "linear" }
+		);
 		let mut cache = self.image_cache.lock();
 		let id = match cache.entry((image.index(), srgb)) {
 			Entry::Occupied(x) => return Ok(*x.get()),
-			Entry::Vacant(x) => *x.insert(AssetId::new()),
+			Entry::Vacant(x) => *x.insert(self.id(&key)),
 		};
 		drop(cache);
+		// Decoding is the slow part, so it is skipped if the image doesn't need to be saved.
+		if self.unchanged(&key) {
+			self.keep(id.to_untyped());
+			return Ok(id);
+		}
 
 		let name = image
 			.name()
@@ -386,10 +593,9 @@ impl GltfImporter {
 		}
 
 		{
-			let sys: &Arc<FsAssetSystem> = Engine::get().asset_source();
 			let s = trace_span!("save");
 			let _e = s.enter();
-			ImageAsset {
+			let asset = ImageAsset {
 				size: Vec3::new(d.width, d.height, 1),
 				format: match (d.format, srgb) {
 					(image::Format::R8, false) => vk::Format::R8_UNORM,
@@ -407,8 +613,8 @@ impl GltfImporter {
 				}
 				.as_raw(),
 				data: d.pixels,
-			}
-			.save(&mut sys.create(&path, id)?)?;
+			};
+			self.save(key, id, &path, &asset)?;
 		}
 
 		Ok::<_, io::Error>(id)
@@ -470,3 +676,21 @@ impl GltfImporter {
 		Ok(out)
 	}
 }
+
+/// Identify the elements of one kind in a file by their names, so that they are matched up across reimports even if
+/// the file is reordered. Elements without a name, or with one shared by another element, fall back to their index.
+fn keys<'a>(kind: &str, names: impl Iterator<Item = Option<&'a str>>) -> Vec<String> {
+	let names: Vec<_> = names.collect();
+	let mut counts = FxHashMap::default();
+	for &name in names.iter().flatten() {
+		*counts.entry(name).or_insert(0) += 1;
+	}
+	names
+		.into_iter()
+		.enumerate()
+		.map(|(i, name)| match name {
+			Some(name) if counts[name] == 1 => format!("{kind}/name/{name}"),
+			_ => format!("{kind}/index/{i}"),
+		})
+		.collect()
+}
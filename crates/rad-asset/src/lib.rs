@@ -0,0 +1,4 @@
+#![feature(path_add_extension)]
+
+pub mod fs;
+pub mod import;
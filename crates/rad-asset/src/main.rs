@@ -0,0 +1,316 @@
+use std::{
+	io,
+	path::{Path, PathBuf},
+	process::ExitCode,
+	sync::Arc,
+};
+
+use clap::{Parser, Subcommand};
+use rad_asset::{
+	fs::FsAssetSystem,
+	import::{GltfImporter, ImportReport},
+};
+use rad_core::{
+	Engine,
+	asset::{
+		Asset,
+		AssetError,
+		Uuid,
+		aref::{AssetId, UntypedAssetId},
+	},
+};
+use rad_graph::ash::vk;
+use rad_renderer::{
+	RendererAssetsModule,
+	assets::{
+		image::ImageAsset,
+		material::Material,
+		mesh::{Mesh, virtual_mesh::VirtualMesh},
+	},
+};
+use rad_world::World;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tracing_subscriber::EnvFilter;
+
+/// Manage the assets of a project without launching the editor.
+#[derive(Parser)]
+struct Cli {
+	/// The project directory.
+	project: PathBuf,
+	#[command(subcommand)]
+	command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Import glTF files into the project. Files that were imported before update their existing assets.
+	Import { files: Vec<PathBuf> },
+	/// Reimport the file an asset was imported from.
+	Reimport { id: UntypedAssetId },
+	/// List every asset in the project.
+	List,
+	/// Cook an asset, or every asset if no ID is given.
+	Cook {
+		id: Option<UntypedAssetId>,
+		/// Throw away existing cooked data first.
+		#[arg(long)]
+		force: bool,
+	},
+	/// Check that every asset in the project decodes.
+	Validate,
+	/// Print the header and a summary of an asset.
+	Dump { id: UntypedAssetId },
+	/// List the assets an asset references.
+	Deps { id: UntypedAssetId },
+	/// List the assets that reference an asset.
+	Refs { id: UntypedAssetId },
+	/// Bake assets into a pack. If roots are given, only they and the assets they reference are included.
+	Bake { out: PathBuf, roots: Vec<UntypedAssetId> },
+	/// Write a world out in its text form.
+	ExportWorld { id: UntypedAssetId, out: PathBuf },
+	/// Convert a text world into a world asset. A world that already exists at `path` is overwritten in place.
+	ImportWorld { file: PathBuf, path: PathBuf },
+}
+
+fn main() -> ExitCode {
+	tracing_subscriber::fmt()
+		.with_env_filter(EnvFilter::from_env("RADLOG"))
+		.with_writer(io::stderr)
+		.init();
+
+	let cli = Cli::parse();
+	if !cli.project.is_dir() {
+		eprintln!("project `{}` is not a directory", cli.project.display());
+		return ExitCode::FAILURE;
+	}
+
+	// Only the asset types, so that no GPU is needed.
+	let mut engine = Engine::builder().module::<RendererAssetsModule>();
+	engine.asset_source(FsAssetSystem::new(Some(cli.project)));
+	engine.build();
+
+	let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+
+	let res = match cli.command {
+		Command::Import { files } => import(&files),
+		Command::Reimport { id } => reimport(id),
+		Command::List => list(fs),
+		Command::Cook { id, force } => cook(fs, id, force),
+		Command::Validate => validate(fs),
+		Command::Dump { id } => dump(fs, id),
+		Command::Deps { id } => deps(fs, id),
+		Command::Refs { id } => refs(fs, id),
+		Command::Bake { out, roots } => bake(fs, &out, &roots),
+		Command::ExportWorld { id, out } => export_world(fs, id, &out),
+		Command::ImportWorld { file, path } => import_world(fs, &file, &path),
+	};
+	match res {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("error: {e}");
+			ExitCode::FAILURE
+		},
+	}
+}
+
+fn name(ty: Uuid) -> &'static str { Engine::get().asset_name(ty).unwrap_or("unknown") }
+
+fn import(files: &[PathBuf]) -> Result<(), io::Error> {
+	for file in files {
+		let importer = GltfImporter::initialize(file).ok_or_else(|| {
+			io::Error::new(io::ErrorKind::InvalidInput, format!("`{}` is not a glTF file", file.display()))
+		})??;
+		let report = importer.import(|x| eprint!("\rimporting {}: {:.2}%", file.display(), x * 100.0))?;
+		eprintln!();
+		print_report(&report);
+	}
+	Ok(())
+}
+
+fn reimport(id: UntypedAssetId) -> Result<(), io::Error> {
+	let report = GltfImporter::reimport(id)?.import(|x| eprint!("\rreimporting: {:.2}%", x * 100.0))?;
+	eprintln!();
+	print_report(&report);
+	Ok(())
+}
+
+fn print_report(report: &ImportReport) {
+	println!(
+		"{} added, {} updated, {} removed",
+		report.added.len(),
+		report.updated.len(),
+		report.removed.len()
+	);
+	for id in report.removed.iter() {
+		println!("removed from source: {id}");
+	}
+}
+
+fn list(fs: &FsAssetSystem) -> Result<(), io::Error> {
+	let mut assets: Vec<_> = fs
+		.assets()
+		.into_iter()
+		.map(|(id, ty)| (fs.path(id).unwrap_or_default(), id, ty))
+		.collect();
+	assets.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+	for (path, id, ty) in assets {
+		println!("{id}  {ty}  {:<40}  {}", name(ty), path.display());
+	}
+	Ok(())
+}
+
+fn cook(fs: &FsAssetSystem, id: Option<UntypedAssetId>, force: bool) -> Result<(), io::Error> {
+	let assets = match id {
+		Some(id) => vec![(id, fs.header(id)?.ty)],
+		None => fs.assets(),
+	};
+
+	let failed = assets
+		.into_par_iter()
+		.flat_map_iter(|(id, ty)| Engine::get().cooked_from(ty).into_iter().map(move |x| (id, x)))
+		.filter(|&(id, ty)| {
+			let res = if force {
+				Engine::get().invalidate_cooked(id).map_err(AssetError::from)
+			} else {
+				Ok(())
+			};
+			match res.and_then(|_| Engine::get().cook_untyped(id, ty)) {
+				Ok(()) => {
+					println!("cooked {id} ({})", name(ty));
+					false
+				},
+				Err(e) => {
+					eprintln!("failed to cook {id} ({}): {e}", name(ty));
+					true
+				},
+			}
+		})
+		.count();
+	if failed > 0 {
+		return Err(io::Error::other(format!("{failed} assets failed to cook")));
+	}
+	Ok(())
+}
+
+fn validate(fs: &FsAssetSystem) -> Result<(), io::Error> {
+	let assets = fs.assets();
+	let total = assets.len();
+	let failed = assets
+		.into_par_iter()
+		.filter(|&(id, ty)| match Engine::get().check_asset(id, ty) {
+			Ok(()) => false,
+			Err(e) => {
+				let path = fs.path(id).unwrap_or_default();
+				eprintln!("{id} ({}) at {}: {e}", name(ty), path.display());
+				true
+			},
+		})
+		.count();
+	println!("{} of {total} assets are valid", total - failed);
+	if failed > 0 {
+		return Err(io::Error::other(format!("{failed} assets failed to decode")));
+	}
+	Ok(())
+}
+
+fn print_assets(fs: &FsAssetSystem, assets: Vec<(UntypedAssetId, Uuid)>) {
+	for (id, ty) in assets {
+		let path = fs.path(id).unwrap_or_default();
+		println!("{id}  {:<40}  {}", name(ty), path.display());
+	}
+}
+
+fn deps(fs: &FsAssetSystem, id: UntypedAssetId) -> Result<(), io::Error> {
+	print_assets(fs, fs.dependencies(id)?);
+	Ok(())
+}
+
+fn refs(fs: &FsAssetSystem, id: UntypedAssetId) -> Result<(), io::Error> {
+	print_assets(fs, fs.dependents(id));
+	Ok(())
+}
+
+fn bake(fs: &FsAssetSystem, out: &Path, roots: &[UntypedAssetId]) -> Result<(), io::Error> {
+	let assets = if roots.is_empty() {
+		fs.assets()
+	} else {
+		let roots = roots
+			.iter()
+			.map(|&id| fs.header(id).map(|x| (id, x.ty)))
+			.collect::<Result<Vec<_>, _>>()?;
+		Engine::get().reachable(roots)?
+	};
+	let count = Engine::get().bake(out, assets)?;
+	println!("baked {count} entries into {}", out.display());
+	Ok(())
+}
+
+fn export_world(fs: &FsAssetSystem, id: UntypedAssetId, out: &Path) -> Result<(), io::Error> {
+	if fs.header(id)?.ty != World::UUID {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{id} is not a world")));
+	}
+	let world: World = unsafe { Engine::get().load_asset(id.typed())? };
+	std::fs::write(out, world.to_text()?)?;
+	println!("exported {id} to {}", out.display());
+	Ok(())
+}
+
+fn import_world(fs: &FsAssetSystem, file: &Path, path: &Path) -> Result<(), io::Error> {
+	let world = World::from_text(&std::fs::read_to_string(file)?)?;
+	let id = match fs.lookup(path) {
+		Some(x) if x.ty == World::UUID => unsafe { x.id.typed() },
+		Some(_) => {
+			return Err(io::Error::new(
+				io::ErrorKind::AlreadyExists,
+				format!("`{}` is not a world", path.display()),
+			));
+		},
+		None => AssetId::new(),
+	};
+	world.save(&mut fs.create(path, id)?)?;
+	println!("imported {} as {id}", file.display());
+	Ok(())
+}
+
+fn dump(fs: &FsAssetSystem, id: UntypedAssetId) -> Result<(), io::Error> {
+	let header = fs.header(id)?;
+	println!("id:      {}", header.id);
+	println!("type:    {} ({})", header.ty, name(header.ty));
+	println!("version: {}", header.version);
+	println!("path:    {}", fs.path(id).as_deref().unwrap_or(Path::new("")).display());
+
+	let e = Engine::get();
+	let ty = header.ty;
+	unsafe {
+		if ty == Mesh::UUID {
+			let mesh: Mesh = e.load_asset(id.typed())?;
+			println!("vertices:  {}", mesh.vertices.len());
+			println!("triangles: {}", mesh.indices.len() / 3);
+			let virt: VirtualMesh = e.cook_asset(id.typed())?;
+			println!("meshlets:  {}", virt.meshlets.len());
+			println!("bvh nodes: {} (depth {})", virt.bvh.len(), virt.bvh_depth);
+		} else if ty == ImageAsset::UUID {
+			let image: ImageAsset = e.load_asset(id.typed())?;
+			println!("size:   {}x{}x{}", image.size.x, image.size.y, image.size.z);
+			println!("format: {:?}", vk::Format::from_raw(image.format));
+			println!("data:   {} bytes", image.data.len());
+		} else if ty == Material::UUID {
+			let mat: Material = e.load_asset(id.typed())?;
+			let tex = |x: Option<_>| x.map(|x| format!("{x}")).unwrap_or_else(|| "none".to_string());
+			println!("base color:         {} * {:?}", tex(mat.base_color), mat.base_color_factor);
+			println!(
+				"metallic roughness: {} * ({}, {})",
+				tex(mat.metallic_roughness),
+				mat.metallic_factor,
+				mat.roughness_factor
+			);
+			println!("normal:             {}", tex(mat.normal));
+			println!("emissive:           {} * {:?}", tex(mat.emissive), mat.emissive_factor);
+		} else if ty == World::UUID {
+			let world: World = e.load_asset(id.typed())?;
+			println!("entities: {}", world.entities().len());
+		}
+	}
+
+	Ok(())
+}
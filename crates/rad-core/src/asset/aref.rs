@@ -1,13 +1,24 @@
 use std::{
+	any::Any,
+	collections::BTreeMap,
 	fmt::{Debug, Display},
 	hash::Hash,
 	io,
 	marker::PhantomData,
 	ops::Deref,
-	sync::{Arc, OnceLock, RwLock},
+	str::FromStr,
+	sync::{
+		atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+		Arc,
+		Mutex,
+		OnceLock,
+		RwLock,
+		Weak,
+	},
+	time::Instant,
 };
 
-use bevy_reflect::{Reflect, ReflectDeserialize, ReflectSerialize, TypePath};
+use bevy_reflect::{reflect_trait, Reflect, ReflectDeserialize, ReflectSerialize, TypePath};
 use bincode::{Decode, Encode};
 use bytemuck::{Pod, Zeroable};
 use rustc_hash::FxHashMap;
@@ -15,8 +26,13 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+	asset::{
+		stats::{ViewCounters, ViewStats},
+		Asset,
+		AssetError,
+		AssetView,
+	},
 	Engine,
-	asset::{Asset, AssetView},
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Pod, Zeroable, Encode, Decode, Reflect)]
@@ -37,15 +53,32 @@ impl Display for UntypedAssetId {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}", self.0) }
 }
 
+impl FromStr for UntypedAssetId {
+	type Err = uuid::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> { Uuid::parse_str(s).map(Self) }
+}
+
 #[derive(Serialize, Deserialize, Pod, Zeroable, Encode, Decode, Reflect)]
 #[serde(transparent)]
 #[repr(transparent)]
-#[reflect(opaque, Serialize, Deserialize)]
+#[reflect(opaque, Serialize, Deserialize, AssetRef)]
 #[reflect(type_path = false)]
-#[reflect(where T: Sized)]
+#[reflect(where T: Asset)]
 // TODO: support procedural assets without having to go through the asset system.
 pub struct AssetId<T>(UntypedAssetId, PhantomData<fn() -> T>);
 
+/// Lets reflection find the assets referenced by a value, such as the components of a world.
+#[reflect_trait]
+pub trait AssetRef {
+	/// The referenced asset, and its type.
+	fn asset(&self) -> (UntypedAssetId, Uuid);
+}
+
+impl<T: Asset> AssetRef for AssetId<T> {
+	fn asset(&self) -> (UntypedAssetId, Uuid) { (self.0, T::UUID) }
+}
+
 impl<T: 'static> TypePath for AssetId<T> {
 	fn type_path() -> &'static str { std::any::type_name::<T>() }
 
@@ -82,12 +115,51 @@ impl<T> AssetId<T> {
 	pub fn to_untyped(self) -> UntypedAssetId { self.0 }
 }
 
+/// The load state of an asset view.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum LoadState {
+	Unloaded,
+	Loading,
+	Loaded,
+	Failed,
+}
+
+impl LoadState {
+	fn from_u8(x: u8) -> Self {
+		match x {
+			0 => Self::Unloaded,
+			1 => Self::Loading,
+			2 => Self::Loaded,
+			_ => Self::Failed,
+		}
+	}
+}
+
 struct ARefData<T: AssetView> {
 	id: AssetId<<T::Base as Asset>::Root>,
+	state: AtomicU8,
+	generation: AtomicU64,
+	error: Mutex<Option<AssetError>>,
 	data: OnceLock<T>,
 }
 
-// TODO: support weak references.
+impl<T: AssetView> ARefData<T> {
+	fn new(id: AssetId<<T::Base as Asset>::Root>) -> Self {
+		Self {
+			id,
+			state: AtomicU8::new(LoadState::Unloaded as _),
+			generation: AtomicU64::new(0),
+			error: Mutex::new(None),
+			data: OnceLock::new(),
+		}
+	}
+
+	fn state(&self) -> LoadState { LoadState::from_u8(self.state.load(Ordering::Acquire)) }
+
+	fn set_state(&self, state: LoadState) { self.state.store(state as _, Ordering::Release); }
+}
+
 /// A unique reference to an asset view.
 pub struct ARef<T: AssetView> {
 	inner: Arc<ARefData<T>>,
@@ -98,16 +170,45 @@ impl<T: AssetView> ARef<T> {
 	pub fn unloaded(id: AssetId<<T::Base as Asset>::Root>) -> Self { Engine::get().assets.cache::<T>().unloaded(id) }
 
 	/// Create a loaded asset view reference. This function will block until the asset view is loaded.
-	pub fn loaded(id: AssetId<<T::Base as Asset>::Root>) -> Result<LARef<T>, io::Error> {
+	pub fn loaded(id: AssetId<<T::Base as Asset>::Root>) -> Result<LARef<T>, AssetError> {
 		Engine::get().assets.cache::<T>().loaded(id)
 	}
 
+	/// Create loaded references to many asset views, loading them in parallel. See `AssetCache::loaded_many`.
+	pub fn loaded_many(
+		ids: &[AssetId<<T::Base as Asset>::Root>], progress: impl Fn(usize, usize) + Sync,
+	) -> Vec<Result<LARef<T>, AssetError>> {
+		Engine::get().assets.cache::<T>().loaded_many(ids, progress)
+	}
+
 	/// Load the asset view. This function will block until the asset view is loaded.
-	pub fn load(self) -> Result<LARef<T>, io::Error> {
+	pub fn load(self) -> Result<LARef<T>, AssetError> {
 		Engine::get().assets.cache::<T>().load(&self.inner)?;
 		Ok(LARef { inner: self })
 	}
 
+	/// Start loading the asset view in the background, returning immediately. Does nothing if the view is already
+	/// loaded or loading. Failed loads are retried.
+	pub fn request(&self) { Engine::get().assets.cache::<T>().request(&self.inner); }
+
+	pub fn state(&self) -> LoadState {
+		if self.inner.data.get().is_some() {
+			LoadState::Loaded
+		} else {
+			self.inner.state()
+		}
+	}
+
+	/// Get the loaded asset view, if it has finished loading.
+	pub fn try_loaded(&self) -> Option<LARef<T>> { self.inner.data.get().map(|_| LARef { inner: self.clone() }) }
+
+	/// Get the error from the last failed load, if the view failed to load.
+	pub fn error(&self) -> Option<AssetError> { self.inner.error.lock().unwrap().clone() }
+
+	/// Incremented every time the asset changes after the view was loaded. If the view could not be updated in
+	/// place, it has been detached from the cache and must be reacquired to see the change.
+	pub fn generation(&self) -> u64 { self.inner.generation.load(Ordering::Acquire) }
+
 	pub fn id(&self) -> AssetId<<T::Base as Asset>::Root> { self.inner.id }
 }
 
@@ -136,6 +237,9 @@ impl<T: AssetView> LARef<T> {
 	pub fn into_inner(self) -> ARef<T> { self.inner }
 
 	pub fn id(&self) -> AssetId<<T::Base as Asset>::Root> { self.inner.id() }
+
+	/// See `ARef::generation`.
+	pub fn generation(&self) -> u64 { self.inner.generation() }
 }
 
 impl<T: AssetView> Clone for LARef<T> {
@@ -161,9 +265,83 @@ impl<T: AssetView> Deref for LARef<T> {
 	fn deref(&self) -> &Self::Target { unsafe { self.inner.inner.data.get().unwrap_unchecked() } }
 }
 
+/// Views kept alive after their last reference is dropped, so that they don't have to be reloaded if they are used
+/// again soon. The least recently used views are evicted when the total size goes over budget.
+struct Retained<T: AssetView> {
+	size: usize,
+	tick: u64,
+	entries: FxHashMap<AssetId<<T::Base as Asset>::Root>, RetainedEntry<T>>,
+	lru: BTreeMap<u64, AssetId<<T::Base as Asset>::Root>>,
+}
+
+struct RetainedEntry<T: AssetView> {
+	data: Arc<ARefData<T>>,
+	size: usize,
+	tick: u64,
+}
+
+impl<T: AssetView> Retained<T> {
+	fn new() -> Self {
+		Self {
+			size: 0,
+			tick: 0,
+			entries: FxHashMap::default(),
+			lru: BTreeMap::new(),
+		}
+	}
+
+	fn touch(&mut self, data: &Arc<ARefData<T>>, size: usize) {
+		let tick = self.tick;
+		self.tick += 1;
+		match self.entries.get_mut(&data.id) {
+			Some(entry) => {
+				self.lru.remove(&entry.tick);
+				entry.tick = tick;
+			},
+			None => {
+				self.entries.insert(
+					data.id,
+					RetainedEntry {
+						data: data.clone(),
+						size,
+						tick,
+					},
+				);
+				self.size += size;
+			},
+		}
+		self.lru.insert(tick, data.id);
+	}
+
+	fn remove(&mut self, id: AssetId<<T::Base as Asset>::Root>) -> Option<Arc<ARefData<T>>> {
+		let entry = self.entries.remove(&id)?;
+		self.lru.remove(&entry.tick);
+		self.size -= entry.size;
+		Some(entry.data)
+	}
+
+	fn evict(&mut self, budget: usize) -> Vec<Arc<ARefData<T>>> {
+		let mut evicted = Vec::new();
+		while self.size > budget
+			&& let Some((_, id)) = self.lru.pop_first()
+		{
+			let entry = self.entries.remove(&id).unwrap();
+			self.size -= entry.size;
+			evicted.push(entry.data);
+		}
+		evicted
+	}
+}
+
 pub struct AssetCache<T: AssetView> {
 	context: T::Ctx,
-	loaded: RwLock<FxHashMap<AssetId<<T::Base as Asset>::Root>, Arc<ARefData<T>>>>,
+	loaded: RwLock<FxHashMap<AssetId<<T::Base as Asset>::Root>, Weak<ARefData<T>>>>,
+	/// The size of `loaded` at which dead entries are next swept out.
+	sweep_at: AtomicUsize,
+	/// The budget for `retained`, in bytes. `usize::MAX` if views aren't retained.
+	budget: AtomicUsize,
+	retained: Mutex<Retained<T>>,
+	counters: ViewCounters,
 }
 
 impl<T: AssetView> Default for AssetCache<T> {
@@ -175,40 +353,217 @@ impl<T: AssetView> AssetCache<T> {
 		Self {
 			context: T::Ctx::default(),
 			loaded: RwLock::new(FxHashMap::default()),
+			sweep_at: AtomicUsize::new(64),
+			budget: AtomicUsize::new(usize::MAX),
+			retained: Mutex::new(Retained::new()),
+			counters: ViewCounters::default(),
 		}
 	}
 
+	/// Keep up to `budget` bytes of recently used views alive after they are no longer referenced. `None` frees views
+	/// as soon as their last reference is dropped.
+	pub fn set_budget(&self, budget: Option<usize>) {
+		self.budget.store(budget.unwrap_or(usize::MAX), Ordering::Relaxed);
+		let evicted = self.retained.lock().unwrap().evict(budget.unwrap_or(0));
+		drop(evicted);
+	}
+
+	pub fn context(&self) -> &T::Ctx { &self.context }
+
+	/// The total size of the views currently kept alive by the budget.
+	pub fn retained_size(&self) -> usize { self.retained.lock().unwrap().size }
+
+	pub fn stats(&self) -> ViewStats {
+		let (live, live_size) = self
+			.loaded
+			.read()
+			.unwrap()
+			.values()
+			.filter_map(Weak::upgrade)
+			.filter_map(|x| x.data.get().map(|x| x.size()))
+			.fold((0, 0), |(count, size), x| (count + 1, size + x));
+		self.counters.get(live, live_size)
+	}
+
 	pub fn unloaded(&self, id: AssetId<<T::Base as Asset>::Root>) -> ARef<T> {
 		let read = self.loaded.read().unwrap();
-		match read.get(&id) {
-			Some(data) => ARef { inner: data.clone() },
-			None => {
-				drop(read);
-				let mut write = self.loaded.write().unwrap();
-				let inner = write
-					.entry(id)
-					.or_insert_with(|| {
-						Arc::new(ARefData {
-							id,
-							data: OnceLock::new(),
-						})
-					})
-					.clone();
-				ARef { inner }
-			},
+		if let Some(inner) = read.get(&id).and_then(Weak::upgrade) {
+			drop(read);
+			self.touch(&inner);
+			return ARef { inner };
+		}
+		drop(read);
+
+		let mut write = self.loaded.write().unwrap();
+		if let Some(inner) = write.get(&id).and_then(Weak::upgrade) {
+			return ARef { inner };
+		}
+		let inner = Arc::new(ARefData::new(id));
+		write.insert(id, Arc::downgrade(&inner));
+		if write.len() >= self.sweep_at.load(Ordering::Relaxed) {
+			write.retain(|_, x| x.strong_count() > 0);
+			self.sweep_at.store((write.len() * 2).max(64), Ordering::Relaxed);
 		}
+		ARef { inner }
 	}
 
-	pub fn loaded(&'static self, id: AssetId<<T::Base as Asset>::Root>) -> Result<LARef<T>, io::Error> {
+	pub fn loaded(&'static self, id: AssetId<<T::Base as Asset>::Root>) -> Result<LARef<T>, AssetError> {
 		let inner = self.unloaded(id);
 		self.load(&inner.inner)?;
 		Ok(LARef { inner })
 	}
 
-	fn load<'a>(&'static self, inner: &'a ARefData<T>) -> Result<&'a T, io::Error> {
-		inner.data.get_or_try_init(|| {
+	/// Load many views in parallel on the asset loader pool, blocking until all of them are done. Repeated ids are
+	/// loaded once, and views already being loaded elsewhere are waited on instead of loaded again. `progress` is
+	/// called with the number of finished and total views as each one finishes. The results are in the order of `ids`.
+	pub fn loaded_many(
+		&'static self, ids: &[AssetId<<T::Base as Asset>::Root>], progress: impl Fn(usize, usize) + Sync,
+	) -> Vec<Result<LARef<T>, AssetError>> {
+		let mut index = FxHashMap::default();
+		let mut unique = Vec::new();
+		let slots: Vec<_> = ids
+			.iter()
+			.map(|&id| {
+				*index.entry(id).or_insert_with(|| {
+					unique.push(self.unloaded(id));
+					unique.len() - 1
+				})
+			})
+			.collect();
+
+		let done = AtomicUsize::new(0);
+		let results = Engine::get().assets.par_load(&unique, |x| {
+			let res = self.load(&x.inner).map(|_| ());
+			progress(done.fetch_add(1, Ordering::Relaxed) + 1, unique.len());
+			res
+		});
+
+		slots
+			.into_iter()
+			.map(|i| {
+				results[i].clone().map(|_| LARef {
+					inner: unique[i].clone(),
+				})
+			})
+			.collect()
+	}
+
+	fn touch(&self, inner: &Arc<ARefData<T>>) {
+		let budget = self.budget.load(Ordering::Relaxed);
+		if budget == usize::MAX {
+			return;
+		}
+		let Some(data) = inner.data.get() else {
+			return;
+		};
+
+		let mut retained = self.retained.lock().unwrap();
+		retained.touch(inner, data.size());
+		let evicted = retained.evict(budget);
+		drop(retained);
+		drop(evicted);
+	}
+
+	fn load<'a>(&'static self, inner: &'a Arc<ARefData<T>>) -> Result<&'a T, AssetError> {
+		let res = inner.data.get_or_try_init(|| {
+			let timer = Instant::now();
 			let asset = Engine::get().assets.load_asset(inner.id)?;
-			T::load(&self.context, asset)
-		})
+			let view = T::load(&self.context, asset).map_err(|e| view_err::<T>(inner.id.to_untyped(), e))?;
+			self.counters.load(timer.elapsed());
+			Ok::<_, AssetError>(view)
+		});
+		match res {
+			Ok(_) => {
+				*inner.error.lock().unwrap() = None;
+				inner.set_state(LoadState::Loaded);
+				self.touch(inner);
+			},
+			Err(ref e) => {
+				*inner.error.lock().unwrap() = Some(e.clone());
+				inner.set_state(LoadState::Failed);
+			},
+		}
+		res
+	}
+
+	fn request(&'static self, inner: &Arc<ARefData<T>>) {
+		if inner.data.get().is_some() {
+			return;
+		}
+		for from in [LoadState::Unloaded, LoadState::Failed] {
+			if inner
+				.state
+				.compare_exchange(from as _, LoadState::Loading as _, Ordering::AcqRel, Ordering::Acquire)
+				.is_ok()
+			{
+				let inner = inner.clone();
+				Engine::get().assets.spawn_load(move || {
+					let _ = self.load(&inner);
+				});
+				return;
+			}
+		}
+	}
+}
+
+/// The type-erased parts of an `AssetCache`.
+pub(super) trait ViewCache: Send + Sync {
+	fn as_any(&self) -> &dyn Any;
+
+	fn name(&self) -> &'static str;
+
+	fn stats(&self) -> ViewStats;
+
+	/// Update the loaded view of `id`, if there is one, after its asset changed.
+	fn reload(&'static self, id: UntypedAssetId) -> Result<(), AssetError>;
+}
+
+fn view_err<T: AssetView>(id: UntypedAssetId, error: io::Error) -> AssetError {
+	AssetError::View {
+		id,
+		view: std::any::type_name::<T>(),
+		error: Arc::new(error),
+	}
+}
+
+impl<T: AssetView> ViewCache for AssetCache<T> {
+	fn as_any(&self) -> &dyn Any { self }
+
+	fn name(&self) -> &'static str { std::any::type_name::<T>() }
+
+	fn stats(&self) -> ViewStats { AssetCache::stats(self) }
+
+	fn reload(&'static self, id: UntypedAssetId) -> Result<(), AssetError> {
+		let id = unsafe { id.typed() };
+		let Some(inner) = self.loaded.read().unwrap().get(&id).and_then(Weak::upgrade) else {
+			return Ok(());
+		};
+		// Views that haven't finished loading will see the new data anyway.
+		let Some(view) = inner.data.get() else {
+			return Ok(());
+		};
+
+		let asset = Engine::get().assets.load_asset(id)?;
+		match view.reload(&self.context, asset) {
+			Ok(()) => {
+				// Retain it again, its size might have changed.
+				let old = self.retained.lock().unwrap().remove(id);
+				self.touch(&inner);
+				drop(old);
+			},
+			Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+				// Detach the view so the next request loads it from scratch.
+				let mut loaded = self.loaded.write().unwrap();
+				if loaded.get(&id).is_some_and(|x| x.as_ptr() == Arc::as_ptr(&inner)) {
+					loaded.remove(&id);
+				}
+				drop(loaded);
+				let old = self.retained.lock().unwrap().remove(id);
+				drop(old);
+			},
+			Err(e) => return Err(view_err::<T>(id.to_untyped(), e)),
+		}
+		inner.generation.fetch_add(1, Ordering::AcqRel);
+		Ok(())
 	}
 }
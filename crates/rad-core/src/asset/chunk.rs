@@ -0,0 +1,271 @@
+use std::{
+	io::{self, Read, Seek, SeekFrom, Write},
+	sync::Mutex,
+};
+
+use bytemuck::{Pod, Zeroable};
+use rustc_hash::FxHashMap;
+
+const CHUNK_MAGIC: [u8; 4] = *b"RADC";
+
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct ChunkHeader {
+	magic: [u8; 4],
+	count: u32,
+	/// The total length of the chunk names, which follow the entries.
+	names_size: u64,
+}
+
+/// An entry in the chunk table, which follows the header.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct ChunkEntry {
+	/// The offset of the compressed data from the start of the container.
+	offset: u64,
+	size: u64,
+	/// The size of the data after decompression.
+	raw_size: u64,
+	name_len: u32,
+	_pad: u32,
+}
+
+/// Builds a chunked asset payload, to be written with `AssetWrite::write_chunks`. Every chunk is compressed
+/// separately, so it can be read without decompressing the rest of the asset.
+#[derive(Default)]
+pub struct ChunkWriter {
+	entries: Vec<ChunkEntry>,
+	names: String,
+	data: Vec<u8>,
+}
+
+impl ChunkWriter {
+	pub fn new() -> Self { Self::default() }
+
+	/// Add a chunk, returning its index. Names are optional, and can be left empty if the chunk is only ever
+	/// accessed by index.
+	pub fn add(&mut self, name: &str, data: &[u8]) -> Result<usize, io::Error> {
+		let offset = self.data.len() as u64;
+		zstd::stream::copy_encode(data, &mut self.data, 5)?;
+		self.entries.push(ChunkEntry {
+			offset,
+			size: self.data.len() as u64 - offset,
+			raw_size: data.len() as _,
+			name_len: name.len() as _,
+			_pad: 0,
+		});
+		self.names.push_str(name);
+		Ok(self.entries.len() - 1)
+	}
+
+	pub fn len(&self) -> usize { self.entries.len() }
+
+	pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+	/// Write out the container.
+	pub fn write<W: Write + ?Sized>(mut self, to: &mut W) -> Result<(), io::Error> {
+		let data_offset = (std::mem::size_of::<ChunkHeader>()
+			+ std::mem::size_of::<ChunkEntry>() * self.entries.len()
+			+ self.names.len()) as u64;
+		for entry in self.entries.iter_mut() {
+			entry.offset += data_offset;
+		}
+
+		let header = ChunkHeader {
+			magic: CHUNK_MAGIC,
+			count: self.entries.len() as _,
+			names_size: self.names.len() as _,
+		};
+		to.write_all(bytemuck::bytes_of(&header))?;
+		to.write_all(bytemuck::cast_slice(&self.entries))?;
+		to.write_all(self.names.as_bytes())?;
+		to.write_all(&self.data)
+	}
+}
+
+trait SeekRead: Read + Seek + Send {}
+impl<T: Read + Seek + Send> SeekRead for T {}
+
+/// Random access to the chunks of an asset, returned by `AssetRead::chunks`. Only the chunk table is read up
+/// front, chunks are read and decompressed when they are asked for.
+pub struct ChunkReader {
+	read: Mutex<Box<dyn SeekRead>>,
+	/// Where the container starts in `read`.
+	base: u64,
+	entries: Vec<ChunkEntry>,
+	names: Vec<String>,
+	lookup: FxHashMap<String, usize>,
+}
+
+impl ChunkReader {
+	/// Open the container starting at the current position of `read`. The chunk table is checked against the length
+	/// of `read`, so corrupt containers fail here instead of when a chunk is read.
+	pub fn open(mut read: impl Read + Seek + Send + 'static) -> Result<Self, io::Error> {
+		let base = read.stream_position()?;
+		let len = read.seek(SeekFrom::End(0))?.saturating_sub(base);
+		read.seek(SeekFrom::Start(base))?;
+
+		let mut header = ChunkHeader::zeroed();
+		read.read_exact(bytemuck::bytes_of_mut(&mut header))?;
+		if header.magic != CHUNK_MAGIC {
+			return Err(invalid("asset is not chunked"));
+		}
+
+		let table_size = u64::from(header.count)
+			.checked_mul(std::mem::size_of::<ChunkEntry>() as u64)
+			.and_then(|x| x.checked_add(std::mem::size_of::<ChunkHeader>() as u64))
+			.and_then(|x| x.checked_add(header.names_size))
+			.filter(|&x| x <= len)
+			.ok_or_else(|| invalid("chunk table is larger than the asset"))?;
+
+		let mut entries = vec![ChunkEntry::zeroed(); header.count as usize];
+		read.read_exact(bytemuck::cast_slice_mut(&mut entries))?;
+		let mut names = vec![0; header.names_size as usize];
+		read.read_exact(&mut names)?;
+
+		for (i, entry) in entries.iter().enumerate() {
+			if entry.offset < table_size || entry.offset.checked_add(entry.size).is_none_or(|end| end > len) {
+				return Err(invalid(format!("chunk {i} is out of bounds")));
+			}
+		}
+
+		let mut rest = names.as_slice();
+		let mut names = Vec::with_capacity(entries.len());
+		for entry in entries.iter() {
+			let len = entry.name_len as usize;
+			if len > rest.len() {
+				return Err(invalid("chunk names are truncated"));
+			}
+			let (name, next) = rest.split_at(len);
+			names.push(String::from_utf8(name.to_vec()).map_err(|e| invalid(e.to_string()))?);
+			rest = next;
+		}
+		let lookup = names
+			.iter()
+			.enumerate()
+			.filter(|(_, x)| !x.is_empty())
+			.map(|(i, x)| (x.clone(), i))
+			.collect();
+
+		Ok(Self {
+			read: Mutex::new(Box::new(read)),
+			base,
+			entries,
+			names,
+			lookup,
+		})
+	}
+
+	pub fn len(&self) -> usize { self.entries.len() }
+
+	pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+	/// The index of the chunk called `name`.
+	pub fn find(&self, name: &str) -> Option<usize> { self.lookup.get(name).copied() }
+
+	pub fn name(&self, index: usize) -> &str { &self.names[index] }
+
+	/// The decompressed size of a chunk.
+	pub fn size(&self, index: usize) -> usize { self.entries[index].raw_size as _ }
+
+	/// Read and decompress a chunk.
+	pub fn read(&self, index: usize) -> Result<Vec<u8>, io::Error> {
+		let entry = self.entries.get(index).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("chunk {index} out of range ({} chunks)", self.entries.len()),
+			)
+		})?;
+
+		let mut compressed = vec![0; entry.size as usize];
+		{
+			let mut read = self.read.lock().unwrap();
+			read.seek(SeekFrom::Start(self.base + entry.offset))?;
+			read.read_exact(&mut compressed)?;
+		}
+
+		// Don't trust the size up front, and stop decompressing as soon as the data is larger than it.
+		let mut out = Vec::new();
+		zstd::stream::read::Decoder::with_buffer(compressed.as_slice())?
+			.take(entry.raw_size.saturating_add(1))
+			.read_to_end(&mut out)?;
+		if out.len() as u64 != entry.raw_size {
+			return Err(invalid("chunk size mismatch"));
+		}
+		Ok(out)
+	}
+
+	/// Read and decompress the chunk called `name`.
+	pub fn read_named(&self, name: &str) -> Result<Vec<u8>, io::Error> {
+		let index = self
+			.find(name)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no chunk named `{name}`")))?;
+		self.read(index)
+	}
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg.into()) }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::{AssetRead, AssetWrite};
+
+	#[test]
+	fn roundtrip() {
+		let big: Vec<u8> = (0..100_000u32).flat_map(|x| (x % 251).to_le_bytes()).collect();
+		let mut chunks = ChunkWriter::new();
+		assert_eq!(chunks.add("header", b"hello").unwrap(), 0);
+		assert_eq!(chunks.add("", &big).unwrap(), 1);
+		assert_eq!(chunks.add("empty", &[]).unwrap(), 2);
+
+		let mut bytes = Vec::new();
+		bytes.write_chunks(chunks).unwrap();
+
+		let read: Box<dyn AssetRead> = Box::new(io::Cursor::new(bytes));
+		let chunks = read.chunks().unwrap();
+		assert_eq!(chunks.len(), 3);
+		assert_eq!(chunks.find("header"), Some(0));
+		assert_eq!(chunks.find(""), None);
+		assert_eq!(chunks.size(1), big.len());
+		assert_eq!(chunks.read(1).unwrap(), big);
+		assert_eq!(chunks.read_named("header").unwrap(), b"hello");
+		assert!(chunks.read_named("empty").unwrap().is_empty());
+		assert_eq!(
+			chunks.read_named("missing").unwrap_err().kind(),
+			io::ErrorKind::NotFound
+		);
+		assert!(chunks.read(3).is_err());
+	}
+
+	#[test]
+	fn corrupt() {
+		let mut chunks = ChunkWriter::new();
+		chunks.add("a", b"hello").unwrap();
+		let mut bytes = Vec::new();
+		chunks.write(&mut bytes).unwrap();
+
+		let open = |patch: &dyn Fn(&mut ChunkHeader, &mut ChunkEntry)| {
+			let mut bytes = bytes.clone();
+			let (header, rest) = bytes.split_at_mut(std::mem::size_of::<ChunkHeader>());
+			let entry = &mut rest[..std::mem::size_of::<ChunkEntry>()];
+			patch(bytemuck::from_bytes_mut(header), bytemuck::from_bytes_mut(entry));
+			ChunkReader::open(io::Cursor::new(bytes)).map(|_| ()).unwrap_err()
+		};
+		let err = open(&|h, _| h.count = u32::MAX);
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(err.to_string().contains("table"), "{err}");
+		assert!(open(&|h, _| h.names_size = u64::MAX).to_string().contains("table"));
+		assert!(open(&|_, e| e.size = u64::MAX).to_string().contains("out of bounds"));
+		assert!(open(&|_, e| e.offset = u64::MAX).to_string().contains("out of bounds"));
+		assert!(open(&|_, e| e.offset = 0).to_string().contains("out of bounds"));
+		assert!(open(&|_, e| e.name_len = 2).to_string().contains("truncated"));
+
+		// Chunks that decompress to more than they claim are cut off.
+		let mut bytes = bytes.clone();
+		let entry = &mut bytes[std::mem::size_of::<ChunkHeader>()..][..std::mem::size_of::<ChunkEntry>()];
+		bytemuck::from_bytes_mut::<ChunkEntry>(entry).raw_size = 2;
+		let chunks = ChunkReader::open(io::Cursor::new(bytes)).unwrap();
+		assert_eq!(chunks.read(0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+	}
+}
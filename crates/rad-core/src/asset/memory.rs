@@ -0,0 +1,515 @@
+use std::{
+	io::{self, Read, Write},
+	sync::{Arc, RwLock},
+};
+
+use rustc_hash::FxHashMap;
+use uuid::Uuid;
+
+use crate::asset::{
+	aref::{AssetId, UntypedAssetId},
+	Asset,
+	AssetError,
+	AssetRead,
+	AssetSource,
+	AssetWrite,
+	CookKey,
+};
+
+type AssetMap = FxHashMap<UntypedAssetId, MemoryAsset>;
+type CookedMap = FxHashMap<(UntypedAssetId, Uuid), (CookKey, Arc<[u8]>)>;
+
+/// An asset stored in a `MemoryAssetSource`.
+#[derive(Clone)]
+pub struct MemoryAsset {
+	pub ty: Uuid,
+	pub version: u32,
+	pub data: Arc<[u8]>,
+}
+
+/// An asset source that keeps everything in memory. Useful for procedural assets and tests.
+///
+/// Assets can be added and removed at any time, and cooked assets derived from them are cached in memory too.
+#[derive(Default)]
+pub struct MemoryAssetSource {
+	assets: Arc<RwLock<AssetMap>>,
+	cooked: Arc<RwLock<CookedMap>>,
+}
+
+impl MemoryAssetSource {
+	pub fn new() -> Self { Self::default() }
+
+	/// Add an already serialized asset of type `ty`, written with `version` of the asset type, replacing any asset
+	/// with the same ID.
+	pub fn insert_bytes(&self, id: UntypedAssetId, ty: Uuid, version: u32, data: impl Into<Arc<[u8]>>) {
+		let asset = MemoryAsset {
+			ty,
+			version,
+			data: data.into(),
+		};
+		self.assets.write().unwrap().insert(id, asset);
+		let _ = self.invalidate_cooked(id);
+	}
+
+	/// Serialize and add `asset`, replacing any asset with the same ID.
+	pub fn insert<T: Asset>(&self, id: AssetId<T::Root>, asset: &T) -> Result<(), io::Error> {
+		let mut data = Vec::new();
+		asset.save(&mut data)?;
+		self.insert_bytes(id.to_untyped(), T::UUID, T::VERSION, data);
+		Ok(())
+	}
+
+	/// Serialize and add `asset` with a new ID.
+	pub fn add<T: Asset<Root = T>>(&self, asset: &T) -> Result<AssetId<T>, io::Error> {
+		let id = AssetId::new();
+		self.insert(id, asset)?;
+		Ok(id)
+	}
+
+	/// Remove an asset, returning `true` if it existed.
+	pub fn remove(&self, id: UntypedAssetId) -> bool {
+		let existed = self.assets.write().unwrap().remove(&id).is_some();
+		let _ = self.invalidate_cooked(id);
+		existed
+	}
+
+	pub fn contains(&self, id: UntypedAssetId) -> bool { self.assets.read().unwrap().contains_key(&id) }
+
+	/// The serialized data of an asset.
+	pub fn get(&self, id: UntypedAssetId) -> Option<MemoryAsset> { self.assets.read().unwrap().get(&id).cloned() }
+
+	/// The number of cooked assets currently cached.
+	pub fn cooked_len(&self) -> usize { self.cooked.read().unwrap().len() }
+}
+
+impl AssetSource for MemoryAssetSource {
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, AssetError> {
+		let assets = self.assets.read().unwrap();
+		let asset = assets.get(&id).ok_or(AssetError::NotFound { id, ty })?;
+		if asset.ty != ty {
+			return Err(AssetError::TypeMismatch {
+				id,
+				expected: ty,
+				found: asset.ty,
+			});
+		}
+		Ok(Box::new(MemoryRead {
+			version: asset.version,
+			read: io::Cursor::new(asset.data.clone()),
+		}))
+	}
+
+	fn load_cooked(&self, key: CookKey) -> Result<Box<dyn AssetRead>, AssetError> {
+		let cooked = self.cooked.read().unwrap();
+		match cooked.get(&(key.id, key.ty)) {
+			Some((k, data)) if *k == key => Ok(Box::new(io::Cursor::new(data.clone()))),
+			// Stale cooked data is as good as none.
+			_ => Err(AssetError::NotFound { id: key.id, ty: key.ty }),
+		}
+	}
+
+	fn store_cooked(&self, key: CookKey) -> Result<Box<dyn AssetWrite>, io::Error> {
+		let cooked = self.cooked.clone();
+		Ok(Box::new(MemoryWrite::new(move |data| {
+			cooked.write().unwrap().insert((key.id, key.ty), (key, data));
+		})))
+	}
+
+	fn invalidate_cooked(&self, id: UntypedAssetId) -> Result<(), io::Error> {
+		self.cooked.write().unwrap().retain(|&(x, _), _| x != id);
+		Ok(())
+	}
+
+	fn purge_cooked(&self) -> Result<(), io::Error> {
+		self.cooked.write().unwrap().clear();
+		Ok(())
+	}
+
+	fn rewrite(&self, id: UntypedAssetId, ty: Uuid, version: u32) -> Result<Box<dyn AssetWrite>, io::Error> {
+		let assets = self.assets.clone();
+		Ok(Box::new(MemoryWrite::new(move |data| {
+			assets.write().unwrap().insert(id, MemoryAsset { ty, version, data });
+		})))
+	}
+}
+
+struct MemoryRead {
+	version: u32,
+	read: io::Cursor<Arc<[u8]>>,
+}
+
+impl Read for MemoryRead {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.read.read(buf) }
+}
+
+impl AssetRead for MemoryRead {
+	fn version(&self) -> Option<u32> { Some(self.version) }
+}
+
+/// Buffers written data, committing it when dropped.
+struct MemoryWrite<F: FnOnce(Arc<[u8]>)> {
+	data: Vec<u8>,
+	commit: Option<F>,
+}
+
+impl<F: FnOnce(Arc<[u8]>)> MemoryWrite<F> {
+	fn new(commit: F) -> Self {
+		Self {
+			data: Vec::new(),
+			commit: Some(commit),
+		}
+	}
+}
+
+impl<F: FnOnce(Arc<[u8]>)> Write for MemoryWrite<F> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.data.write(buf) }
+
+	fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+impl<F: FnOnce(Arc<[u8]>)> AssetWrite for MemoryWrite<F> {}
+
+impl<F: FnOnce(Arc<[u8]>)> Drop for MemoryWrite<F> {
+	fn drop(&mut self) {
+		if let Some(commit) = self.commit.take() {
+			commit(std::mem::take(&mut self.data).into());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+	use bincode::{Decode, Encode};
+
+	use super::*;
+	use crate::{
+		asset::{
+			aref::{ARef, LoadState},
+			map_dec_err,
+			map_enc_err,
+			stats::{AssetStats, ViewStats},
+			AssetView,
+			BincodeAsset,
+			CookedAsset,
+		},
+		Engine,
+		ScopedEngine,
+	};
+
+	#[derive(Encode, Decode)]
+	struct Numbers(Vec<u32>);
+
+	impl BincodeAsset for Numbers {
+		const UUID: Uuid = uuid::uuid!("7d4bb3b4-4c1c-4b5e-9a4f-6f0b1b8e3c01");
+	}
+
+	#[derive(Encode, Decode)]
+	struct Sum(u32);
+
+	impl BincodeAsset for Sum {
+		type Root = Numbers;
+
+		const UUID: Uuid = uuid::uuid!("7d4bb3b4-4c1c-4b5e-9a4f-6f0b1b8e3c02");
+	}
+
+	impl CookedAsset for Sum {
+		type Base = Numbers;
+
+		fn cook(base: &Self::Base) -> Self { Self(base.0.iter().sum()) }
+	}
+
+	/// Cooked through `Sum`.
+	#[derive(Encode, Decode)]
+	struct Doubled(u32);
+
+	impl BincodeAsset for Doubled {
+		type Root = Numbers;
+
+		const UUID: Uuid = uuid::uuid!("7d4bb3b4-4c1c-4b5e-9a4f-6f0b1b8e3c04");
+	}
+
+	impl CookedAsset for Doubled {
+		type Base = Sum;
+
+		fn cook(base: &Self::Base) -> Self { Self(base.0 * 2) }
+	}
+
+	#[derive(Encode, Decode)]
+	struct Named {
+		name: String,
+		count: u32,
+	}
+
+	impl BincodeAsset for Named {
+		const UUID: Uuid = uuid::uuid!("7d4bb3b4-4c1c-4b5e-9a4f-6f0b1b8e3c03");
+		const VERSION: u32 = 1;
+	}
+
+	fn migrate_named(data: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+		let c = bincode::config::standard();
+		let (name, _): (String, _) = bincode::decode_from_slice(&data, c).map_err(map_dec_err)?;
+		bincode::encode_to_vec(Named { name, count: 0 }, c).map_err(map_enc_err)
+	}
+
+	struct TotalView(u32);
+
+	impl AssetView for TotalView {
+		type Base = Numbers;
+		type Ctx = ();
+
+		fn load(_: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error> { Ok(Self(base.0.iter().sum())) }
+	}
+
+	struct LiveTotalView(AtomicU32);
+
+	impl AssetView for LiveTotalView {
+		type Base = Numbers;
+		type Ctx = ();
+
+		fn load(_: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error> {
+			Ok(Self(AtomicU32::new(base.0.iter().sum())))
+		}
+
+		fn reload(&self, _: &'static Self::Ctx, base: Self::Base) -> Result<(), io::Error> {
+			self.0.store(base.0.iter().sum(), Ordering::Relaxed);
+			Ok(())
+		}
+	}
+
+	struct CountView(usize);
+
+	impl AssetView for CountView {
+		type Base = Numbers;
+		type Ctx = ();
+
+		fn load(_: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error> { Ok(Self(base.0.len())) }
+	}
+
+	fn engine() -> ScopedEngine {
+		let mut engine = Engine::builder();
+		engine.asset_source(MemoryAssetSource::new());
+		engine.asset::<Numbers>();
+		engine.asset::<Named>();
+		engine.migration::<Named>(0, migrate_named);
+		engine.rewrite_migrated(true);
+		engine.cooked_asset::<Sum>();
+		engine.cooked_asset::<Doubled>();
+		engine.save_cook_intermediates(true);
+		engine.asset_view::<TotalView>();
+		engine.asset_view::<LiveTotalView>();
+		engine.asset_view::<CountView>();
+		engine.build_scoped()
+	}
+
+	/// The statistics of the asset type whose name ends with `name`, in the current engine.
+	fn asset_stats(name: &str) -> AssetStats {
+		let assets = Engine::get().asset_stats();
+		assets.iter().find(|x| x.0.ends_with(name)).unwrap().1
+	}
+
+	/// The statistics of the view type whose name ends with `name`, in the current engine.
+	fn view_stats(name: &str) -> ViewStats {
+		let views = Engine::get().view_stats();
+		views.iter().find(|x| x.0.ends_with(name)).unwrap().1
+	}
+
+	#[test]
+	fn load() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let id = source.add(&Numbers(vec![1, 2, 3])).unwrap();
+
+		let numbers: Numbers = Engine::get().load_asset(id).unwrap();
+		assert_eq!(numbers.0, [1, 2, 3]);
+
+		let err = Engine::get()
+			.check_asset(id.to_untyped(), <Named as Asset>::UUID)
+			.err()
+			.unwrap();
+		assert!(matches!(err, AssetError::TypeMismatch { found, .. } if found == <Numbers as Asset>::UUID));
+
+		assert!(source.remove(id.to_untyped()));
+		let err = Engine::get().load_asset::<Numbers>(id).err().unwrap();
+		assert!(matches!(err, AssetError::NotFound { .. }));
+	}
+
+	#[test]
+	fn cook() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let id = source.add(&Numbers(vec![1, 2, 3])).unwrap();
+
+		let cooks = || asset_stats("::Sum").cooks;
+		assert_eq!(Engine::get().cook_asset::<Sum>(id).unwrap().0, 6);
+		assert_eq!(Engine::get().cook_asset::<Sum>(id).unwrap().0, 6);
+		assert_eq!(cooks(), 1);
+
+		source.insert(id, &Numbers(vec![4, 5])).unwrap();
+		assert_eq!(Engine::get().cook_asset::<Sum>(id).unwrap().0, 9);
+		assert_eq!(cooks(), 2);
+	}
+
+	#[test]
+	fn corrupt_cooked() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let id = source.add(&Numbers(vec![1, 2])).unwrap();
+		assert_eq!(Engine::get().cook_asset::<Doubled>(id).unwrap().0, 6);
+		assert_eq!(source.cooked_len(), 2);
+
+		// Cooked data that can't be loaded is recooked, including intermediate steps.
+		for (_, data) in source.cooked.write().unwrap().values_mut() {
+			*data = Vec::new().into();
+		}
+		assert_eq!(Engine::get().cook_asset::<Doubled>(id).unwrap().0, 6);
+		assert_eq!(asset_stats("::Sum").cooks, 2);
+	}
+
+	#[test]
+	fn view() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let id = source.add(&Numbers(vec![10, 20])).unwrap();
+
+		let loads = || view_stats("::TotalView").loads;
+		let a = ARef::<TotalView>::loaded(id).unwrap();
+		let b = ARef::<TotalView>::loaded(id).unwrap();
+		assert_eq!(a.0, 30);
+		assert!(a == b);
+		assert_eq!(loads(), 1);
+
+		drop((a, b));
+		let _ = ARef::<TotalView>::loaded(id).unwrap();
+		assert_eq!(loads(), 2);
+	}
+
+	#[test]
+	fn batch() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let a = source.add(&Numbers(vec![1])).unwrap();
+		let b = source.add(&Numbers(vec![1, 2, 3])).unwrap();
+		let missing = AssetId::new();
+
+		let calls = AtomicUsize::new(0);
+		let res = ARef::<CountView>::loaded_many(&[a, b, missing, a], |done, total| {
+			calls.fetch_add(1, Ordering::Relaxed);
+			assert!(done <= total && total == 3);
+		});
+		assert_eq!(calls.load(Ordering::Relaxed), 3);
+		assert_eq!(res.len(), 4);
+		assert_eq!(res[0].as_ref().unwrap().0, 1);
+		assert_eq!(res[1].as_ref().unwrap().0, 3);
+		assert!(matches!(res[2], Err(AssetError::NotFound { .. })));
+		assert!(res[0].as_ref().unwrap() == res[3].as_ref().unwrap());
+	}
+
+	#[test]
+	fn stats() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let id = source.add(&Numbers(vec![1, 2, 3])).unwrap();
+
+		let _: Numbers = Engine::get().load_asset(id).unwrap();
+		let _ = Engine::get().cook_asset::<Sum>(id).unwrap();
+		let _view = ARef::<CountView>::loaded(id).unwrap();
+
+		let numbers = asset_stats("::Numbers");
+		// Once directly, once to cook `Sum`, and once for the view.
+		assert_eq!(numbers.loads, 3);
+		assert!(numbers.load_bytes > 0);
+		assert_eq!(numbers.cooks, 0);
+		assert_eq!(asset_stats("::Sum").cooks, 1);
+
+		let count = view_stats("::CountView");
+		assert_eq!(count.loads, 1);
+		assert_eq!(count.live, 1);
+	}
+
+	#[test]
+	fn reload() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let id = source.add(&Numbers(vec![1, 2])).unwrap();
+
+		let live = ARef::<LiveTotalView>::loaded(id).unwrap();
+		let count = ARef::<CountView>::loaded(id).unwrap();
+		assert_eq!(live.generation(), 0);
+
+		source.insert(id, &Numbers(vec![4, 5, 6])).unwrap();
+		Engine::get().reload_asset(id.to_untyped()).unwrap();
+
+		assert_eq!(live.0.load(Ordering::Relaxed), 15);
+		assert_eq!(live.generation(), 1);
+		assert!(ARef::<LiveTotalView>::loaded(id).unwrap() == live);
+
+		// Views that can't be reloaded in place are detached instead.
+		assert_eq!(count.0, 2);
+		assert_eq!(count.generation(), 1);
+		let fresh = ARef::<CountView>::loaded(id).unwrap();
+		assert!(fresh != count);
+		assert_eq!(fresh.0, 3);
+	}
+
+	#[test]
+	fn migrate() {
+		let engine = engine();
+		let source: &MemoryAssetSource = engine.get().asset_source();
+		let c = bincode::config::standard();
+
+		let id = AssetId::<Named>::new();
+		let old = bincode::encode_to_vec("old".to_string(), c).unwrap();
+		source.insert_bytes(id.to_untyped(), <Named as Asset>::UUID, 0, old);
+
+		let named: Named = Engine::get().load_asset(id).unwrap();
+		assert_eq!(named.name, "old");
+		assert_eq!(named.count, 0);
+		assert_eq!(source.get(id.to_untyped()).unwrap().version, 1);
+
+		source.insert_bytes(id.to_untyped(), <Named as Asset>::UUID, 2, []);
+		let err = Engine::get().load_asset::<Named>(id).err().unwrap();
+		assert!(matches!(err, AssetError::Decode { .. }));
+	}
+
+	#[test]
+	fn scoped() {
+		let outer = engine();
+		let source: &MemoryAssetSource = outer.get().asset_source();
+		let id = source.add(&Numbers(vec![1])).unwrap();
+
+		let inner = engine();
+		assert!(std::ptr::eq(Engine::get(), inner.get()));
+		assert!(matches!(
+			Engine::get().load_asset::<Numbers>(id),
+			Err(AssetError::NotFound { .. })
+		));
+
+		// Background loads see the engine that started them.
+		let view = ARef::<TotalView>::unloaded(id);
+		view.request();
+		while view.state() == LoadState::Loading {
+			std::thread::yield_now();
+		}
+		assert_eq!(view.state(), LoadState::Failed);
+		drop(view);
+
+		drop(inner);
+		assert!(std::ptr::eq(Engine::get(), outer.get()));
+		let view = ARef::<TotalView>::unloaded(id);
+		view.request();
+		while view.state() != LoadState::Loaded {
+			std::thread::yield_now();
+		}
+		assert_eq!(view.try_loaded().unwrap().0, 1);
+	}
+
+	#[test]
+	#[should_panic(expected = "reverse order")]
+	fn scoped_order() {
+		let outer = engine();
+		let _inner = engine();
+		drop(outer);
+	}
+}
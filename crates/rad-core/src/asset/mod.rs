@@ -1,9 +1,13 @@
 use std::{
 	alloc::Layout,
-	any::{Any, TypeId},
+	any::TypeId,
+	fmt::{Debug, Display},
 	io::{self, Read, Write},
 	mem::MaybeUninit,
-	sync::Arc,
+	path::Path,
+	ptr::NonNull,
+	sync::{Arc, Condvar, Mutex, OnceLock},
+	time::Instant,
 };
 
 use bincode::{
@@ -11,40 +15,102 @@ use bincode::{
 	Decode,
 	Encode,
 };
-use petgraph::graph::{DiGraph, NodeIndex};
-use rustc_hash::FxHashMap;
-use tracing::trace_span;
+use petgraph::{
+	graph::{DiGraph, NodeIndex},
+	Direction,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rustc_hash::{FxHashMap, FxHashSet};
+use tracing::{trace_span, warn};
 pub use uuid::Uuid;
+use xxhash_rust::xxh3::xxh3_64;
 
-use crate::asset::aref::{AssetCache, AssetId, UntypedAssetId};
+use crate::{
+	asset::{
+		aref::{AssetCache, AssetId, UntypedAssetId, ViewCache},
+		chunk::{ChunkReader, ChunkWriter},
+		pack::PackWriter,
+		stats::{AssetCounters, AssetStats, ViewStats},
+	},
+	Engine,
+};
 
 pub mod aref;
+pub mod chunk;
+pub mod memory;
+pub mod pack;
+pub mod stats;
+
+pub trait AssetRead: Read {
+	/// The `VERSION` of the asset type this data was written with, if the source knows it. Data without a version is
+	/// assumed to be up to date.
+	fn version(&self) -> Option<u32> { None }
+
+	/// The `xxh3_64` hash of the data, if the source knows it without reading the data. Cooked data is keyed by this
+	/// hash, so a source that stores it avoids reading the whole asset when the cooked data is up to date.
+	fn content_hash(&self) -> Option<u64> { None }
+
+	/// Random access to the chunks of an asset saved with `AssetWrite::write_chunks`. Sources that can seek within
+	/// the asset only read chunks when they are asked for, others read the whole asset into memory first.
+	fn chunks(self: Box<Self>) -> Result<ChunkReader, io::Error> {
+		let mut bytes = Vec::new();
+		let mut this = self;
+		this.read_to_end(&mut bytes)?;
+		ChunkReader::open(io::Cursor::new(bytes))
+	}
+}
 
-pub trait AssetRead: Read {}
+pub trait AssetWrite: Write {
+	/// Save the asset as separately compressed chunks, to be read with `AssetRead::chunks`. Nothing else should be
+	/// written to the asset.
+	fn write_chunks(&mut self, chunks: ChunkWriter) -> Result<(), io::Error> { chunks.write(self) }
+}
+
+impl<T: AsRef<[u8]>> AssetRead for io::Cursor<T> {}
 
-pub trait AssetWrite: Write {}
+impl AssetWrite for Vec<u8> {}
 
 /// An asset, representing *data* that can be loaded and saved.
 pub trait Asset: Sized + 'static {
 	const UUID: Uuid;
 	/// The asset at the root of the cook chain.
 	type Root: Asset = Self;
+	/// The version of the serialized format of the asset. Bump this whenever `save` changes its output, and register
+	/// a migration from the previous version so existing data can still be loaded. Cooked assets are not migrated,
+	/// bump `CookedAsset::COOK_VERSION` instead.
+	const VERSION: u32 = 0;
+	/// Whether `dependencies` can return anything. Assets of types that can't are never loaded just to find the assets
+	/// they reference.
+	const REFERENCES_ASSETS: bool = false;
 
 	/// Load the asset from the source. Note that this does *not* have to actually load everything
 	/// into memory, it can just load metadata and stream.
 	fn load(from: Box<dyn AssetRead>) -> Result<Self, io::Error>;
 
 	fn save(&self, to: &mut dyn AssetWrite) -> Result<(), io::Error>;
+
+	/// The assets this asset references, and their types.
+	fn dependencies(&self) -> Vec<(UntypedAssetId, Uuid)> { Vec::new() }
 }
 
 pub trait BincodeAsset: Encode + Decode + Sized + 'static {
 	const UUID: Uuid;
 	type Root: Asset = Self;
+	/// See `Asset::VERSION`. Bincode encodes fields by position, so this must be bumped whenever a field is added,
+	/// removed, or reordered.
+	const VERSION: u32 = 0;
+	/// See `Asset::REFERENCES_ASSETS`.
+	const REFERENCES_ASSETS: bool = false;
+
+	/// See `Asset::dependencies`.
+	fn dependencies(&self) -> Vec<(UntypedAssetId, Uuid)> { Vec::new() }
 }
 impl<T: BincodeAsset> Asset for T {
 	type Root = T::Root;
 
+	const REFERENCES_ASSETS: bool = T::REFERENCES_ASSETS;
 	const UUID: Uuid = T::UUID;
+	const VERSION: u32 = T::VERSION;
 
 	fn load(mut from: Box<dyn AssetRead>) -> Result<Self, io::Error> {
 		let c = bincode::config::standard();
@@ -56,6 +122,8 @@ impl<T: BincodeAsset> Asset for T {
 		bincode::encode_into_std_write(self, &mut to, c).map_err(map_enc_err)?;
 		Ok(())
 	}
+
+	fn dependencies(&self) -> Vec<(UntypedAssetId, Uuid)> { BincodeAsset::dependencies(self) }
 }
 
 pub fn map_enc_err(e: EncodeError) -> io::Error {
@@ -72,10 +140,100 @@ pub fn map_dec_err(e: DecodeError) -> io::Error {
 	}
 }
 
+/// Why an asset could not be loaded, cooked, or viewed.
+#[derive(Clone)]
+pub enum AssetError {
+	/// No source has the asset.
+	NotFound { id: UntypedAssetId, ty: Uuid },
+	/// A source has the asset, but as a different type.
+	TypeMismatch {
+		id: UntypedAssetId,
+		expected: Uuid,
+		found: Uuid,
+	},
+	/// The asset data is invalid, or could not be migrated to the current version.
+	Decode {
+		id: UntypedAssetId,
+		ty: Uuid,
+		error: Arc<io::Error>,
+	},
+	/// The asset cannot be cooked, because the cook chain is broken.
+	Cook {
+		id: UntypedAssetId,
+		ty: Uuid,
+		reason: String,
+	},
+	/// The view of an asset could not be created or updated from the loaded asset.
+	View {
+		id: UntypedAssetId,
+		view: &'static str,
+		error: Arc<io::Error>,
+	},
+	/// The asset type was never registered.
+	Unregistered(Uuid),
+	/// The source failed to read the asset.
+	Io(Arc<io::Error>),
+}
+
+impl AssetError {
+	fn decode(id: UntypedAssetId, ty: Uuid, error: io::Error) -> Self {
+		Self::Decode {
+			id,
+			ty,
+			error: Arc::new(error),
+		}
+	}
+
+	/// If the source doesn't have the asset as the requested type, as opposed to having it but failing to read it.
+	/// The next source is tried, and cooked assets are cooked instead.
+	fn is_missing(&self) -> bool { matches!(self, Self::NotFound { .. } | Self::TypeMismatch { .. }) }
+}
+
+impl Display for AssetError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			Self::NotFound { id, ty } => write!(f, "asset {id} ({ty}) not found"),
+			Self::TypeMismatch { id, expected, found } => {
+				write!(f, "asset {id} is a {found}, expected a {expected}")
+			},
+			Self::Decode { id, ty, error } => write!(f, "failed to decode asset {id} ({ty}): {error}"),
+			Self::Cook { id, ty, reason } => write!(f, "failed to cook asset {id} ({ty}): {reason}"),
+			Self::View { id, view, error } => write!(f, "failed to create `{view}` for asset {id}: {error}"),
+			Self::Unregistered(ty) => write!(f, "asset type {ty} not registered"),
+			Self::Io(error) => write!(f, "I/O error: {error}"),
+		}
+	}
+}
+
+impl Debug for AssetError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { <Self as Display>::fmt(self, f) }
+}
+
+impl std::error::Error for AssetError {}
+
+impl From<io::Error> for AssetError {
+	fn from(error: io::Error) -> Self { Self::Io(Arc::new(error)) }
+}
+
+impl From<AssetError> for io::Error {
+	fn from(err: AssetError) -> Self {
+		let kind = match &err {
+			AssetError::NotFound { .. } => io::ErrorKind::NotFound,
+			AssetError::Decode { .. } => io::ErrorKind::InvalidData,
+			AssetError::Io(error) => error.kind(),
+			_ => io::ErrorKind::Other,
+		};
+		io::Error::new(kind, err)
+	}
+}
+
 /// An asset derived from another.
 pub trait CookedAsset: Asset {
 	/// The asset this derives from.
 	type Base: Asset<Root = Self::Root>;
+	/// The version of the cooker. Bump this whenever `cook` changes its output, so that stale cooked data is not
+	/// reused.
+	const COOK_VERSION: u32 = 0;
 
 	/// Cook the asset from its base.
 	fn cook(base: &Self::Base) -> Self;
@@ -89,32 +247,202 @@ pub trait AssetView: Sized + Send + Sync + 'static {
 	type Ctx: Default + Send + Sync + 'static;
 
 	fn load(ctx: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error>;
+
+	/// The approximate amount of memory used by this view, in bytes. Used for the view budget.
+	fn size(&self) -> usize { 0 }
+
+	/// Update the view in place after its asset changed. Anything handed out to users, such as bindless IDs and GPU
+	/// pointers, should stay valid so they see the new data without reacquiring the view. Returns `Unsupported` if
+	/// the view can't be updated in place, in which case it is reloaded from scratch the next time it is requested.
+	fn reload(&self, ctx: &'static Self::Ctx, base: Self::Base) -> Result<(), io::Error> {
+		let _ = (ctx, base);
+		Err(io::Error::new(io::ErrorKind::Unsupported, "view cannot be reloaded"))
+	}
+}
+
+/// Identifies the output of a cook.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CookKey {
+	/// The asset that was cooked.
+	pub id: UntypedAssetId,
+	/// The type of the cooked asset.
+	pub ty: Uuid,
+	/// The content hash of the source asset the cook chain started from, mixed with the types and versions of any
+	/// intermediate cooks.
+	pub base_hash: u64,
+	/// The `COOK_VERSION` of the cooked asset.
+	pub version: u32,
 }
 
 pub trait AssetSource: Send + Sync + 'static {
-	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, io::Error>;
+	/// Load an asset. Returns `NotFound` or `TypeMismatch` if the source doesn't have it as `ty`, so that the next
+	/// source is tried.
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, AssetError>;
+
+	/// Load cooked data previously stored with `store_cooked`. Returns `NotFound` if there is no cooked data
+	/// matching `key`.
+	fn load_cooked(&self, key: CookKey) -> Result<Box<dyn AssetRead>, AssetError> {
+		Err(AssetError::NotFound { id: key.id, ty: key.ty })
+	}
+
+	/// Store cooked data, replacing anything previously cooked for the same asset and type. Returns `Unsupported`
+	/// if the source cannot cache cooked assets.
+	fn store_cooked(&self, key: CookKey) -> Result<Box<dyn AssetWrite>, io::Error> {
+		let _ = key;
+		Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			"source cannot cache cooked assets",
+		))
+	}
+
+	/// Remove all cooked data derived from `id`.
+	fn invalidate_cooked(&self, id: UntypedAssetId) -> Result<(), io::Error> {
+		let _ = id;
+		Ok(())
+	}
+
+	/// Remove all cooked data.
+	fn purge_cooked(&self) -> Result<(), io::Error> { Ok(()) }
+
+	/// Overwrite an asset with data of the given `version`, used to write back migrated assets. Returns
+	/// `Unsupported` if the source is read-only.
+	fn rewrite(&self, id: UntypedAssetId, ty: Uuid, version: u32) -> Result<Box<dyn AssetWrite>, io::Error> {
+		let _ = (id, ty, version);
+		Err(io::Error::new(io::ErrorKind::Unsupported, "source is read-only"))
+	}
 }
 impl<T: AssetSource> AssetSource for Arc<T> {
-	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, io::Error> {
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, AssetError> {
 		T::load(self.as_ref(), id, ty)
 	}
+
+	fn load_cooked(&self, key: CookKey) -> Result<Box<dyn AssetRead>, AssetError> { T::load_cooked(self.as_ref(), key) }
+
+	fn store_cooked(&self, key: CookKey) -> Result<Box<dyn AssetWrite>, io::Error> {
+		T::store_cooked(self.as_ref(), key)
+	}
+
+	fn invalidate_cooked(&self, id: UntypedAssetId) -> Result<(), io::Error> { T::invalidate_cooked(self.as_ref(), id) }
+
+	fn purge_cooked(&self) -> Result<(), io::Error> { T::purge_cooked(self.as_ref()) }
+
+	fn rewrite(&self, id: UntypedAssetId, ty: Uuid, version: u32) -> Result<Box<dyn AssetWrite>, io::Error> {
+		T::rewrite(self.as_ref(), id, ty, version)
+	}
+}
+
+/// Type-erased operations on a registered asset.
+struct AssetVTable {
+	name: &'static str,
+	version: u32,
+	layout: Layout,
+	load: fn(from: Box<dyn AssetRead>, out: *mut ()) -> Result<(), io::Error>,
+	save: fn(asset: *const (), to: &mut dyn AssetWrite) -> Result<(), io::Error>,
+	references_assets: bool,
+	dependencies: fn(asset: *const ()) -> Vec<(UntypedAssetId, Uuid)>,
+	drop: unsafe fn(asset: *mut ()),
+	stats: AssetCounters,
 }
 
+/// Upgrades the serialized data of an asset by one version.
+pub type Migration = fn(Vec<u8>) -> Result<Vec<u8>, io::Error>;
+
 struct Kitchen {
-	base: Uuid,
-	base_layout: Layout,
+	version: u32,
 	cook: fn(base: *const (), out: *mut ()),
 }
-type ErasedAssetLoad = fn(from: Box<dyn AssetRead>, out: *mut ()) -> Result<(), io::Error>;
+
+/// An asset that some source has, and the types that must be cooked from it to get to the requested one.
+struct CookChain {
+	source: Box<dyn AssetRead>,
+	from_ty: Uuid,
+	/// In cook order.
+	steps: Vec<Uuid>,
+}
+
+/// A type-erased asset on the heap, used to hold the intermediate steps of a cook chain.
+struct ErasedAsset<'a> {
+	ptr: NonNull<u8>,
+	vtable: &'a AssetVTable,
+}
+
+impl<'a> ErasedAsset<'a> {
+	fn new<E>(vtable: &'a AssetVTable, init: impl FnOnce(*mut ()) -> Result<(), E>) -> Result<Self, E> {
+		let ptr = if vtable.layout.size() == 0 {
+			NonNull::new(std::ptr::without_provenance_mut(vtable.layout.align())).unwrap()
+		} else {
+			let ptr = unsafe { std::alloc::alloc(vtable.layout) };
+			NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(vtable.layout))
+		};
+
+		match init(ptr.as_ptr() as _) {
+			Ok(()) => Ok(Self { ptr, vtable }),
+			Err(e) => {
+				if vtable.layout.size() != 0 {
+					unsafe { std::alloc::dealloc(ptr.as_ptr(), vtable.layout) }
+				}
+				Err(e)
+			},
+		}
+	}
+
+	fn as_ptr(&self) -> *const () { self.ptr.as_ptr() as _ }
+}
+
+impl Drop for ErasedAsset<'_> {
+	fn drop(&mut self) {
+		unsafe {
+			(self.vtable.drop)(self.ptr.as_ptr() as _);
+			if self.vtable.layout.size() != 0 {
+				std::alloc::dealloc(self.ptr.as_ptr(), self.vtable.layout);
+			}
+		}
+	}
+}
+
+/// Counts a background load as pending until it is dropped, even if the load panics.
+struct PendingLoad(&'static AssetRegistry);
+
+impl PendingLoad {
+	fn new(registry: &'static AssetRegistry) -> Self {
+		*registry.pending_loads.lock().unwrap() += 1;
+		Self(registry)
+	}
+}
+
+impl Drop for PendingLoad {
+	fn drop(&mut self) {
+		let mut pending = self.0.pending_loads.lock().unwrap();
+		*pending -= 1;
+		if *pending == 0 {
+			self.0.loads_done.notify_all();
+		}
+	}
+}
+
+fn chain_hash(base_hash: u64, ty: Uuid, version: u32) -> u64 {
+	let mut bytes = [0; 28];
+	bytes[..8].copy_from_slice(&base_hash.to_le_bytes());
+	bytes[8..24].copy_from_slice(ty.as_bytes());
+	bytes[24..].copy_from_slice(&version.to_le_bytes());
+	xxh3_64(&bytes)
+}
 
 pub struct AssetRegistry {
 	cook_dep_graph: DiGraph<Uuid, ()>,
 	asset_indices: FxHashMap<Uuid, NodeIndex>,
 	sources: Vec<Box<dyn AssetSource>>,
 	source_to_index: FxHashMap<TypeId, usize>,
-	assets: FxHashMap<Uuid, ErasedAssetLoad>,
-	view_caches: FxHashMap<TypeId, Box<dyn Any + Send + Sync>>,
+	assets: FxHashMap<Uuid, AssetVTable>,
+	view_caches: FxHashMap<TypeId, Box<dyn ViewCache>>,
 	kitchens: FxHashMap<Uuid, Kitchen>,
+	migrations: FxHashMap<(Uuid, u32), Migration>,
+	save_intermediates: bool,
+	rewrite_migrated: bool,
+	loader: OnceLock<rayon::ThreadPool>,
+	pending_loads: Mutex<usize>,
+	/// Notified when `pending_loads` drops to zero.
+	loads_done: Condvar,
 }
 
 impl Default for AssetRegistry {
@@ -133,6 +461,12 @@ impl AssetRegistry {
 			assets: FxHashMap::default(),
 			view_caches: FxHashMap::default(),
 			kitchens: FxHashMap::default(),
+			migrations: FxHashMap::default(),
+			save_intermediates: false,
+			rewrite_migrated: false,
+			loader: OnceLock::new(),
+			pending_loads: Mutex::new(0),
+			loads_done: Condvar::new(),
 		}
 	}
 
@@ -143,31 +477,44 @@ impl AssetRegistry {
 	}
 
 	pub fn register_asset<T: Asset>(&mut self) {
-		self.assets.insert(T::UUID, |from, out| {
-			let out = out as *mut T;
-			unsafe {
-				out.write(T::load(from)?);
-			}
-			Ok(())
-		});
-		let index = self.cook_dep_graph.add_node(T::UUID);
-		self.asset_indices.insert(T::UUID, index);
+		self.assets.insert(
+			T::UUID,
+			AssetVTable {
+				name: std::any::type_name::<T>(),
+				version: T::VERSION,
+				layout: Layout::new::<T>(),
+				load: |from, out| {
+					let out = out as *mut T;
+					unsafe {
+						out.write(T::load(from)?);
+					}
+					Ok(())
+				},
+				save: |asset, to| {
+					let asset = unsafe { &*(asset as *const T) };
+					asset.save(to)
+				},
+				references_assets: T::REFERENCES_ASSETS,
+				dependencies: |asset| unsafe { &*(asset as *const T) }.dependencies(),
+				drop: |asset| unsafe { std::ptr::drop_in_place(asset as *mut T) },
+				stats: AssetCounters::default(),
+			},
+		);
+		self.node(T::UUID);
 	}
 
+	/// Register a cooked asset. The base asset does not have to be registered yet, but it must be by the time
+	/// anything is cooked.
 	pub fn register_cooked<T: CookedAsset>(&mut self) {
 		self.register_asset::<T>();
-		let base_index = self
-			.asset_indices
-			.get(&T::Base::UUID)
-			.expect("base asset not registered");
-		let cooked_index = self.asset_indices.get(&T::UUID).unwrap();
-		self.cook_dep_graph.add_edge(*cooked_index, *base_index, ());
+		let base_index = self.node(T::Base::UUID);
+		let cooked_index = self.node(T::UUID);
+		self.cook_dep_graph.update_edge(cooked_index, base_index, ());
 
 		self.kitchens.insert(
 			T::UUID,
 			Kitchen {
-				base: T::Base::UUID,
-				base_layout: Layout::new::<T::Base>(),
+				version: T::COOK_VERSION,
 				cook: |base, out| {
 					let base = unsafe { &*(base as *const T::Base) };
 					let out = out as *mut T;
@@ -179,11 +526,29 @@ impl AssetRegistry {
 		);
 	}
 
+	/// Register a migration that upgrades serialized `T`s from version `from` to `from + 1`.
+	pub fn register_migration<T: Asset>(&mut self, from: u32, migrate: Migration) {
+		self.migrations.insert((T::UUID, from), migrate);
+	}
+
 	pub fn register_view<T: AssetView>(&mut self) {
 		self.view_caches
 			.insert(TypeId::of::<T>(), Box::new(AssetCache::<T>::new()));
 	}
 
+	/// The context shared by all views of type `T`.
+	pub fn view_context<T: AssetView>(&self) -> &T::Ctx { self.cache::<T>().context() }
+
+	/// Keep up to `budget` bytes of unreferenced views of type `T` alive, evicting the least recently used ones.
+	pub fn set_view_budget<T: AssetView>(&self, budget: Option<usize>) { self.cache::<T>().set_budget(budget); }
+
+	/// Also store the intermediate steps of cook chains in the cook cache, so changing a later step doesn't have to
+	/// recook the earlier ones.
+	pub fn save_cook_intermediates(&mut self, save: bool) { self.save_intermediates = save; }
+
+	/// Write migrated assets back to their source, so they don't have to be migrated again on the next load.
+	pub fn rewrite_migrated(&mut self, rewrite: bool) { self.rewrite_migrated = rewrite; }
+
 	pub fn source<T: AssetSource>(&self) -> &T {
 		match self.source_to_index.get(&TypeId::of::<T>()) {
 			Some(&source) => unsafe { &*(self.sources[source].as_ref() as *const dyn AssetSource as *const T) },
@@ -195,53 +560,469 @@ impl AssetRegistry {
 		match self
 			.view_caches
 			.get(&TypeId::of::<T>())
-			.and_then(|cache| cache.downcast_ref::<AssetCache<T>>())
+			.and_then(|cache| cache.as_any().downcast_ref::<AssetCache<T>>())
 		{
 			Some(cache) => cache,
 			None => panic!("view `{}` not registered", std::any::type_name::<T>()),
 		}
 	}
 
-	#[inline(always)]
-	fn cook_dynamic(&self, id: UntypedAssetId, ty: Uuid, base: *const (), into: *mut ()) {
-		let s = trace_span!("cook asset", id = %id, ty = %ty);
+	fn loader(&self) -> &rayon::ThreadPool {
+		self.loader.get_or_init(|| {
+			rayon::ThreadPoolBuilder::new()
+				.thread_name(|i| format!("asset loader {i}"))
+				.build()
+				.expect("failed to create asset loader pool")
+		})
+	}
+
+	/// Run `load` on the background loader pool, with the current engine entered.
+	fn spawn_load(&'static self, load: impl FnOnce() + Send + 'static) {
+		let engine = Engine::get();
+		let pending = PendingLoad::new(self);
+		self.loader().spawn(move || {
+			let _pending = pending;
+			engine.enter(load);
+		});
+	}
+
+	/// Run `load` on every item in parallel on the loader pool, with the current engine entered. Blocks until all of
+	/// them are done.
+	fn par_load<I: Sync, R: Send>(&self, items: &[I], load: impl Fn(&I) -> R + Sync) -> Vec<R> {
+		let engine = Engine::get();
+		self.loader()
+			.install(|| items.par_iter().map(|x| engine.enter(|| load(x))).collect())
+	}
+
+	/// The number of asset views currently being loaded in the background.
+	pub fn pending_loads(&self) -> usize { *self.pending_loads.lock().unwrap() }
+
+	/// Block until all background loads are done.
+	pub fn wait_for_loads(&self) {
+		let pending = self.pending_loads.lock().unwrap();
+		drop(self.loads_done.wait_while(pending, |x| *x > 0).unwrap());
+	}
+
+	fn node(&mut self, ty: Uuid) -> NodeIndex {
+		*self
+			.asset_indices
+			.entry(ty)
+			.or_insert_with(|| self.cook_dep_graph.add_node(ty))
+	}
+
+	/// Load and cook statistics of every registered asset type, with the type names.
+	pub fn asset_stats(&self) -> Vec<(&'static str, AssetStats)> {
+		self.assets.values().map(|x| (x.name, x.stats.get())).collect()
+	}
+
+	/// Statistics of every registered view type, with the type names.
+	pub fn view_stats(&self) -> Vec<(&'static str, ViewStats)> {
+		self.view_caches.values().map(|x| (x.name(), x.stats())).collect()
+	}
+
+	/// The type name of a registered asset.
+	pub fn asset_name(&self, ty: Uuid) -> Option<&'static str> { self.assets.get(&ty).map(|x| x.name) }
+
+	fn name(&self, ty: Uuid) -> String {
+		self.assets
+			.get(&ty)
+			.map(|x| x.name.to_string())
+			.unwrap_or_else(|| ty.to_string())
+	}
+
+	fn vtable(&self, ty: Uuid) -> Result<&AssetVTable, AssetError> {
+		self.assets.get(&ty).ok_or(AssetError::Unregistered(ty))
+	}
+
+	/// Walk the cook graph from `ty` towards the root until an asset that some source has is found.
+	fn cook_chain(&self, id: UntypedAssetId, ty: Uuid) -> Result<CookChain, AssetError> {
+		let cook_err = |reason| AssetError::Cook { id, ty, reason };
+		let mut steps = vec![ty];
+		loop {
+			let cooked = *steps.last().unwrap();
+			let base = self
+				.asset_indices
+				.get(&cooked)
+				.and_then(|&x| self.cook_dep_graph.neighbors(x).next())
+				.map(|x| self.cook_dep_graph[x])
+				.ok_or_else(|| cook_err(format!("`{}` is not a registered cooked asset", self.name(cooked))))?;
+			if !self.assets.contains_key(&base) {
+				return Err(cook_err(format!(
+					"`{}` is cooked from `{base}`, which is not registered",
+					self.name(cooked)
+				)));
+			}
+
+			match self.load_source(id, base) {
+				Ok(source) => {
+					steps.reverse();
+					return Ok(CookChain {
+						source,
+						from_ty: base,
+						steps,
+					});
+				},
+				// Assets that are cooked from themselves can only come from a source.
+				Err(e) if base == cooked => return Err(e),
+				Err(e) if e.is_missing() && self.kitchens.contains_key(&base) => {
+					if let Some(i) = steps.iter().position(|&x| x == base) {
+						let cycle: Vec<_> = steps[i..].iter().chain([&base]).map(|&x| self.name(x)).collect();
+						return Err(cook_err(format!("cook cycle: {}", cycle.join(" -> "))));
+					}
+					steps.push(base);
+				},
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	fn cook_dynamic(&self, id: UntypedAssetId, ty: Uuid, into: *mut ()) -> Result<(), AssetError> {
+		let CookChain {
+			mut source,
+			from_ty: base,
+			steps,
+		} = self.cook_chain(id, ty)?;
+
+		let mut hash = match source.content_hash() {
+			Some(hash) => hash,
+			None => {
+				let mut bytes = Vec::new();
+				source.read_to_end(&mut bytes)?;
+				let hash = xxh3_64(&bytes);
+				source = Box::new(io::Cursor::new(bytes));
+				hash
+			},
+		};
+		let keys: Vec<_> = steps
+			.iter()
+			.map(|&ty| {
+				let version = self.kitchens[&ty].version;
+				let key = CookKey {
+					id,
+					ty,
+					base_hash: hash,
+					version,
+				};
+				hash = chain_hash(hash, ty, version);
+				key
+			})
+			.collect();
+
+		// Start from the latest step of the chain that is already cooked. Cooked data that fails to load is recooked.
+		let mut start = 0;
+		let mut cooked_base = None;
+		for (i, &key) in keys.iter().enumerate().rev() {
+			let Some(cooked) = self.load_cooked(key) else {
+				continue;
+			};
+			let vtable = self.vtable(key.ty)?;
+			let timer = Instant::now();
+			let res = if i == keys.len() - 1 {
+				(vtable.load)(vtable.stats.count(cooked), into).map(|()| None)
+			} else {
+				ErasedAsset::new(vtable, |out| (vtable.load)(vtable.stats.count(cooked), out)).map(Some)
+			};
+			match res {
+				Ok(None) => {
+					vtable.stats.load(timer.elapsed());
+					return Ok(());
+				},
+				Ok(Some(x)) => {
+					vtable.stats.load(timer.elapsed());
+					start = i + 1;
+					cooked_base = Some(x);
+					break;
+				},
+				Err(e) => warn!(
+					"failed to load cooked asset {id} ({}), recooking: {e:?}",
+					self.name(key.ty)
+				),
+			}
+		}
+
+		let mut base = match cooked_base {
+			Some(x) => x,
+			None => {
+				let vtable = self.vtable(base)?;
+				let timer = Instant::now();
+				let asset = ErasedAsset::new(vtable, |out| {
+					(vtable.load)(vtable.stats.count(source), out).map_err(|e| AssetError::decode(id, base, e))
+				})?;
+				vtable.stats.load(timer.elapsed());
+				asset
+			},
+		};
+		for (i, &key) in keys.iter().enumerate().skip(start) {
+			let s = trace_span!("cook asset", id = %id, ty = %key.ty);
+			let _e = s.enter();
+
+			let kitchen = &self.kitchens[&key.ty];
+			let vtable = self.vtable(key.ty)?;
+			let timer = Instant::now();
+			if i == keys.len() - 1 {
+				(kitchen.cook)(base.as_ptr(), into);
+				vtable.stats.cook(timer.elapsed());
+				self.store_cooked(key, vtable, into);
+			} else {
+				base = ErasedAsset::new(vtable, |out| {
+					(kitchen.cook)(base.as_ptr(), out);
+					Ok::<_, AssetError>(())
+				})?;
+				vtable.stats.cook(timer.elapsed());
+				if self.save_intermediates {
+					self.store_cooked(key, vtable, base.as_ptr());
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn load_cooked(&self, key: CookKey) -> Option<Box<dyn AssetRead>> {
+		for src in self.sources.iter().rev() {
+			match src.load_cooked(key) {
+				Ok(from) => return Some(from),
+				Err(e) if e.is_missing() => continue,
+				Err(e) => {
+					warn!("failed to load cooked asset {} ({}): {e:?}", key.id, self.name(key.ty));
+					return None;
+				},
+			}
+		}
+		None
+	}
+
+	fn store_cooked(&self, key: CookKey, vtable: &AssetVTable, cooked: *const ()) {
+		let s = trace_span!("store cooked asset", id = %key.id, ty = %key.ty);
 		let _e = s.enter();
 
-		(self.kitchens.get(&ty).expect("asset not registered").cook)(base, into);
+		for src in self.sources.iter().rev() {
+			match src.store_cooked(key) {
+				Ok(mut to) => {
+					if let Err(e) = (vtable.save)(cooked, to.as_mut()) {
+						warn!("failed to store cooked asset {} ({}): {e:?}", key.id, vtable.name);
+					}
+					return;
+				},
+				Err(e) if e.kind() == io::ErrorKind::Unsupported => continue,
+				Err(e) => {
+					warn!("failed to store cooked asset {} ({}): {e:?}", key.id, vtable.name);
+					return;
+				},
+			}
+		}
 	}
 
-	pub fn cook_asset<T: CookedAsset>(&self, id: AssetId<T::Root>) -> Result<T, io::Error> {
-		let base: T::Base = self.load_asset(id)?;
+	pub fn cook_asset<T: CookedAsset>(&self, id: AssetId<T::Root>) -> Result<T, AssetError> {
 		let mut out = MaybeUninit::<T>::uninit();
-		self.cook_dynamic(
-			id.to_untyped(),
-			T::UUID,
-			&base as *const _ as _,
-			out.as_mut_ptr() as *mut (),
-		);
+		self.cook_dynamic(id.to_untyped(), T::UUID, out.as_mut_ptr() as *mut ())?;
 		Ok(unsafe { out.assume_init() })
 	}
 
-	fn load_dynamic(&self, id: UntypedAssetId, ty: Uuid, into: *mut ()) -> Result<(), io::Error> {
-		let s = trace_span!("load asset", id = %id, ty = %ty);
+	/// Write `assets` into a pack at `to`, along with everything cooked from them so that nothing has to be cooked
+	/// when loading from the pack. Returns the number of entries written.
+	pub fn bake(
+		&self, to: &Path, assets: impl IntoIterator<Item = (UntypedAssetId, Uuid)>,
+	) -> Result<usize, AssetError> {
+		let s = trace_span!("bake pack", path = %to.display());
+		let _e = s.enter();
+
+		let mut pack = PackWriter::create(to)?;
+		let mut bytes = Vec::new();
+		for (id, ty) in assets {
+			let vtable = self.vtable(ty)?;
+			bytes.clear();
+			self.load_source(id, ty)?.read_to_end(&mut bytes)?;
+			pack.add(id, ty, vtable.version, &bytes)?;
+
+			for cooked in self.cooked_from(ty) {
+				let vtable = self.vtable(cooked)?;
+				let asset = ErasedAsset::new(vtable, |out| self.cook_dynamic(id, cooked, out))?;
+				bytes.clear();
+				(vtable.save)(asset.as_ptr(), &mut bytes)?;
+				pack.add(id, cooked, vtable.version, &bytes)?;
+			}
+		}
+
+		let count = pack.len();
+		pack.finish()?;
+		Ok(count)
+	}
+
+	/// All the cooked assets that can be cooked from `ty`, directly or through other cooked assets.
+	pub fn cooked_from(&self, ty: Uuid) -> Vec<Uuid> {
+		let mut out = Vec::new();
+		let mut stack: Vec<_> = self.asset_indices.get(&ty).copied().into_iter().collect();
+		while let Some(node) = stack.pop() {
+			for cooked in self.cook_dep_graph.neighbors_directed(node, Direction::Incoming) {
+				let cooked_ty = self.cook_dep_graph[cooked];
+				if cooked_ty != ty && self.kitchens.contains_key(&cooked_ty) && !out.contains(&cooked_ty) {
+					out.push(cooked_ty);
+					stack.push(cooked);
+				}
+			}
+		}
+		out
+	}
+
+	/// Cook the asset `ty` from `id`, storing it in the cook cache if it isn't already cooked.
+	pub fn cook_untyped(&self, id: UntypedAssetId, ty: Uuid) -> Result<(), AssetError> {
+		let vtable = self.vtable(ty)?;
+		ErasedAsset::new(vtable, |out| self.cook_dynamic(id, ty, out)).map(drop)
+	}
+
+	/// Load an asset and throw it away, to check that it decodes.
+	pub fn check_asset(&self, id: UntypedAssetId, ty: Uuid) -> Result<(), AssetError> {
+		let vtable = self.vtable(ty)?;
+		ErasedAsset::new(vtable, |out| self.load_dynamic(id, ty, out)).map(drop)
+	}
+
+	/// The assets directly referenced by an asset, and their types. The asset is only loaded if its type can reference
+	/// other assets.
+	pub fn dependencies(&self, id: UntypedAssetId, ty: Uuid) -> Result<Vec<(UntypedAssetId, Uuid)>, AssetError> {
+		let vtable = self.vtable(ty)?;
+		if !vtable.references_assets {
+			return Ok(Vec::new());
+		}
+		let asset = ErasedAsset::new(vtable, |out| self.load_dynamic(id, ty, out))?;
+		Ok((vtable.dependencies)(asset.as_ptr()))
+	}
+
+	/// `roots` and every asset they reference, directly or indirectly.
+	pub fn reachable(
+		&self, roots: impl IntoIterator<Item = (UntypedAssetId, Uuid)>,
+	) -> Result<Vec<(UntypedAssetId, Uuid)>, AssetError> {
+		let mut out: Vec<_> = roots.into_iter().collect();
+		let mut seen: FxHashSet<_> = out.iter().copied().collect();
+		let mut i = 0;
+		while let Some(&(id, ty)) = out.get(i) {
+			for dep in self.dependencies(id, ty)? {
+				if seen.insert(dep) {
+					out.push(dep);
+				}
+			}
+			i += 1;
+		}
+		Ok(out)
+	}
+
+	/// Update every loaded view of `id` after the asset changed.
+	pub fn reload(&'static self, id: UntypedAssetId) -> Result<(), AssetError> {
+		let s = trace_span!("reload asset", id = %id);
 		let _e = s.enter();
 
+		let mut res = Ok(());
+		for cache in self.view_caches.values() {
+			if let Err(e) = cache.reload(id) {
+				res = Err(e);
+			}
+		}
+		res
+	}
+
+	pub fn invalidate_cooked(&self, id: UntypedAssetId) -> Result<(), io::Error> {
+		for src in self.sources.iter() {
+			src.invalidate_cooked(id)?;
+		}
+		Ok(())
+	}
+
+	pub fn purge_cooked(&self) -> Result<(), io::Error> {
+		for src in self.sources.iter() {
+			src.purge_cooked()?;
+		}
+		Ok(())
+	}
+
+	fn load_source(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, AssetError> {
+		let mut missing = AssetError::NotFound { id, ty };
 		for src in self.sources.iter().rev() {
 			match src.load(id, ty) {
-				Ok(from) => {
-					let load = self.assets.get(&ty).expect("asset not registered");
-					load(from, into)?;
-					return Ok(());
-				},
-				Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+				Ok(from) => return self.migrate(src.as_ref(), id, ty, from),
+				// Prefer reporting that the asset exists with a different type over not finding it at all.
+				Err(e @ AssetError::TypeMismatch { .. }) => missing = e,
+				Err(e) if e.is_missing() => continue,
 				Err(e) => return Err(e),
 			}
 		}
 
-		Err(io::Error::new(io::ErrorKind::NotFound, "asset not found"))
+		Err(missing)
+	}
+
+	/// Bring asset data written with an older `VERSION` up to date.
+	fn migrate(
+		&self, src: &dyn AssetSource, id: UntypedAssetId, ty: Uuid, mut from: Box<dyn AssetRead>,
+	) -> Result<Box<dyn AssetRead>, AssetError> {
+		let vtable = self.vtable(ty)?;
+		let Some(version) = from.version() else {
+			return Ok(from);
+		};
+		if version == vtable.version {
+			return Ok(from);
+		}
+		if version > vtable.version {
+			return Err(AssetError::decode(
+				id,
+				ty,
+				io::Error::new(
+					io::ErrorKind::InvalidData,
+					format!(
+						"asset has version {version}, but only versions up to {} are supported",
+						vtable.version
+					),
+				),
+			));
+		}
+
+		let s = trace_span!("migrate asset", id = %id, ty = %ty, from = version, to = vtable.version);
+		let _e = s.enter();
+
+		let mut bytes = Vec::new();
+		from.read_to_end(&mut bytes)?;
+		for v in version..vtable.version {
+			let migrate = self.migrations.get(&(ty, v)).ok_or_else(|| {
+				AssetError::decode(
+					id,
+					ty,
+					io::Error::new(io::ErrorKind::InvalidData, format!("no migration from version {v}")),
+				)
+			})?;
+			bytes = migrate(bytes).map_err(|e| AssetError::decode(id, ty, e))?;
+		}
+
+		if self.rewrite_migrated {
+			match src
+				.rewrite(id, ty, vtable.version)
+				.and_then(|mut to| to.write_all(&bytes))
+			{
+				Ok(()) => {},
+				Err(e) if e.kind() == io::ErrorKind::Unsupported => {},
+				Err(e) => warn!("failed to rewrite migrated asset {id} ({}): {e:?}", vtable.name),
+			}
+		}
+
+		Ok(Box::new(io::Cursor::new(bytes)))
+	}
+
+	fn load_dynamic(&self, id: UntypedAssetId, ty: Uuid, into: *mut ()) -> Result<(), AssetError> {
+		let s = trace_span!("load asset", id = %id, ty = %ty);
+		let _e = s.enter();
+
+		let timer = Instant::now();
+		match self.load_source(id, ty) {
+			Ok(from) => {
+				let vtable = self.vtable(ty)?;
+				(vtable.load)(vtable.stats.count(from), into).map_err(|e| AssetError::decode(id, ty, e))?;
+				vtable.stats.load(timer.elapsed());
+				Ok(())
+			},
+			// Cooked assets that aren't in any source get cooked on demand.
+			Err(e) if e.is_missing() && self.kitchens.contains_key(&ty) => self.cook_dynamic(id, ty, into),
+			Err(e) => Err(e),
+		}
 	}
 
-	pub fn load_asset<T: Asset>(&self, id: AssetId<T::Root>) -> Result<T, io::Error> {
+	pub fn load_asset<T: Asset>(&self, id: AssetId<T::Root>) -> Result<T, AssetError> {
 		let mut out = MaybeUninit::<T>::uninit();
 		self.load_dynamic(id.to_untyped(), T::UUID, out.as_mut_ptr() as *mut ())?;
 		Ok(unsafe { out.assume_init() })
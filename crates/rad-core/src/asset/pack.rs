@@ -0,0 +1,280 @@
+use std::{
+	fs,
+	io::{self, BufRead, BufWriter, Read, Seek, SeekFrom, Write},
+	path::Path,
+	sync::Arc,
+};
+
+use bytemuck::{Pod, Zeroable};
+use memmap2::Mmap;
+use rustc_hash::FxHashMap;
+use tracing::trace_span;
+use uuid::Uuid;
+use zstd::Decoder;
+
+use crate::asset::{aref::UntypedAssetId, AssetError, AssetRead, AssetSource};
+
+const PACK_MAGIC: [u8; 4] = *b"RADP";
+/// The version of the pack format.
+const PACK_VERSION: u32 = 1;
+
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct PackHeader {
+	magic: [u8; 4],
+	version: u32,
+	entry_count: u64,
+	/// The offset of the table of contents, which is at the end of the file.
+	toc_offset: u64,
+}
+
+/// An entry in the table of contents of a pack.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct PackEntry {
+	id: UntypedAssetId,
+	ty: Uuid,
+	/// The `Asset::VERSION` the entry was written with.
+	version: u32,
+	_pad: u32,
+	offset: u64,
+	size: u64,
+}
+
+/// Writes assets into a pack, to be read by `PackAssetSource`. Every entry is compressed separately so it can be
+/// read without touching the rest of the pack.
+pub struct PackWriter {
+	file: BufWriter<fs::File>,
+	entries: Vec<PackEntry>,
+	offset: u64,
+}
+
+impl PackWriter {
+	pub fn create(path: &Path) -> Result<Self, io::Error> {
+		let mut file = BufWriter::new(fs::File::create(path)?);
+		file.write_all(bytemuck::bytes_of(&PackHeader::zeroed()))?;
+		Ok(Self {
+			file,
+			entries: Vec::new(),
+			offset: std::mem::size_of::<PackHeader>() as _,
+		})
+	}
+
+	/// Add a serialized asset of type `ty`, written with `version` of the asset type.
+	pub fn add(&mut self, id: UntypedAssetId, ty: Uuid, version: u32, data: &[u8]) -> Result<(), io::Error> {
+		let s = trace_span!("pack asset", id = %id, ty = %ty);
+		let _e = s.enter();
+
+		let data = zstd::encode_all(data, 5)?;
+		self.file.write_all(&data)?;
+		self.entries.push(PackEntry {
+			id,
+			ty,
+			version,
+			_pad: 0,
+			offset: self.offset,
+			size: data.len() as _,
+		});
+		self.offset += data.len() as u64;
+		Ok(())
+	}
+
+	pub fn len(&self) -> usize { self.entries.len() }
+
+	pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+	/// Write the table of contents and finish the pack.
+	pub fn finish(mut self) -> Result<(), io::Error> {
+		self.file.write_all(bytemuck::cast_slice(&self.entries))?;
+		let header = PackHeader {
+			magic: PACK_MAGIC,
+			version: PACK_VERSION,
+			entry_count: self.entries.len() as _,
+			toc_offset: self.offset,
+		};
+		self.file.seek(SeekFrom::Start(0))?;
+		self.file.write_all(bytemuck::bytes_of(&header))?;
+		self.file.flush()
+	}
+}
+
+/// A read-only asset source backed by a memory-mapped pack written by `PackWriter`.
+pub struct PackAssetSource {
+	map: Arc<Mmap>,
+	entries: FxHashMap<(UntypedAssetId, Uuid), PackEntry>,
+}
+
+impl PackAssetSource {
+	pub fn open(path: &Path) -> Result<Self, io::Error> {
+		let s = trace_span!("open pack", path = %path.display());
+		let _e = s.enter();
+
+		let file = fs::File::open(path)?;
+		let map = unsafe { Mmap::map(&file)? };
+
+		let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {msg}", path.display()));
+		let header: PackHeader = map
+			.get(..std::mem::size_of::<PackHeader>())
+			.map(bytemuck::pod_read_unaligned)
+			.ok_or_else(|| invalid("pack is truncated"))?;
+		if header.magic != PACK_MAGIC {
+			return Err(invalid("not a pack"));
+		}
+		if header.version > PACK_VERSION {
+			return Err(invalid(&format!(
+				"pack version {} is newer than the supported version {PACK_VERSION}",
+				header.version
+			)));
+		}
+
+		let size = std::mem::size_of::<PackEntry>();
+		let toc = usize::try_from(header.entry_count)
+			.ok()
+			.and_then(|x| x.checked_mul(size))
+			.zip(usize::try_from(header.toc_offset).ok())
+			.and_then(|(len, offset)| map.get(offset..)?.get(..len))
+			.ok_or_else(|| invalid("pack is truncated"))?;
+		let mut entries = FxHashMap::default();
+		for entry in toc.chunks_exact(size) {
+			let entry: PackEntry = bytemuck::pod_read_unaligned(entry);
+			if entry
+				.offset
+				.checked_add(entry.size)
+				.is_none_or(|end| end > map.len() as u64)
+			{
+				return Err(invalid("pack is truncated"));
+			}
+			entries.insert((entry.id, entry.ty), entry);
+		}
+
+		Ok(Self {
+			map: Arc::new(map),
+			entries,
+		})
+	}
+
+	pub fn contains(&self, id: UntypedAssetId, ty: Uuid) -> bool { self.entries.contains_key(&(id, ty)) }
+
+	/// All the assets in the pack, and their types.
+	pub fn assets(&self) -> impl Iterator<Item = (UntypedAssetId, Uuid)> + '_ { self.entries.keys().copied() }
+}
+
+impl AssetSource for PackAssetSource {
+	fn load(&self, id: UntypedAssetId, ty: Uuid) -> Result<Box<dyn AssetRead>, AssetError> {
+		let entry = self.entries.get(&(id, ty)).ok_or(AssetError::NotFound { id, ty })?;
+		let data = MapSlice {
+			map: self.map.clone(),
+			pos: entry.offset as _,
+			end: (entry.offset + entry.size) as _,
+		};
+		Ok(Box::new(PackRead {
+			version: entry.version,
+			read: Decoder::with_buffer(data)?,
+		}))
+	}
+}
+
+/// A range of the memory-mapped pack.
+struct MapSlice {
+	map: Arc<Mmap>,
+	pos: usize,
+	end: usize,
+}
+
+impl Read for MapSlice {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.fill_buf()?.read(buf)?;
+		self.consume(read);
+		Ok(read)
+	}
+}
+
+impl BufRead for MapSlice {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> { Ok(&self.map[self.pos..self.end]) }
+
+	fn consume(&mut self, amt: usize) { self.pos = (self.pos + amt).min(self.end); }
+}
+
+struct PackRead {
+	version: u32,
+	read: Decoder<'static, MapSlice>,
+}
+
+impl Read for PackRead {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.read.read(buf) }
+}
+
+impl AssetRead for PackRead {
+	fn version(&self) -> Option<u32> { Some(self.version) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::asset::aref::AssetId;
+
+	#[test]
+	fn roundtrip() {
+		let path = std::env::temp_dir().join(format!("rad-pack-{}.radpack", std::process::id()));
+		let a = AssetId::<()>::new().to_untyped();
+		let b = AssetId::<()>::new().to_untyped();
+		let ty = Uuid::new_v4();
+
+		let mut pack = PackWriter::create(&path).unwrap();
+		pack.add(a, ty, 1, b"hello").unwrap();
+		pack.add(b, ty, 2, &[7; 4096]).unwrap();
+		pack.finish().unwrap();
+
+		let source = PackAssetSource::open(&path).unwrap();
+		let mut read = source.load(a, ty).unwrap();
+		let mut data = Vec::new();
+		read.read_to_end(&mut data).unwrap();
+		assert_eq!(data, b"hello");
+		assert_eq!(read.version(), Some(1));
+
+		let mut read = source.load(b, ty).unwrap();
+		data.clear();
+		read.read_to_end(&mut data).unwrap();
+		assert_eq!(data, [7; 4096]);
+		assert_eq!(read.version(), Some(2));
+
+		assert!(matches!(
+			source.load(a, Uuid::new_v4()),
+			Err(AssetError::NotFound { .. })
+		));
+
+		drop(source);
+		let _ = fs::remove_file(path);
+	}
+
+	#[test]
+	fn truncated() {
+		let path = std::env::temp_dir().join(format!("rad-pack-truncated-{}.radpack", std::process::id()));
+		let mut pack = PackWriter::create(&path).unwrap();
+		pack.add(AssetId::<()>::new().to_untyped(), Uuid::new_v4(), 1, b"hello")
+			.unwrap();
+		pack.finish().unwrap();
+		let bytes = fs::read(&path).unwrap();
+
+		let header = std::mem::size_of::<PackHeader>();
+		let entry = bytes.len() - std::mem::size_of::<PackEntry>();
+		let open = |patch: &dyn Fn(&mut PackHeader, &mut PackEntry)| {
+			let mut bytes = bytes.clone();
+			let mut h: PackHeader = bytemuck::pod_read_unaligned(&bytes[..header]);
+			let mut e: PackEntry = bytemuck::pod_read_unaligned(&bytes[entry..]);
+			patch(&mut h, &mut e);
+			bytes[..header].copy_from_slice(bytemuck::bytes_of(&h));
+			bytes[entry..].copy_from_slice(bytemuck::bytes_of(&e));
+			fs::write(&path, bytes).unwrap();
+			PackAssetSource::open(&path).map(|_| ())
+		};
+		assert!(open(&|_, _| {}).is_ok());
+		let truncated = |x: Result<(), io::Error>| x.unwrap_err().kind() == io::ErrorKind::InvalidData;
+		assert!(truncated(open(&|h, _| h.entry_count = u64::MAX / 2)));
+		assert!(truncated(open(&|h, _| h.toc_offset = u64::MAX)));
+		assert!(truncated(open(&|_, e| e.offset = u64::MAX)));
+		assert!(truncated(open(&|_, e| e.size = u64::MAX)));
+
+		let _ = fs::remove_file(path);
+	}
+}
@@ -0,0 +1,115 @@
+use std::{
+	io::{self, Read},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+use crate::asset::{chunk::ChunkReader, AssetRead};
+
+/// Load and cook statistics of an asset type, since the engine was created.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AssetStats {
+	/// The number of times the asset was loaded, from a source or the cooked asset cache.
+	pub loads: u64,
+	pub load_time: Duration,
+	/// The number of bytes decoded by loads. Chunks read after the asset was loaded are not counted.
+	pub load_bytes: u64,
+	pub cooks: u64,
+	pub cook_time: Duration,
+}
+
+/// Statistics of an asset view type.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ViewStats {
+	/// The number of views loaded since the engine was created.
+	pub loads: u64,
+	/// The time spent loading views, including loading and cooking their assets.
+	pub load_time: Duration,
+	/// The number of views currently in the cache.
+	pub live: usize,
+	/// The total `AssetView::size` of the live views.
+	pub live_size: usize,
+}
+
+#[derive(Default)]
+pub(super) struct AssetCounters {
+	loads: AtomicU64,
+	load_nanos: AtomicU64,
+	load_bytes: Arc<AtomicU64>,
+	cooks: AtomicU64,
+	cook_nanos: AtomicU64,
+}
+
+impl AssetCounters {
+	pub fn load(&self, time: Duration) {
+		self.loads.fetch_add(1, Ordering::Relaxed);
+		self.load_nanos.fetch_add(time.as_nanos() as _, Ordering::Relaxed);
+	}
+
+	pub fn cook(&self, time: Duration) {
+		self.cooks.fetch_add(1, Ordering::Relaxed);
+		self.cook_nanos.fetch_add(time.as_nanos() as _, Ordering::Relaxed);
+	}
+
+	/// Count the bytes read from `from` towards `AssetStats::load_bytes`.
+	pub fn count(&self, from: Box<dyn AssetRead>) -> Box<dyn AssetRead> {
+		Box::new(CountRead {
+			inner: from,
+			count: self.load_bytes.clone(),
+		})
+	}
+
+	pub fn get(&self) -> AssetStats {
+		AssetStats {
+			loads: self.loads.load(Ordering::Relaxed),
+			load_time: Duration::from_nanos(self.load_nanos.load(Ordering::Relaxed)),
+			load_bytes: self.load_bytes.load(Ordering::Relaxed),
+			cooks: self.cooks.load(Ordering::Relaxed),
+			cook_time: Duration::from_nanos(self.cook_nanos.load(Ordering::Relaxed)),
+		}
+	}
+}
+
+#[derive(Default)]
+pub(super) struct ViewCounters {
+	loads: AtomicU64,
+	load_nanos: AtomicU64,
+}
+
+impl ViewCounters {
+	pub fn load(&self, time: Duration) {
+		self.loads.fetch_add(1, Ordering::Relaxed);
+		self.load_nanos.fetch_add(time.as_nanos() as _, Ordering::Relaxed);
+	}
+
+	pub fn get(&self, live: usize, live_size: usize) -> ViewStats {
+		ViewStats {
+			loads: self.loads.load(Ordering::Relaxed),
+			load_time: Duration::from_nanos(self.load_nanos.load(Ordering::Relaxed)),
+			live,
+			live_size,
+		}
+	}
+}
+
+struct CountRead {
+	inner: Box<dyn AssetRead>,
+	count: Arc<AtomicU64>,
+}
+
+impl Read for CountRead {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+		self.count.fetch_add(read as _, Ordering::Relaxed);
+		Ok(read)
+	}
+}
+
+impl AssetRead for CountRead {
+	fn version(&self) -> Option<u32> { self.inner.version() }
+
+	fn chunks(self: Box<Self>) -> Result<ChunkReader, io::Error> { self.inner.chunks() }
+}
@@ -5,48 +5,159 @@
 
 use std::{
 	any::{Any, TypeId},
-	sync::OnceLock,
+	cell::Cell,
+	marker::PhantomData,
+	path::Path,
+	sync::{Mutex, OnceLock},
 };
 
 use rustc_hash::FxHashMap;
-
-use crate::asset::{aref::AssetId, Asset, AssetRegistry, AssetSource, AssetView, CookedAsset};
+use tracing::trace_span;
+
+use crate::asset::{
+	aref::{AssetId, UntypedAssetId},
+	stats::{AssetStats, ViewStats},
+	Asset,
+	AssetError,
+	AssetRegistry,
+	AssetSource,
+	AssetView,
+	CookedAsset,
+	Migration,
+	Uuid,
+};
 
 pub mod asset;
 
 static ENGINE: OnceLock<Engine> = OnceLock::new();
 
+thread_local! {
+	/// The engine entered on this thread, which takes precedence over the global one.
+	static CURRENT: Cell<Option<&'static Engine>> = const { Cell::new(None) };
+}
+
 pub struct Engine {
 	assets: AssetRegistry,
 	globals: GlobalRegistry,
+	/// Initialized modules, in initialization order.
+	modules: Mutex<Vec<ModuleInfo>>,
 }
 
 impl Engine {
 	pub fn builder() -> EngineBuilder { EngineBuilder::new() }
 
+	/// The engine entered on this thread, or the global engine if there is none. Engines are never freed, so this stays
+	/// valid after a scoped engine is dropped, though its modules will have been shut down.
 	pub fn get() -> &'static Engine {
-		ENGINE
+		CURRENT
 			.get()
+			.or_else(|| ENGINE.get())
 			.expect("`Engine` not initialized, call `Engine::builder().build()` first")
 	}
 
+	/// Make this engine the one returned by `Engine::get` on this thread while `f` runs.
+	pub fn enter<R>(&'static self, f: impl FnOnce() -> R) -> R {
+		struct Restore(Option<&'static Engine>);
+		impl Drop for Restore {
+			fn drop(&mut self) { CURRENT.set(self.0); }
+		}
+
+		let _restore = Restore(CURRENT.replace(Some(self)));
+		f()
+	}
+
 	pub fn global<T: Any + Send + Sync>(&self) -> &T { self.try_global().unwrap() }
 
 	pub fn try_global<T: Any + Send + Sync>(&self) -> Option<&T> { self.globals.get() }
 
 	pub fn asset_source<T: AssetSource>(&self) -> &T { self.assets.source::<T>() }
 
-	pub fn load_asset<T: Asset>(&self, id: AssetId<T::Root>) -> Result<T, std::io::Error> { self.assets.load_asset(id) }
+	pub fn load_asset<T: Asset>(&self, id: AssetId<T::Root>) -> Result<T, AssetError> { self.assets.load_asset(id) }
 
-	pub fn cook_asset<T: CookedAsset>(&self, id: AssetId<T::Root>) -> Result<T, std::io::Error> {
+	pub fn cook_asset<T: CookedAsset>(&self, id: AssetId<T::Root>) -> Result<T, AssetError> {
 		self.assets.cook_asset(id)
 	}
 
-	pub unsafe fn destroy() { unsafe { std::ptr::drop_in_place(&ENGINE as *const _ as *mut OnceLock<Engine>); }}
+	/// Cook the asset `ty` from `id`, storing it in the cook cache if it isn't already cooked.
+	pub fn cook_untyped(&self, id: UntypedAssetId, ty: Uuid) -> Result<(), AssetError> {
+		self.assets.cook_untyped(id, ty)
+	}
+
+	/// Load an asset and throw it away, to check that it decodes.
+	pub fn check_asset(&self, id: UntypedAssetId, ty: Uuid) -> Result<(), AssetError> {
+		self.assets.check_asset(id, ty)
+	}
+
+	/// The assets directly referenced by an asset, and their types.
+	pub fn dependencies(&self, id: UntypedAssetId, ty: Uuid) -> Result<Vec<(UntypedAssetId, Uuid)>, AssetError> {
+		self.assets.dependencies(id, ty)
+	}
+
+	/// `roots` and every asset they reference, directly or indirectly.
+	pub fn reachable(
+		&self, roots: impl IntoIterator<Item = (UntypedAssetId, Uuid)>,
+	) -> Result<Vec<(UntypedAssetId, Uuid)>, AssetError> {
+		self.assets.reachable(roots)
+	}
+
+	/// The type name of a registered asset.
+	pub fn asset_name(&self, ty: Uuid) -> Option<&'static str> { self.assets.asset_name(ty) }
+
+	/// All the cooked assets that can be cooked from `ty`, directly or through other cooked assets.
+	pub fn cooked_from(&self, ty: Uuid) -> Vec<Uuid> { self.assets.cooked_from(ty) }
+
+	/// Load and cook statistics of every registered asset type, with the type names.
+	pub fn asset_stats(&self) -> Vec<(&'static str, AssetStats)> { self.assets.asset_stats() }
+
+	/// Statistics of every registered view type, with the type names.
+	pub fn view_stats(&self) -> Vec<(&'static str, ViewStats)> { self.assets.view_stats() }
+
+	/// The number of asset views currently being loaded in the background.
+	pub fn pending_asset_loads(&self) -> usize { self.assets.pending_loads() }
+
+	/// The context shared by all views of type `T`.
+	pub fn view_context<T: AssetView>(&self) -> &T::Ctx { self.assets.view_context::<T>() }
+
+	/// Keep up to `budget` bytes of unreferenced views of type `T` alive, so they don't have to be reloaded if they are
+	/// used again soon. The least recently used views are evicted first. `None` frees views as soon as they are no
+	/// longer referenced.
+	pub fn set_view_budget<T: AssetView>(&self, budget: Option<usize>) { self.assets.set_view_budget::<T>(budget) }
+
+	/// Update every loaded view of `id` after the asset changed, see `AssetView::reload`.
+	pub fn reload_asset(&'static self, id: UntypedAssetId) -> Result<(), AssetError> { self.assets.reload(id) }
+
+	/// Throw away all cooked data derived from `id`, forcing it to be recooked on next use.
+	pub fn invalidate_cooked(&self, id: UntypedAssetId) -> Result<(), std::io::Error> {
+		self.assets.invalidate_cooked(id)
+	}
+
+	/// Write `assets` and everything cooked from them into a pack at `to`, to be loaded with `PackAssetSource`.
+	pub fn bake(
+		&self, to: &Path, assets: impl IntoIterator<Item = (UntypedAssetId, Uuid)>,
+	) -> Result<usize, AssetError> {
+		self.assets.bake(to, assets)
+	}
+
+	/// Throw away all cooked data in every source.
+	pub fn purge_cooked(&self) -> Result<(), std::io::Error> { self.assets.purge_cooked() }
+
+	/// Run the shutdown hooks of all modules, in reverse initialization order. Only the first call does anything,
+	/// and it is called automatically when a scoped engine is dropped. The global engine is never dropped, so it must
+	/// be called before the program exits.
+	pub fn shutdown(&self) {
+		let modules = std::mem::take(&mut *self.modules.lock().unwrap());
+		for module in modules.iter().rev() {
+			let s = trace_span!("shutdown module", module = module.name);
+			let _e = s.enter();
+			(module.shutdown)(self);
+		}
+	}
 }
 
 pub struct EngineBuilder {
 	inner: Engine,
+	/// Modules whose `init` is running, to detect dependency cycles.
+	initializing: Vec<(TypeId, &'static str)>,
 }
 
 impl Default for EngineBuilder {
@@ -61,7 +172,9 @@ impl EngineBuilder {
 			inner: Engine {
 				assets: AssetRegistry::new(),
 				globals: GlobalRegistry::new(),
+				modules: Mutex::new(Vec::new()),
 			},
+			initializing: Vec::new(),
 		}
 	}
 
@@ -75,18 +188,128 @@ impl EngineBuilder {
 
 	pub fn asset_view<T: AssetView>(&mut self) { self.inner.assets.register_view::<T>(); }
 
+	pub fn view_budget<T: AssetView>(&mut self, budget: usize) { self.inner.assets.set_view_budget::<T>(Some(budget)); }
+
+	pub fn save_cook_intermediates(&mut self, save: bool) { self.inner.assets.save_cook_intermediates(save); }
+
+	pub fn migration<T: Asset>(&mut self, from: u32, migrate: Migration) {
+		self.inner.assets.register_migration::<T>(from, migrate);
+	}
+
+	pub fn rewrite_migrated(&mut self, rewrite: bool) { self.inner.assets.rewrite_migrated(rewrite); }
+
 	pub fn get_global<T: Any + Send + Sync>(&mut self) -> &mut T { self.inner.globals.get_mut().unwrap() }
 
+	/// Initialize a module, after its dependencies. Modules that are already initialized are skipped.
 	pub fn module<M: Module>(mut self) -> Self {
-		M::init(&mut self);
+		self.add_module::<M>();
 		self
 	}
 
+	fn add_module<M: Module>(&mut self) {
+		let id = TypeId::of::<M>();
+		let name = std::any::type_name::<M>();
+		if let Some(i) = self.initializing.iter().position(|&(x, _)| x == id) {
+			let cycle: Vec<_> = self.initializing[i..].iter().map(|&(_, x)| x).chain([name]).collect();
+			panic!("module dependency cycle: {}", cycle.join(" -> "));
+		}
+		if self.inner.modules.get_mut().unwrap().iter().any(|x| x.id == id) {
+			return;
+		}
+
+		self.initializing.push((id, name));
+		M::Dependencies::init(self);
+		M::init(self);
+		self.initializing.pop();
+		self.inner.modules.get_mut().unwrap().push(ModuleInfo {
+			id,
+			name,
+			shutdown: M::shutdown,
+		});
+	}
+
+	/// Install the engine globally. Does nothing if there already is a global engine.
 	pub fn build(self) { ENGINE.get_or_init(|| self.inner); }
+
+	/// Build an engine that is only used on this thread, and by the background asset loads it starts, until the
+	/// returned `ScopedEngine` is dropped. This allows several engines with different configurations to exist in one
+	/// process, which is mostly useful for tests and tools. The engine is leaked, like the global engine, so that
+	/// references from `Engine::get` can't outlive it.
+	pub fn build_scoped(self) -> ScopedEngine {
+		let engine: &'static Engine = Box::leak(Box::new(self.inner));
+		ScopedEngine {
+			engine,
+			prev: CURRENT.replace(Some(engine)),
+			_not_send: PhantomData,
+		}
+	}
+}
+
+/// An engine entered on the current thread, see `EngineBuilder::build_scoped`. Dropping it waits for background
+/// asset loads to finish, shuts down its modules, and restores the previously entered engine. Scoped engines on the
+/// same thread must be dropped in the reverse order they were built in.
+pub struct ScopedEngine {
+	engine: &'static Engine,
+	prev: Option<&'static Engine>,
+	_not_send: PhantomData<*const ()>,
+}
+
+impl ScopedEngine {
+	pub fn get(&self) -> &Engine { self.engine }
+}
+
+impl Drop for ScopedEngine {
+	fn drop(&mut self) {
+		// Dropping an outer engine first would leave the inner one restoring a destroyed engine.
+		assert!(
+			CURRENT.get().is_some_and(|x| std::ptr::eq(x, self.engine)),
+			"scoped engines must be dropped in the reverse order they were built in"
+		);
+		self.engine.assets.wait_for_loads();
+		self.engine.shutdown();
+		CURRENT.set(self.prev);
+	}
 }
 
 pub trait Module: 'static {
+	/// The modules that must be initialized before this one, as a tuple.
+	type Dependencies: Modules = ();
+
 	fn init(engine: &mut EngineBuilder);
+
+	/// Release anything the module holds onto, such as GPU resources. Engines are never freed, so this is the only
+	/// chance to do so. Modules are shut down in reverse initialization order.
+	fn shutdown(engine: &Engine) { let _ = engine; }
+}
+
+/// A tuple of modules, see `Module::Dependencies`.
+pub trait Modules {
+	fn init(engine: &mut EngineBuilder);
+}
+
+macro_rules! impl_modules {
+	($($m:ident),*) => {
+		impl<$($m: Module),*> Modules for ($($m,)*) {
+			#[allow(unused_variables)]
+			fn init(engine: &mut EngineBuilder) { $(engine.add_module::<$m>();)* }
+		}
+	};
+}
+
+impl_modules!();
+impl_modules!(A);
+impl_modules!(A, B);
+impl_modules!(A, B, C);
+impl_modules!(A, B, C, D);
+impl_modules!(A, B, C, D, E);
+impl_modules!(A, B, C, D, E, F);
+impl_modules!(A, B, C, D, E, F, G);
+impl_modules!(A, B, C, D, E, F, G, H);
+
+struct ModuleInfo {
+	id: TypeId,
+	name: &'static str,
+	shutdown: fn(&Engine),
 }
 
 struct GlobalRegistry {
@@ -114,3 +337,72 @@ impl GlobalRegistry {
 }
 
 pub use uuid::uuid;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	static LOG: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+
+	struct Base;
+	struct Middle;
+	struct Top;
+
+	impl Module for Base {
+		fn init(_: &mut EngineBuilder) { LOG.lock().unwrap().push("init base"); }
+
+		fn shutdown(_: &Engine) { LOG.lock().unwrap().push("shutdown base"); }
+	}
+
+	impl Module for Middle {
+		type Dependencies = (Base,);
+
+		fn init(_: &mut EngineBuilder) { LOG.lock().unwrap().push("init middle"); }
+
+		fn shutdown(_: &Engine) { LOG.lock().unwrap().push("shutdown middle"); }
+	}
+
+	impl Module for Top {
+		type Dependencies = (Middle, Base);
+
+		fn init(_: &mut EngineBuilder) { LOG.lock().unwrap().push("init top"); }
+
+		fn shutdown(_: &Engine) { LOG.lock().unwrap().push("shutdown top"); }
+	}
+
+	struct Chicken;
+	struct Egg;
+
+	impl Module for Chicken {
+		type Dependencies = (Egg,);
+
+		fn init(_: &mut EngineBuilder) {}
+	}
+
+	impl Module for Egg {
+		type Dependencies = (Chicken,);
+
+		fn init(_: &mut EngineBuilder) {}
+	}
+
+	#[test]
+	fn module_order() {
+		let engine = Engine::builder().module::<Top>().module::<Base>().build_scoped();
+		drop(engine);
+		assert_eq!(
+			*LOG.lock().unwrap(),
+			[
+				"init base",
+				"init middle",
+				"init top",
+				"shutdown top",
+				"shutdown middle",
+				"shutdown base"
+			]
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "module dependency cycle")]
+	fn module_cycle() { Engine::builder().module::<Chicken>(); }
+}
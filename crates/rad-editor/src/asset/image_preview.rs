@@ -25,7 +25,7 @@ impl ImagePreviewer {
 					let rect = ui.available_rect_before_wrap();
 					let mut size = rect.size();
 					ui.allocate_rect(rect, Sense::focusable_noninteractive());
-					let desc = img.image().desc();
+					let desc = img.desc();
 					let aspect = desc.size.width as f32 / desc.size.height as f32;
 					if size.x / aspect < size.y {
 						size.y = size.x / aspect;
@@ -1,27 +1,31 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{io, path::PathBuf, sync::Arc};
 
-use rad_core::{Engine, asset::Asset};
+use rad_asset::{fs::FsAssetSystem, import::GltfImporter};
+use rad_core::{
+	Engine,
+	asset::{Asset, aref::UntypedAssetId},
+};
 use rad_renderer::assets::{image::ImageAsset, material::Material, mesh::Mesh};
 use rad_ui::{
 	egui::{Button, Context, Grid, Key, KeyboardShortcut, Modifiers, RichText, ScrollArea, TopBottomPanel},
 	icons::{self, icon},
 };
 use rad_world::World;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
-	asset::{fs::FsAssetSystem, image_preview::ImagePreviewer, import::GltfImporter},
+	asset::{image_preview::ImagePreviewer, references::ReferenceFinder},
 	world::WorldContext,
 };
 
-pub mod fs;
 mod image_preview;
-mod import;
+mod references;
 
 pub struct AssetTray {
 	open: bool,
 	cursor: PathBuf,
 	image_previewer: ImagePreviewer,
+	references: ReferenceFinder,
 }
 
 impl AssetTray {
@@ -30,11 +34,13 @@ impl AssetTray {
 			open: false,
 			cursor: PathBuf::new(),
 			image_previewer: ImagePreviewer::new(),
+			references: ReferenceFinder::new(),
 		}
 	}
 
 	pub fn render(&mut self, ctx: &Context, world: &mut WorldContext) {
 		self.image_previewer.render(ctx);
+		self.references.render(ctx);
 
 		self.open ^= ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Space)));
 
@@ -59,16 +65,13 @@ impl AssetTray {
 
 					let dropped = ctx.input_mut(|x| std::mem::take(&mut x.raw.dropped_files));
 					for file in dropped {
-						if let Some(x) = GltfImporter::initialize(&file.path.unwrap())
-							&& let Err(e) = x.and_then(|x| {
-								x.import(|x| {
-									info!("import: {:.2}%", x * 100.0);
-								})
-							}) {
-							error!("import error: {:?}", e);
+						if let Some(x) = GltfImporter::initialize(&file.path.unwrap()) {
+							Self::import(x);
 						}
 					}
 
+					// Performed once the directory tree is no longer borrowed.
+					let mut action = None;
 					ui.vertical(|ui| {
 						ui.add_space(5.0);
 						ui.horizontal(|ui| {
@@ -186,8 +189,29 @@ impl AssetTray {
 												} else {
 													icons::FILE
 												};
-												if ui.add(Button::new(icon(i).size(35.0)).frame(false)).double_clicked()
-												{
+												let res = ui.add(Button::new(icon(i).size(35.0)).frame(false));
+												res.context_menu(|ui| {
+													if ui.button("find references").clicked() {
+														self.references.find(n.clone(), header.id);
+														ui.close_menu();
+													}
+													if fs.import_info(header.id).is_some()
+														&& ui.button("reimport").clicked()
+													{
+														action = Some(AssetAction::Reimport(header.id));
+														ui.close_menu();
+													}
+													if ui.button("duplicate").clicked() {
+														let to = self.cursor.join(format!("{n} copy"));
+														action = Some(AssetAction::Duplicate(header.id, to));
+														ui.close_menu();
+													}
+													if ui.button("delete").clicked() {
+														action = Some(AssetAction::Delete(n.clone(), header.id));
+														ui.close_menu();
+													}
+												});
+												if res.double_clicked() {
 													unsafe {
 														if is_world {
 															if let Err(e) = world.open(header.id.typed()) {
@@ -207,7 +231,55 @@ impl AssetTray {
 									});
 							});
 					});
+
+					let res = match action {
+						Some(AssetAction::Reimport(id)) => {
+							Self::import(GltfImporter::reimport(id));
+							Ok(())
+						},
+						Some(AssetAction::Duplicate(id, to)) => fs.duplicate(id, &to).map(drop),
+						// Show what still uses the asset instead of leaving it dangling.
+						Some(AssetAction::Delete(name, id)) if !fs.dependents(id).is_empty() => {
+							warn!("not deleting `{name}`, as other assets still reference it");
+							self.references.find(name, id);
+							Ok(())
+						},
+						Some(AssetAction::Delete(_, id)) => fs.delete(id),
+						None => Ok(()),
+					};
+					if let Err(e) = res {
+						error!("asset operation failed: {:?}", e);
+					}
 				});
 		}
 	}
+
+	fn import(importer: Result<GltfImporter, io::Error>) {
+		let res = importer.and_then(|x| {
+			x.import(|x| {
+				info!("import: {:.2}%", x * 100.0);
+			})
+		});
+		match res {
+			Ok(report) => {
+				info!(
+					"import: {} added, {} updated, {} unchanged, {} removed",
+					report.added.len(),
+					report.updated.len(),
+					report.unchanged.len(),
+					report.removed.len()
+				);
+				for id in report.removed {
+					warn!("asset {id} is no longer in the imported file");
+				}
+			},
+			Err(e) => error!("import error: {:?}", e),
+		}
+	}
+}
+
+enum AssetAction {
+	Reimport(UntypedAssetId),
+	Duplicate(UntypedAssetId, PathBuf),
+	Delete(String, UntypedAssetId),
 }
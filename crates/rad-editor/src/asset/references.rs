@@ -0,0 +1,76 @@
+use std::{
+	path::PathBuf,
+	sync::{Arc, OnceLock},
+};
+
+use rad_asset::fs::FsAssetSystem;
+use rad_core::{asset::aref::UntypedAssetId, Engine};
+use rad_ui::egui::{Context, Id, ScrollArea, Window};
+
+struct Search {
+	name: String,
+	id: UntypedAssetId,
+	found: Arc<OnceLock<Vec<PathBuf>>>,
+}
+
+pub struct ReferenceFinder {
+	searches: Vec<Search>,
+}
+
+impl ReferenceFinder {
+	pub fn new() -> Self { Self { searches: Vec::new() } }
+
+	pub fn render(&mut self, ctx: &Context) {
+		let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+
+		let mut yeet = Vec::new();
+		for (i, search) in self.searches.iter().enumerate() {
+			let mut open = true;
+			Window::new(format!("references to {}", search.name))
+				.id(Id::new("references").with(search.id))
+				.open(&mut open)
+				.show(ctx, |ui| match search.found.get() {
+					None => {
+						ui.spinner();
+					},
+					Some(found) if found.is_empty() => {
+						ui.label("no references");
+					},
+					Some(found) => {
+						let root = fs.root().clone().unwrap_or_default();
+						ScrollArea::vertical().show(ui, |ui| {
+							for path in found {
+								let path = path.strip_prefix(&root).unwrap_or(path).with_extension("");
+								ui.label(path.to_string_lossy());
+							}
+						});
+					},
+				});
+
+			if !open {
+				yeet.push(i);
+			}
+		}
+
+		for i in yeet.into_iter().rev() {
+			self.searches.swap_remove(i);
+		}
+	}
+
+	/// Find the assets that reference `id` in the background.
+	pub fn find(&mut self, name: String, id: UntypedAssetId) {
+		if self.searches.iter().any(|x| x.id == id) {
+			return;
+		}
+
+		let found = Arc::new(OnceLock::new());
+		let f = found.clone();
+		std::thread::spawn(move || {
+			let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+			let mut paths: Vec<_> = fs.dependents(id).into_iter().filter_map(|(x, _)| fs.path(x)).collect();
+			paths.sort_unstable();
+			let _ = f.set(paths);
+		});
+		self.searches.push(Search { name, id, found });
+	}
+}
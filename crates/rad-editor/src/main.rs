@@ -1,18 +1,19 @@
-#![feature(path_add_extension)]
-
-use std::mem::ManuallyDrop;
+use std::{
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
 
+use rad_asset::fs::FsAssetSystem;
 use rad_core::{Engine, EngineBuilder, Module};
-use rad_graph::{graph::Frame, Result};
+use rad_graph::{device::Device, graph::Frame, Result};
 use rad_renderer::RendererModule;
 use rad_rhi::RhiModule;
 use rad_ui::{egui::Context, App, UiApp, UiModule};
 use rad_window::{winit::event::WindowEvent, Window, WindowModule};
-use rad_world::WorldModule;
 use tracing_subscriber::{fmt::format::FmtSpan, layer::SubscriberExt, EnvFilter, Layer, Registry};
 
 use crate::{
-	asset::{fs::FsAssetSystem, AssetTray},
+	asset::AssetTray,
 	menu::Menu,
 	render::Renderer,
 	world::WorldContext,
@@ -35,60 +36,77 @@ fn main() -> Result<()> {
 			.with(tracy::tracing::TracyLayer),
 	);
 
-	Engine::builder()
-		.module::<RhiModule>()
-		.module::<UiModule>()
-		.module::<WindowModule>()
-		.module::<WorldModule>()
-		.module::<RendererModule>()
-		.module::<EditorModule>()
-		.build();
+	Engine::builder().module::<EditorModule>().build();
 
-	rad_window::run(UiApp::new(EditorApp::new())?)
+	let res = rad_window::run(UiApp::new(EditorApp::new())?);
+	// The app has been dropped, so nothing is using the engine anymore.
+	Engine::get().shutdown();
+	res
 }
 
 struct EditorModule;
 
 impl Module for EditorModule {
-	fn init(engine: &mut EngineBuilder) { engine.asset_source(FsAssetSystem::new()); }
+	type Dependencies = (RhiModule, UiModule, WindowModule, RendererModule);
+
+	fn init(engine: &mut EngineBuilder) {
+		engine.asset_source(FsAssetSystem::new(std::env::args().nth(1).map(PathBuf::from)));
+		engine.global(EditorRenderer(Mutex::new(None)));
+	}
+
+	fn shutdown(engine: &Engine) {
+		if let Some(renderer) = engine.global::<EditorRenderer>().0.lock().unwrap().take() {
+			// This runs before the UI and RHI shut down, so frames might still be in flight.
+			unsafe {
+				let _ = engine.global::<Device>().device().device_wait_idle();
+				renderer.destroy();
+			}
+		}
+	}
 }
 
+/// Where the app leaves its renderer when it is dropped, so that the module destroys it when the engine shuts down.
+struct EditorRenderer(Mutex<Option<Renderer>>);
+
 struct EditorApp {
 	menu: Menu,
 	assets: AssetTray,
 	world: WorldContext,
-	renderer: ManuallyDrop<Renderer>,
+	renderer: Option<Renderer>,
 }
 
 impl EditorApp {
 	fn new() -> Self {
+		let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
+		fs.hot_reload();
+
 		Self {
 			menu: Menu::new(),
 			assets: AssetTray::new(),
 			world: WorldContext::new(),
-			renderer: ManuallyDrop::new(Renderer::new().unwrap()),
+			renderer: Some(Renderer::new().unwrap()),
 		}
 	}
 }
 
+impl Drop for EditorApp {
+	fn drop(&mut self) {
+		// Frames might still be in flight, so leave destroying the renderer to the module.
+		*Engine::get().global::<EditorRenderer>().0.lock().unwrap() = self.renderer.take();
+	}
+}
+
 impl App for EditorApp {
 	fn render<'pass>(&'pass mut self, window: &mut Window, frame: &mut Frame<'pass, '_>, ctx: &Context) -> Result<()> {
-		self.menu.render(ctx, &mut self.renderer);
+		let renderer = self.renderer.as_mut().unwrap();
+		self.menu.render(ctx, renderer, &mut self.world);
 		self.assets.render(ctx, &mut self.world);
-		self.renderer.render(window, frame, ctx, &mut self.world);
+		renderer.render(window, frame, ctx, &mut self.world);
 
 		Ok(())
 	}
 
 	fn window_event(&mut self, window: &mut Window, event: &WindowEvent) {
-		self.renderer.on_window_event(window, event);
-	}
-}
-
-impl Drop for EditorApp {
-	fn drop(&mut self) {
-		unsafe {
-			ManuallyDrop::take(&mut self.renderer).destroy();
-		}
+		self.renderer.as_mut().unwrap().on_window_event(window, event);
 	}
 }
@@ -1,35 +1,80 @@
 use std::sync::Arc;
 
+use rad_asset::fs::FsAssetSystem;
 use rad_core::Engine;
-use rad_ui::egui::{menu, Context, Key, KeyboardShortcut, Modifiers, TopBottomPanel};
+use rad_ui::egui::{menu, Button, Context, Key, KeyboardShortcut, Modifiers, TopBottomPanel};
 use rfd::FileDialog;
+use tracing::{error, info};
 
-use crate::{asset::fs::FsAssetSystem, render::Renderer};
+use crate::{render::Renderer, world::WorldContext};
 
 pub struct Menu {}
 
 impl Menu {
 	pub fn new() -> Self { Self {} }
 
-	pub fn render(&mut self, ctx: &Context, renderer: &mut Renderer) {
+	pub fn render(&mut self, ctx: &Context, renderer: &mut Renderer, world: &mut WorldContext) {
 		let fs: &Arc<FsAssetSystem> = Engine::get().asset_source();
 
 		let mut new = ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::N)));
 		let mut open = ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::O)));
+		// Consumed first, as the undo shortcut would also match it.
+		let mut redo = ctx
+			.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z)));
+		let mut undo = ctx.input_mut(|x| x.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::Z)));
+		let mut bake = false;
 
 		TopBottomPanel::top("menu").show(ctx, |ui| {
 			menu::bar(ui, |ui| {
 				ui.menu_button("file", |ui| {
 					new |= ui.button("new").clicked();
 					open |= ui.button("open").clicked();
+					ui.separator();
+					bake |= ui.button("bake pack").clicked();
+					if ui.button("purge cooked assets").clicked()
+						&& let Err(e) = Engine::get().purge_cooked()
+					{
+						error!("failed to purge cooked assets: {:?}", e);
+					}
+				});
+
+				ui.menu_button("edit", |ui| {
+					undo |= ui.add_enabled(world.can_undo(), Button::new("undo")).clicked();
+					redo |= ui.add_enabled(world.can_redo(), Button::new("redo")).clicked();
+					ui.separator();
+					if ui.button("add entity").clicked() {
+						world.spawn();
+					}
 				});
 
 				ui.menu_button("window", |ui| {
 					ui.checkbox(&mut renderer.debug_window.enabled, "debug");
 				});
+
+				let loads = Engine::get().pending_asset_loads();
+				if loads > 0 {
+					ui.separator();
+					ui.spinner();
+					ui.label(format!("loading {loads} assets"));
+				}
 			});
 		});
 
+		if undo {
+			world.undo();
+		}
+		if redo {
+			world.redo();
+		}
+
+		if bake && let Some(path) = FileDialog::new().add_filter("pack", &["radpack"]).save_file() {
+			let assets = fs.assets();
+			std::thread::spawn(move || match Engine::get().bake(&path, assets) {
+				Ok(count) => info!("baked {count} entries into {}", path.display()),
+				Err(e) => error!("failed to bake pack: {:?}", e),
+			});
+		}
+
 		if (new || open)
 			&& let Some(path) = FileDialog::new().pick_folder() {
 				fs.open(path);
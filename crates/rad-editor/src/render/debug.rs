@@ -1,11 +1,14 @@
+use std::{cmp::Reverse, time::Duration};
+
 use egui_plot::{Bar, BarChart, HPlacement, Plot, VLine, VPlacement};
+use rad_core::Engine;
 use rad_graph::device::{Device, HotreloadStatus};
 use rad_renderer::{
 	debug::mesh::DebugVis,
 	mesh::{CullStats, PassStats},
 	tonemap::exposure::{ExposureCalc, ExposureStats},
 };
-use rad_ui::egui::{Checkbox, ComboBox, Context, DragValue, Ui, Window};
+use rad_ui::egui::{Checkbox, ComboBox, Context, DragValue, Grid, Ui, Window};
 
 #[derive(Copy, Clone)]
 pub enum RenderMode {
@@ -183,6 +186,8 @@ impl DebugWindow {
 				}
 			});
 
+			ui.collapsing("assets", Self::asset_stats);
+
 			if let Some(stats) = stats {
 				ui.label("early");
 				Self::pass_stats(ui, stats.early);
@@ -253,6 +258,49 @@ impl DebugWindow {
 		ui.label(format!("sw meshlets: {}", pass.sw_meshlets));
 	}
 
+	fn asset_stats(ui: &mut Ui) {
+		let short = |name: &'static str| name.rsplit("::").next().unwrap_or(name);
+		let ms = |time: Duration| format!("{:.2} ms", time.as_secs_f64() * 1000.0);
+		let mib = |bytes: u64| format!("{:.2} MiB", bytes as f64 / (1024.0 * 1024.0));
+
+		let mut assets = Engine::get().asset_stats();
+		assets.sort_unstable_by_key(|(_, x)| Reverse(x.load_time + x.cook_time));
+		Grid::new("asset stats").striped(true).show(ui, |ui| {
+			for header in ["asset", "loads", "load time", "decoded", "cooks", "cook time"] {
+				ui.label(header);
+			}
+			ui.end_row();
+			for (name, x) in assets {
+				ui.label(short(name));
+				ui.label(x.loads.to_string());
+				ui.label(ms(x.load_time));
+				ui.label(mib(x.load_bytes));
+				ui.label(x.cooks.to_string());
+				ui.label(ms(x.cook_time));
+				ui.end_row();
+			}
+		});
+
+		ui.separator();
+
+		let mut views = Engine::get().view_stats();
+		views.sort_unstable_by_key(|(_, x)| Reverse(x.load_time));
+		Grid::new("view stats").striped(true).show(ui, |ui| {
+			for header in ["view", "loads", "load time", "live", "size"] {
+				ui.label(header);
+			}
+			ui.end_row();
+			for (name, x) in views {
+				ui.label(short(name));
+				ui.label(x.loads.to_string());
+				ui.label(ms(x.load_time));
+				ui.label(x.live.to_string());
+				ui.label(mib(x.live_size as _));
+				ui.end_row();
+			}
+		});
+	}
+
 	pub fn render_mode(&self) -> RenderMode { self.render_mode }
 
 	pub fn tonemap(&self) -> Tonemap { self.tonemap }
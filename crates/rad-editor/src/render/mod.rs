@@ -1,6 +1,7 @@
 use rad_core::Engine;
 use rad_graph::{Result, graph::Frame};
 use rad_renderer::{
+	assets::{image::ImageAssetView, material::MaterialView},
 	debug::mesh::DebugMesh,
 	mesh::{self, VisBuffer},
 	pt::{self, PathTracer},
@@ -76,6 +77,9 @@ impl Renderer {
 		&'pass mut self, window: &mut Window, frame: &mut Frame<'pass, '_>, ctx: &Context,
 		world: &'pass mut WorldContext,
 	) {
+		Engine::get().view_context::<ImageAssetView>().delete(frame);
+		Engine::get().view_context::<MaterialView>().write_reloaded();
+
 		let (stats, pt) = CentralPanel::default()
 			.show(ctx, |ui| {
 				let rect = ui.available_rect_before_wrap();
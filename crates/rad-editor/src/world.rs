@@ -8,14 +8,17 @@ use rad_renderer::{
 use rad_world::{
 	World,
 	bevy_ecs::{entity::Entity, world::EntityMut},
+	history::History,
 	serde::DoNotSerialize,
 	tick::Tick,
 };
+use tracing::error;
 
 pub struct WorldContext {
 	edit: World,
 	edit_tick: Tick,
 	editor: Entity,
+	history: History,
 }
 
 impl WorldContext {
@@ -24,6 +27,7 @@ impl WorldContext {
 			edit: World::new(),
 			edit_tick: Tick::new(),
 			editor: Entity::from_raw(0),
+			history: History::default(),
 		};
 		this.setup_world();
 		this
@@ -40,9 +44,31 @@ impl WorldContext {
 
 	pub fn edit_tick(&mut self) { self.edit_tick.tick(&mut self.edit); }
 
+	/// The world being edited, for things that don't change what is saved, such as rendering. Edits to the scene go
+	/// through `history`, so that they can be undone.
 	pub fn world_mut(&mut self) -> &mut World { &mut self.edit }
 
+	/// Add an empty entity to the scene.
+	pub fn spawn(&mut self) -> Entity { self.history.spawn(&mut self.edit, ()) }
+
+	pub fn can_undo(&self) -> bool { self.history.can_undo() }
+
+	pub fn can_redo(&self) -> bool { self.history.can_redo() }
+
+	pub fn undo(&mut self) {
+		if let Err(e) = self.history.undo(&mut self.edit) {
+			error!("failed to undo: {:?}", e);
+		}
+	}
+
+	pub fn redo(&mut self) {
+		if let Err(e) = self.history.redo(&mut self.edit) {
+			error!("failed to redo: {:?}", e);
+		}
+	}
+
 	fn setup_world(&mut self) {
+		self.history.clear();
 		self.edit_tick = Tick::new();
 		self.editor = self
 			.edit
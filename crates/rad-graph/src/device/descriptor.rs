@@ -71,10 +71,26 @@ impl Descriptors {
 		let mut inner = self.inner.lock().unwrap();
 
 		let index = inner.sampled_images.get_index();
+		unsafe {
+			Self::write_image(self.set, device, index, image);
+		}
+
+		ImageId(index)
+	}
+
+	/// Point an existing image ID at a different image view.
+	pub fn update_image(&self, device: &ash::Device, id: ImageId, image: vk::ImageView) {
+		let _inner = self.inner.lock().unwrap();
+		unsafe {
+			Self::write_image(self.set, device, id.0, image);
+		}
+	}
+
+	unsafe fn write_image(set: vk::DescriptorSet, device: &ash::Device, index: NonZeroU32, image: vk::ImageView) {
 		unsafe {
 			device.update_descriptor_sets(
 				&[vk::WriteDescriptorSet::default()
-					.dst_set(self.set)
+					.dst_set(set)
 					.dst_binding(0)
 					.dst_array_element(index.get())
 					.descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
@@ -84,8 +100,6 @@ impl Descriptors {
 				&[],
 			);
 		}
-
-		ImageId(index)
 	}
 
 	pub fn get_storage_image(&self, device: &ash::Device, image: vk::ImageView) -> StorageImageId {
@@ -139,6 +139,11 @@ impl Device {
 
 	pub fn return_image_id(&self, id: descriptor::ImageId) { self.inner.descriptors.return_image(id) }
 
+	/// Point `id` at `image` instead of the view it was created for.
+	pub fn update_image_id(&self, id: descriptor::ImageId, image: vk::ImageView) {
+		self.inner.descriptors.update_image(&self.inner.device, id, image)
+	}
+
 	pub fn storage_image_id(&self, image: vk::ImageView) -> descriptor::StorageImageId {
 		self.inner.descriptors.get_storage_image(&self.inner.device, image)
 	}
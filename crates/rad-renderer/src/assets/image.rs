@@ -1,4 +1,7 @@
-use std::io::{self, Write};
+use std::{
+	io::{self, Write},
+	sync::{Mutex, RwLock},
+};
 
 use ash::vk;
 use bincode::{Decode, Encode};
@@ -12,6 +15,7 @@ use rad_core::{
 use rad_graph::{
 	cmd::CommandPool,
 	device::{descriptor::ImageId, Device, QueueWait, Transfer},
+	graph::{self, Frame},
 	resource::{
 		Buffer,
 		BufferDesc,
@@ -26,9 +30,11 @@ use rad_graph::{
 	},
 	sync::{get_image_barrier, ImageBarrier, UsageType},
 };
-use tracing::trace_span;
+use tracing::{error, trace_span};
 use vek::Vec3;
 
+use crate::util::wait_for_frames;
+
 #[derive(Encode, Decode)]
 pub struct ImageAsset {
 	#[bincode(with_serde)]
@@ -119,18 +125,27 @@ impl CookedAsset for ImageAsset {
 }
 
 pub struct ImageAssetView {
-	image: Image,
-	view: ImageView,
+	id: ImageId,
+	inner: RwLock<(Image, ImageView)>,
 }
 
 impl ImageAssetView {
-	pub fn image(&self) -> &Image { &self.image }
+	pub fn desc(&self) -> graph::ImageDesc { self.inner.read().unwrap().0.desc() }
 
-	pub fn view(&self) -> &ImageView { &self.view }
+	/// The current view of the image. This changes if the image is reloaded, but `image_id` does not.
+	pub fn view(&self) -> ImageView { self.inner.read().unwrap().1 }
 
-	pub fn image_id(&self) -> ImageId { self.view.id.unwrap() }
+	pub fn image_id(&self) -> ImageId { self.id }
 
 	pub fn new(name: &str, data: ImageAsset) -> Result<Self, std::io::Error> {
+		let (image, view) = Self::create(name, data)?;
+		Ok(Self {
+			id: view.id.unwrap(),
+			inner: RwLock::new((image, view)),
+		})
+	}
+
+	fn create(name: &str, data: ImageAsset) -> Result<(Image, ImageView), std::io::Error> {
 		let s = trace_span!("load image", name = name);
 		let _e = s.enter();
 
@@ -247,16 +262,83 @@ impl ImageAssetView {
 			},
 		)?;
 
-		Ok(Self { image, view })
+		Ok((image, view))
 	}
 }
 
 impl AssetView for ImageAssetView {
 	type Base = ImageAsset;
-	type Ctx = ();
+	type Ctx = RetiredImages;
 
 	fn load(_: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error> {
 		// TODO: fix
 		Self::new("image asset", base)
 	}
+
+	fn size(&self) -> usize { self.inner.read().unwrap().0.size() as _ }
+
+	fn reload(&self, ctx: &'static Self::Ctx, base: Self::Base) -> Result<(), io::Error> {
+		let (image, view) = Self::create("image asset", base)?;
+
+		// Swap the IDs so the new view takes over the old one, and the new ID is returned when the old view is
+		// destroyed. Frames in flight might still be sampling the old view through the ID, so it is only pointed at
+		// the new view once they are done.
+		let mut inner = self.inner.write().unwrap();
+		let (old_image, mut old_view) = std::mem::replace(&mut *inner, (image, view));
+		old_view.id = std::mem::replace(&mut inner.1.id, Some(self.id));
+		ctx.0.lock().unwrap().push(Retired {
+			id: self.id,
+			view: inner.1.view,
+			old: (old_image, old_view),
+		});
+
+		Ok(())
+	}
+}
+
+/// Images replaced by reloads, which have to be kept alive until the GPU is done with them.
+#[derive(Default)]
+pub struct RetiredImages(Mutex<Vec<Retired>>);
+
+struct Retired {
+	/// The ID of the reloaded image, and the view it should point at.
+	id: ImageId,
+	view: vk::ImageView,
+	old: (Image, ImageView),
+}
+
+impl RetiredImages {
+	/// Point the IDs of reloaded images at their new views, and queue the old images for deletion once `frame` is no
+	/// longer in flight. This must be called at the start of a frame, before any passes that sample images are
+	/// recorded.
+	pub fn delete(&self, frame: &mut Frame) {
+		let mut retired = self.0.lock().unwrap();
+		if retired.is_empty() {
+			return;
+		}
+
+		// A descriptor can't be written while a submitted frame might read it, and every frame might read these, so
+		// wait for all of them. This only happens after a reload.
+		let device: &Device = Engine::get().global();
+		if let Err(e) = wait_for_frames(device) {
+			error!("failed to wait for frames in flight: {:?}", e);
+			return;
+		}
+
+		for Retired { id, view, old } in retired.drain(..) {
+			device.update_image_id(id, view);
+			frame.delete(old.0);
+			frame.delete(old.1);
+		}
+	}
+
+	/// Destroy the retired images right away. The GPU must be idle.
+	pub unsafe fn destroy(&self, device: &Device) {
+		for Retired { old, .. } in self.0.lock().unwrap().drain(..) {
+			unsafe {
+				old.0.destroy(device);
+				old.1.destroy(device);
+			}
+		}
+	}
 }
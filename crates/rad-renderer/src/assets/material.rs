@@ -1,10 +1,13 @@
-use std::sync::RwLock;
+use std::{
+	io,
+	sync::{Mutex, RwLock},
+};
 
 use bincode::{Decode, Encode};
 use bytemuck::{Pod, Zeroable};
 use rad_core::{
 	asset::{
-		aref::{ARef, AssetId, LARef},
+		aref::{ARef, AssetId, AssetRef, LARef, UntypedAssetId},
 		AssetView,
 		BincodeAsset,
 	},
@@ -12,14 +15,17 @@ use rad_core::{
 	Engine,
 };
 use rad_graph::{
-	device::descriptor::ImageId,
+	device::{descriptor::ImageId, Device},
 	resource::{Buffer, BufferDesc, BufferType, GpuPtr, Resource},
 };
 use rad_world::Uuid;
-use tracing::trace_span;
+use tracing::{error, trace_span};
 use vek::{Vec3, Vec4};
 
-use crate::assets::image::{ImageAsset, ImageAssetView};
+use crate::{
+	assets::image::{ImageAsset, ImageAssetView},
+	util::wait_for_frames,
+};
 
 #[derive(Encode, Decode)]
 pub struct Material {
@@ -40,7 +46,16 @@ pub struct Material {
 }
 
 impl BincodeAsset for Material {
+	const REFERENCES_ASSETS: bool = true;
 	const UUID: Uuid = uuid!("15695530-bc12-4745-9410-21d24480e8f1");
+
+	fn dependencies(&self) -> Vec<(UntypedAssetId, Uuid)> {
+		[self.base_color, self.metallic_roughness, self.normal, self.emissive]
+			.into_iter()
+			.flatten()
+			.map(|x| x.asset())
+			.collect()
+	}
 }
 
 #[derive(Copy, Clone, Default, Pod, Zeroable)]
@@ -60,29 +75,43 @@ pub struct MaterialView {
 	ptr: GpuPtr<GpuMaterial>,
 	buf: BufRef,
 	ctx: &'static MaterialBuffers,
-	pub base_color: Option<LARef<ImageAssetView>>,
-	pub metallic_roughness: Option<LARef<ImageAssetView>>,
-	pub normal: Option<LARef<ImageAssetView>>,
-	pub emissive: Option<LARef<ImageAssetView>>,
-	pub emissive_factor: Vec3<f32>,
+	data: RwLock<MaterialData>,
+}
+
+/// The parts of a material view that are replaced when it is reloaded.
+struct MaterialData {
+	/// Keeps the images referenced by the GPU material alive.
+	_images: [Option<LARef<ImageAssetView>>; 4],
+	emissive_factor: Vec3<f32>,
 }
 
 impl MaterialView {
 	pub fn gpu_ptr(&self) -> GpuPtr<GpuMaterial> { self.ptr }
+
+	pub fn emissive_factor(&self) -> Vec3<f32> { self.data.read().unwrap().emissive_factor }
 }
 
 impl AssetView for MaterialView {
 	type Base = Material;
 	type Ctx = MaterialBuffers;
 
-	fn load(ctx: &'static Self::Ctx, base: Self::Base) -> Result<Self, std::io::Error> { Ok(ctx.load(base)) }
+	fn load(ctx: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error> { ctx.load(base) }
+
+	fn size(&self) -> usize { std::mem::size_of::<GpuMaterial>() }
+
+	fn reload(&self, ctx: &'static Self::Ctx, base: Self::Base) -> Result<(), io::Error> {
+		let (gpu, data) = ctx.resolve(base)?;
+		let old = std::mem::replace(&mut *self.data.write().unwrap(), data);
+		ctx.reloaded.lock().unwrap().push((Some(self.buf), gpu, old));
+		Ok(())
+	}
 }
 
 impl Drop for MaterialView {
 	fn drop(&mut self) { self.ctx.unload(self); }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 struct BufRef {
 	buf: u32,
 	id: u32,
@@ -90,6 +119,10 @@ struct BufRef {
 
 pub struct MaterialBuffers {
 	inner: RwLock<MaterialBuffersInner>,
+	/// Slots of reloaded materials, which are rewritten at the start of the next frame once the GPU is done reading
+	/// them. The old data keeps the images the slot still points to alive until then. The slot is `None` if the
+	/// material was unloaded in the meantime.
+	reloaded: Mutex<Vec<(Option<BufRef>, GpuMaterial, MaterialData)>>,
 }
 
 struct MaterialBuffersInner {
@@ -106,6 +139,7 @@ impl Default for MaterialBuffers {
 				free: Vec::new(),
 				bump: 0,
 			}),
+			reloaded: Mutex::new(Vec::new()),
 		}
 	}
 }
@@ -116,10 +150,45 @@ impl MaterialBuffers {
 
 	fn id(i: &Option<LARef<ImageAssetView>>) -> Option<ImageId> { i.as_ref().map(|i| i.image_id()) }
 
-	fn load(&'static self, mat: Material) -> MaterialView {
+	/// Write the materials changed by reloads into their slots. This must be called at the start of a frame, before
+	/// any passes that read materials are recorded.
+	pub fn write_reloaded(&self) {
+		let mut reloaded = self.reloaded.lock().unwrap();
+		if reloaded.is_empty() {
+			return;
+		}
+
+		// Earlier frames might still be reading the slots, and the images the old data keeps alive.
+		if let Err(e) = wait_for_frames(Engine::get().global()) {
+			error!("failed to wait for frames in flight: {:?}", e);
+			return;
+		}
+		for (buf, gpu, _old) in reloaded.drain(..) {
+			if let Some(buf) = buf {
+				self.write(buf, gpu);
+			}
+		}
+	}
+
+	/// Destroy the material buffers. The GPU must be idle, and materials that are still loaded must not be used
+	/// afterwards.
+	pub unsafe fn destroy(&self, device: &Device) {
+		self.reloaded.lock().unwrap().clear();
+		let mut inner = self.inner.write().unwrap();
+		inner.free.clear();
+		inner.bump = 0;
+		for buf in inner.buffers.drain(..) {
+			unsafe {
+				buf.destroy(device);
+			}
+		}
+	}
+
+	fn load(&'static self, mat: Material) -> Result<MaterialView, io::Error> {
 		let s = trace_span!("load material");
 		let _e = s.enter();
 
+		let (gpu, data) = self.resolve(mat)?;
 		let mut inner = self.inner.write().unwrap();
 		let buf = if let Some(free) = inner.free.pop() {
 			free
@@ -147,59 +216,66 @@ impl MaterialBuffers {
 			BufRef { buf, id: 0 }
 		};
 
-		let id = buf.id;
-		let b = &inner.buffers[buf.buf as usize];
-		let ptr = b.ptr::<GpuMaterial>().offset(id as _);
+		let ptr = inner.buffers[buf.buf as usize].ptr::<GpuMaterial>().offset(buf.id as _);
+		drop(inner);
 
-		// TODO: should we multithread these?
-		// TODO: unwrap bad
-		let base_color = mat.base_color.map(|id| ARef::loaded(id)).transpose().unwrap();
-		let metallic_roughness = mat.metallic_roughness.map(|id| ARef::loaded(id)).transpose().unwrap();
-		let normal = mat.normal.map(|id| ARef::loaded(id)).transpose().unwrap();
-		let emissive = mat.emissive.map(|id| ARef::loaded(id)).transpose().unwrap();
-
-		unsafe {
-			b.data()
-				.cast::<GpuMaterial>()
-				.offset(id as _)
-				.as_ptr()
-				.write(GpuMaterial {
-					base_color: Self::id(&base_color),
-					base_color_factor: mat.base_color_factor,
-					metallic_roughness: Self::id(&metallic_roughness),
-					metallic_factor: mat.metallic_factor,
-					roughness_factor: mat.roughness_factor,
-					normal: Self::id(&normal),
-					emissive: Self::id(&emissive),
-					emissive_factor: mat.emissive_factor,
-				});
-		}
-
-		MaterialView {
+		self.write(buf, gpu);
+		Ok(MaterialView {
 			ptr,
 			buf,
 			ctx: self,
-			base_color,
-			metallic_roughness,
-			normal,
-			emissive,
+			data: RwLock::new(data),
+		})
+	}
+
+	/// Load the images `mat` references, and build the GPU material that refers to them.
+	fn resolve(&self, mat: Material) -> Result<(GpuMaterial, MaterialData), io::Error> {
+		let ids = [mat.base_color, mat.metallic_roughness, mat.normal, mat.emissive];
+		let present: Vec<_> = ids.iter().flatten().copied().collect();
+		let mut images = ARef::loaded_many(&present, |_, _| {}).into_iter();
+		let [base_color, metallic_roughness, normal, emissive] = ids.map(|id| id.map(|_| images.next().unwrap()));
+		let base_color = base_color.transpose()?;
+		let metallic_roughness = metallic_roughness.transpose()?;
+		let normal = normal.transpose()?;
+		let emissive = emissive.transpose()?;
+
+		let gpu = GpuMaterial {
+			base_color: Self::id(&base_color),
+			base_color_factor: mat.base_color_factor,
+			metallic_roughness: Self::id(&metallic_roughness),
+			metallic_factor: mat.metallic_factor,
+			roughness_factor: mat.roughness_factor,
+			normal: Self::id(&normal),
+			emissive: Self::id(&emissive),
 			emissive_factor: mat.emissive_factor,
-		}
+		};
+		let data = MaterialData {
+			_images: [base_color, metallic_roughness, normal, emissive],
+			emissive_factor: mat.emissive_factor,
+		};
+		Ok((gpu, data))
 	}
 
-	fn unload(&self, view: &MaterialView) {
-		let mut inner = self.inner.write().unwrap();
-		inner.free.push(view.buf);
+	fn write(&self, buf: BufRef, gpu: GpuMaterial) {
+		let inner = self.inner.read().unwrap();
+		unsafe {
+			inner.buffers[buf.buf as usize]
+				.data()
+				.cast::<GpuMaterial>()
+				.offset(buf.id as _)
+				.as_ptr()
+				.write(gpu);
+		}
 	}
-}
 
-impl Drop for MaterialBuffersInner {
-	fn drop(&mut self) {
-		let dev = Engine::get().global();
-		for buf in self.buffers.drain(..) {
-			unsafe {
-				buf.destroy(dev);
+	fn unload(&self, view: &MaterialView) {
+		// The slot might be reused before the next frame starts, but the old data has to live until then.
+		for (buf, ..) in self.reloaded.lock().unwrap().iter_mut() {
+			if *buf == Some(view.buf) {
+				*buf = None;
 			}
 		}
+		let mut inner = self.inner.write().unwrap();
+		inner.free.push(view.buf);
 	}
 }
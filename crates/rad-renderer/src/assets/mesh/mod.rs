@@ -256,4 +256,6 @@ impl AssetView for RaytracingMeshView {
 			})
 		}
 	}
+
+	fn size(&self) -> usize { (self.buffer.size() + self.as_.size()) as _ }
 }
@@ -970,4 +970,6 @@ impl AssetView for VirtualMeshView {
 			aabb: m.aabb,
 		})
 	}
+
+	fn size(&self) -> usize { self.buffer.size() as _ }
 }
@@ -1,4 +1,7 @@
-use rad_world::{bevy_reflect::Reflect, RadComponent};
+use rad_world::{
+	bevy_reflect::{self, Reflect},
+	RadComponent,
+};
 use vek::Vec3;
 
 #[derive(Copy, Clone, Reflect)]
@@ -9,7 +12,17 @@ pub enum LightType {
 
 #[derive(RadComponent)]
 #[uuid("69a570e9-032e-4ca0-aa96-92e9cc4a950c")]
+#[reflect(Default)]
 pub struct LightComponent {
 	pub ty: LightType,
 	pub radiance: Vec3<f32>,
 }
+
+impl Default for LightComponent {
+	fn default() -> Self {
+		Self {
+			ty: LightType::Point,
+			radiance: Vec3::one(),
+		}
+	}
+}
@@ -1,7 +1,8 @@
 #![feature(allocator_api)]
 
-use rad_core::{EngineBuilder, Module, asset::aref::AssetId};
-use rad_world::WorldBuilderExt;
+use rad_core::{Engine, EngineBuilder, Module, asset::aref::AssetId};
+use rad_graph::device::Device;
+use rad_world::{WorldBuilderExt, WorldModule};
 pub use vek;
 
 pub mod assets;
@@ -15,20 +16,19 @@ pub mod sort;
 pub mod tonemap;
 mod util;
 
-pub struct RendererModule;
+/// The renderer's assets and components, without anything that needs a GPU. This is enough to import, cook, and
+/// inspect assets headlessly.
+pub struct RendererAssetsModule;
+
+impl Module for RendererAssetsModule {
+	type Dependencies = (WorldModule,);
 
-impl Module for RendererModule {
 	fn init(engine: &mut EngineBuilder) {
 		engine.asset::<assets::mesh::Mesh>();
 		engine.asset::<assets::material::Material>();
 		engine.cooked_asset::<assets::mesh::virtual_mesh::VirtualMesh>();
 		engine.cooked_asset::<assets::image::ImageAsset>();
 
-		engine.asset_view::<assets::mesh::RaytracingMeshView>();
-		engine.asset_view::<assets::mesh::virtual_mesh::VirtualMeshView>();
-		engine.asset_view::<assets::image::ImageAssetView>();
-		engine.asset_view::<assets::material::MaterialView>();
-
 		engine.component::<components::mesh::MeshComponent>();
 		engine.component_dep_type::<(AssetId<assets::mesh::Mesh>, AssetId<assets::material::Material>)>();
 		engine.component_dep_type::<Vec<(AssetId<assets::mesh::Mesh>, AssetId<assets::material::Material>)>>();
@@ -37,3 +37,29 @@ impl Module for RendererModule {
 		engine.component::<components::camera::PrimaryViewComponent>();
 	}
 }
+
+pub struct RendererModule;
+
+impl Module for RendererModule {
+	type Dependencies = (RendererAssetsModule,);
+
+	fn init(engine: &mut EngineBuilder) {
+		engine.asset_view::<assets::mesh::RaytracingMeshView>();
+		engine.asset_view::<assets::mesh::virtual_mesh::VirtualMeshView>();
+		engine.asset_view::<assets::image::ImageAssetView>();
+		engine.asset_view::<assets::material::MaterialView>();
+	}
+
+	fn shutdown(engine: &Engine) {
+		// Without a device, nothing was ever put on the GPU.
+		let Some(device) = engine.try_global::<Device>() else {
+			return;
+		};
+		// Modules using the renderer have shut down, but the frames they submitted might still be in flight.
+		unsafe {
+			let _ = device.device().device_wait_idle();
+			engine.view_context::<assets::image::ImageAssetView>().destroy(device);
+			engine.view_context::<assets::material::MaterialView>().destroy(device);
+		}
+	}
+}
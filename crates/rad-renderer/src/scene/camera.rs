@@ -10,7 +10,7 @@ use rad_world::{
 		system::{Query, ResMut, Resource},
 	},
 	tick::Tick,
-	transform::Transform,
+	transform::{GlobalTransform, Transform},
 	TickStage,
 	World,
 };
@@ -93,13 +93,13 @@ pub struct CameraSceneData {
 impl Resource for CameraSceneData {}
 
 fn find_primary_view(
-	mut r: ResMut<CameraSceneData>, q: Query<(&Transform, &CameraComponent), With<PrimaryViewComponent>>,
+	mut r: ResMut<CameraSceneData>, q: Query<(&GlobalTransform, &CameraComponent), With<PrimaryViewComponent>>,
 ) {
 	let mut iter = q.iter();
 	if let Some((t, c)) = iter.next() {
 		r.prev = r.curr;
 		r.curr = Camera {
-			transform: *t,
+			transform: t.0,
 			camera: *c,
 		};
 	} else {
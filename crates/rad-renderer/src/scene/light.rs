@@ -18,7 +18,7 @@ use rad_world::{
 		system::{Commands, Query, ResMut, Resource},
 	},
 	tick::Tick,
-	transform::Transform,
+	transform::GlobalTransform,
 };
 use rustc_hash::FxHashMap;
 use vek::{Aabb, Vec3};
@@ -393,7 +393,7 @@ impl Component for KnownLight {
 	const STORAGE_TYPE: StorageType = StorageType::Table;
 }
 
-fn transform_aabb(a: Aabb<f32>, transform: &Transform) -> Aabb<f32> {
+fn transform_aabb(a: Aabb<f32>, transform: &GlobalTransform) -> Aabb<f32> {
 	let mat = transform.into_matrix();
 	let corners = [
 		Vec3::new(a.min.x, a.min.y, a.min.z),
@@ -418,12 +418,12 @@ fn transform_aabb(a: Aabb<f32>, transform: &Transform) -> Aabb<f32> {
 // TODO: figure out how deal with component or entity removal.
 fn sync_lights(
 	mut r: ResMut<LightSceneData>, mut cmd: Commands,
-	unknown_punctual: Query<(Entity, &Transform, &LightComponent), Without<KnownLight>>,
-	unknown_emissive: Query<(Entity, &Transform, &KnownRtInstances), Without<KnownLight>>,
+	unknown_punctual: Query<(Entity, &GlobalTransform, &LightComponent), Without<KnownLight>>,
+	unknown_emissive: Query<(Entity, &GlobalTransform, &KnownRtInstances), Without<KnownLight>>,
 ) {
 	for (e, t, m) in unknown_emissive.iter() {
 		for (_, mesh, material) in m.0.iter() {
-			if material.emissive_factor == Vec3::zero() {
+			if material.emissive_factor() == Vec3::zero() {
 				continue;
 			}
 
@@ -5,22 +5,33 @@ use std::{
 
 use bytemuck::NoUninit;
 use hashbrown::hash_map::Entry;
+use rad_core::asset::{
+	aref::{ARef, LARef, LoadState},
+	Asset,
+	AssetView,
+};
 use rad_graph::{
 	arena::Arena,
 	graph::{ArenaMap, ArenaSet, Frame},
 };
 use rad_world::{
 	bevy_ecs::{
-		component::ComponentId,
+		component::{Component, ComponentId, StorageType},
 		system::{Res, Resource},
 		world::unsafe_world_cell::UnsafeWorldCell,
 	},
 	tick::Tick,
-	transform::Transform,
+	transform::GlobalTransform,
 	World,
 };
+use tracing::warn;
 use vek::{Quaternion, Vec3};
 
+use crate::{
+	assets::{material::MaterialView, mesh::Mesh},
+	components::mesh::MeshComponent,
+};
+
 pub mod camera;
 pub mod light;
 pub mod rt_scene;
@@ -43,8 +54,8 @@ pub struct GpuTransform {
 	pub scale: Vec3<f32>,
 }
 
-impl From<Transform> for GpuTransform {
-	fn from(t: Transform) -> Self {
+impl From<GlobalTransform> for GpuTransform {
+	fn from(GlobalTransform(t): GlobalTransform) -> Self {
 		Self {
 			position: t.position,
 			rotation: t.rotation,
@@ -65,6 +76,61 @@ fn should_scene_sync<T: GpuScene>(cond: Option<Res<SceneRunCondition<T>>>) -> bo
 	cond.map(|c| c.run).unwrap_or(false)
 }
 
+/// Instances of a mesh component whose asset views are still loading in the background, with `M` being the view of
+/// the mesh that the scene uses.
+pub struct PendingInstances<M: AssetView>(pub Vec<(ARef<M>, ARef<MaterialView>)>);
+impl<M: AssetView> Component for PendingInstances<M> {
+	const STORAGE_TYPE: StorageType = StorageType::Table;
+}
+
+impl<M: AssetView> PendingInstances<M>
+where
+	M::Base: Asset<Root = Mesh>,
+{
+	/// Start loading the views of every instance of `mesh`.
+	fn request(mesh: &MeshComponent) -> Self {
+		Self(
+			mesh.inner
+				.iter()
+				.map(|&(m, mat)| {
+					let m = ARef::unloaded(m);
+					let mat = ARef::unloaded(mat);
+					m.request();
+					mat.request();
+					(m, mat)
+				})
+				.collect(),
+		)
+	}
+}
+
+impl<M: AssetView> PendingInstances<M> {
+	/// Returns the loaded views once every view has either loaded or failed to load.
+	fn resolve(&self) -> Option<Vec<(LARef<M>, LARef<MaterialView>)>> {
+		let loading = |x: LoadState| matches!(x, LoadState::Unloaded | LoadState::Loading);
+		if self.0.iter().any(|(m, mat)| loading(m.state()) || loading(mat.state())) {
+			return None;
+		}
+
+		Some(
+			self.0
+				.iter()
+				.filter_map(|(m, mat)| {
+					let m = m
+						.try_loaded()
+						.ok_or_else(|| warn!("failed to load mesh {:?}: {:?}", m.id(), m.error()))
+						.ok()?;
+					let mat = mat
+						.try_loaded()
+						.ok_or_else(|| warn!("failed to load material {:?}: {:?}", mat.id(), mat.error()))
+						.ok()?;
+					Some((m, mat))
+				})
+				.collect(),
+		)
+	}
+}
+
 // TODO: i think run conditions have one frame of latency.
 pub struct WorldRenderer<'pass, 'graph> {
 	world: UnsafeWorldCell<'pass>,
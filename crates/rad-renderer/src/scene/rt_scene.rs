@@ -1,11 +1,6 @@
-use std::sync::Mutex;
-
 use ash::vk;
 use bytemuck::NoUninit;
-use rad_core::{
-	Engine,
-	asset::aref::{ARef, LARef},
-};
+use rad_core::{Engine, asset::aref::LARef};
 use rad_graph::{
 	device::ShaderInfo,
 	graph::{BufferDesc, BufferUsage, BufferUsageType, ExternalBuffer, Frame, Res},
@@ -17,17 +12,15 @@ use rad_world::{
 	TickStage,
 	World,
 	bevy_ecs::{
-		batching::BatchingStrategy,
 		component::{Component, StorageType},
 		entity::Entity,
-		query::{Changed, Or, Without},
+		query::{Changed, Or, With, Without},
 		schedule::IntoSystemConfigs,
 		system::{Commands, Query, ResMut, Resource},
 	},
 	tick::Tick,
-	transform::Transform,
+	transform::GlobalTransform,
 };
-use tracing::warn;
 
 use crate::{
 	assets::{
@@ -35,7 +28,7 @@ use crate::{
 		mesh::{GpuVertex, RaytracingMeshView},
 	},
 	components::mesh::MeshComponent,
-	scene::{GpuScene, GpuTransform, should_scene_sync},
+	scene::{GpuScene, GpuTransform, PendingInstances, should_scene_sync},
 	util::ResizableBuffer,
 };
 
@@ -291,7 +284,7 @@ impl RtSceneData {
 	}
 }
 
-fn map_instance(t: &Transform, m: &LARef<RaytracingMeshView>, mat: &LARef<MaterialView>) -> (GpuRtInstance, u64) {
+fn map_instance(t: &GlobalTransform, m: &LARef<RaytracingMeshView>, mat: &LARef<MaterialView>) -> (GpuRtInstance, u64) {
 	(
 		GpuRtInstance {
 			transform: (*t).into(),
@@ -309,34 +302,28 @@ impl Component for KnownRtInstances {
 	const STORAGE_TYPE: StorageType = StorageType::Table;
 }
 
+/// Instances whose asset views are still loading in the background.
+pub type PendingRtInstances = PendingInstances<RaytracingMeshView>;
+
 // TODO: edits and deletion.
 fn sync_rt_scene(
 	mut r: ResMut<RtSceneData>, mut cmd: Commands,
-	unknown: Query<(Entity, &Transform, &MeshComponent), Without<KnownRtInstances>>,
-	_: Query<(&Transform, &MeshComponent, &KnownRtInstances), Or<(Changed<Transform>, Changed<MeshComponent>)>>,
+	unknown: Query<(Entity, &MeshComponent), (Without<KnownRtInstances>, Without<PendingRtInstances>)>,
+	pending: Query<(Entity, &GlobalTransform, &PendingRtInstances), With<MeshComponent>>,
+	_: Query<
+		(&GlobalTransform, &MeshComponent, &KnownRtInstances),
+		Or<(Changed<GlobalTransform>, Changed<MeshComponent>)>,
+	>,
 ) {
-	let cache = Mutex::new(Vec::new());
-	unknown
-		.par_iter()
-		.batching_strategy(BatchingStrategy::fixed(1))
-		.for_each(|(e, t, m)| {
-			let x: Vec<_> = m
-				.inner
-				.iter()
-				.filter_map(|&(m, mat)| {
-					let m = ARef::loaded(m)
-						.map_err(|e| warn!("failed to load mesh {:?}: {:?}", m, e))
-						.ok()?;
-					let mat = ARef::loaded(mat)
-						.map_err(|e| warn!("failed to load material {:?}: {:?}", mat, e))
-						.ok()?;
-					Some((m, mat))
-				})
-				.collect();
-			cache.lock().unwrap().push((e, t, x));
-		});
+	for (e, m) in unknown.iter() {
+		cmd.entity(e).insert(PendingRtInstances::request(m));
+	}
+
+	for (e, t, p) in pending.iter() {
+		let Some(inner) = p.resolve() else {
+			continue;
+		};
 
-	for (e, t, inner) in cache.into_inner().unwrap() {
 		let inner = inner
 			.into_iter()
 			.map(|(view, mat)| {
@@ -352,6 +339,8 @@ fn sync_rt_scene(
 				(index, view, mat)
 			})
 			.collect();
-		cmd.entity(e).insert(KnownRtInstances(inner));
+		cmd.entity(e)
+			.remove::<PendingRtInstances>()
+			.insert(KnownRtInstances(inner));
 	}
 }
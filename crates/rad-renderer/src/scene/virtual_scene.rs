@@ -1,10 +1,5 @@
-use std::sync::Mutex;
-
 use bytemuck::NoUninit;
-use rad_core::{
-	Engine,
-	asset::aref::{ARef, LARef},
-};
+use rad_core::{Engine, asset::aref::LARef};
 use rad_graph::{
 	device::ShaderInfo,
 	graph::{BufferDesc, BufferUsage, ExternalBuffer, Frame, Res},
@@ -16,17 +11,15 @@ use rad_world::{
 	TickStage,
 	World,
 	bevy_ecs::{
-		batching::BatchingStrategy,
 		component::{Component, StorageType},
 		entity::Entity,
-		query::{Changed, Or, Without},
+		query::{Changed, Or, With, Without},
 		schedule::IntoSystemConfigs,
 		system::{Commands, Query, ResMut, Resource},
 	},
 	tick::Tick,
-	transform::Transform,
+	transform::GlobalTransform,
 };
-use tracing::warn;
 
 use crate::{
 	assets::{
@@ -34,7 +27,7 @@ use crate::{
 		mesh::virtual_mesh::{GpuAabb, VirtualMeshView},
 	},
 	components::mesh::MeshComponent,
-	scene::{GpuScene, GpuTransform, should_scene_sync},
+	scene::{GpuScene, GpuTransform, PendingInstances, should_scene_sync},
 	util::ResizableBuffer,
 };
 
@@ -172,7 +165,9 @@ impl VirtualSceneData {
 		}
 	}
 
-	fn push_instance(&mut self, index: u32, t: &Transform, m: &LARef<VirtualMeshView>, mat: &LARef<MaterialView>) {
+	fn push_instance(
+		&mut self, index: u32, t: &GlobalTransform, m: &LARef<VirtualMeshView>, mat: &LARef<MaterialView>,
+	) {
 		self.updates.push(GpuInstanceUpdate {
 			index,
 			_pad: 0,
@@ -194,34 +189,28 @@ impl Component for KnownVirtualInstances {
 	const STORAGE_TYPE: StorageType = StorageType::Table;
 }
 
+/// Instances whose asset views are still loading in the background.
+pub type PendingVirtualInstances = PendingInstances<VirtualMeshView>;
+
 // TODO: edits and deletion.
 fn sync_virtual_scene(
 	mut r: ResMut<VirtualSceneData>, mut cmd: Commands,
-	unknown: Query<(Entity, &Transform, &MeshComponent), Without<KnownVirtualInstances>>,
-	_: Query<(&Transform, &MeshComponent, &KnownVirtualInstances), Or<(Changed<Transform>, Changed<MeshComponent>)>>,
+	unknown: Query<(Entity, &MeshComponent), (Without<KnownVirtualInstances>, Without<PendingVirtualInstances>)>,
+	pending: Query<(Entity, &GlobalTransform, &PendingVirtualInstances), With<MeshComponent>>,
+	_: Query<
+		(&GlobalTransform, &MeshComponent, &KnownVirtualInstances),
+		Or<(Changed<GlobalTransform>, Changed<MeshComponent>)>,
+	>,
 ) {
-	let cache = Mutex::new(Vec::new());
-	unknown
-		.par_iter()
-		.batching_strategy(BatchingStrategy::fixed(1))
-		.for_each(|(e, t, m)| {
-			let x: Vec<_> = m
-				.inner
-				.iter()
-				.filter_map(|&(m, mat)| {
-					let m = ARef::loaded(m)
-						.map_err(|e| warn!("failed to load mesh {:?}: {:?}", m, e))
-						.ok()?;
-					let mat = ARef::loaded(mat)
-						.map_err(|e| warn!("failed to load material {:?}: {:?}", mat, e))
-						.ok()?;
-					Some((m, mat))
-				})
-				.collect();
-			cache.lock().unwrap().push((e, t, x));
-		});
+	for (e, m) in unknown.iter() {
+		cmd.entity(e).insert(PendingVirtualInstances::request(m));
+	}
+
+	for (e, t, p) in pending.iter() {
+		let Some(inner) = p.resolve() else {
+			continue;
+		};
 
-	for (e, t, inner) in cache.into_inner().unwrap() {
 		let inner = inner
 			.into_iter()
 			.map(|(view, material)| {
@@ -231,6 +220,8 @@ fn sync_virtual_scene(
 				(index, view, material)
 			})
 			.collect();
-		cmd.entity(e).insert(KnownVirtualInstances(inner));
+		cmd.entity(e)
+			.remove::<PendingVirtualInstances>()
+			.insert(KnownVirtualInstances(inner));
 	}
 }
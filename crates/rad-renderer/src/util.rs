@@ -2,7 +2,7 @@ use std::io::Write;
 
 use bytemuck::{bytes_of, NoUninit};
 use rad_graph::{
-	device::Device,
+	device::{Compute, Device, Graphics},
 	graph::{BufferUsage, ExternalBuffer, Frame, Res},
 	resource::{Buffer, BufferDesc, BufferHandle, BufferType, Resource},
 	Result,
@@ -74,3 +74,9 @@ impl ResizableBuffer {
 		}
 	}
 }
+
+/// Wait for every frame that has been submitted to finish, such as before writing data that all of them might read.
+pub fn wait_for_frames(device: &Device) -> Result<()> {
+	device.current_sync_point::<Graphics>().wait(device)?;
+	device.current_sync_point::<Compute>().wait(device)
+}
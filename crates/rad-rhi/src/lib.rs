@@ -1,4 +1,4 @@
-use rad_core::{EngineBuilder, Module};
+use rad_core::{Engine, EngineBuilder, Module};
 use rad_graph::{
 	ash::{ext, vk},
 	device::Device,
@@ -56,4 +56,12 @@ impl Module for RhiModule {
 				.0,
 		);
 	}
+
+	fn shutdown(engine: &Engine) {
+		// Modules using the device shut down before this and release what they put on it. The device itself is shared
+		// by every clone of `Device`, so it is only released with the process, which must not happen mid-submission.
+		unsafe {
+			let _ = engine.global::<Device>().device().device_wait_idle();
+		}
+	}
 }
@@ -1,6 +1,6 @@
 #![feature(allocator_api)]
 
-use std::mem::ManuallyDrop;
+use std::sync::Mutex;
 
 pub use egui;
 use egui::{Context, ViewportId};
@@ -8,9 +8,11 @@ use egui_winit::{pixels_per_point, winit::window::Theme, State};
 use rad_core::{Engine, EngineBuilder, Module};
 use rad_graph::{
 	arena::Arena,
+	device::Device,
 	graph::{Frame, RenderGraph, SwapchainImage},
 	Result,
 };
+use rad_rhi::RhiModule;
 use rad_window::{
 	winit::{event::WindowEvent, event_loop::ActiveEventLoop},
 	Window,
@@ -28,18 +30,37 @@ pub mod widgets;
 pub struct UiModule;
 
 impl Module for UiModule {
+	type Dependencies = (RhiModule,);
+
 	fn init(engine: &mut EngineBuilder) {
 		let ctx = Context::default();
 		fonts::setup_fonts(&ctx);
 		engine.global(ctx);
+		engine.global(UiRenderer(Mutex::new(None)));
+	}
+
+	fn shutdown(engine: &Engine) {
+		if let Some(ui) = engine.global::<UiRenderer>().0.lock().unwrap().take() {
+			let device: &Device = engine.global();
+			ui.graph.destroy(device);
+			unsafe {
+				ui.renderer.destroy();
+			}
+		}
 	}
 }
 
+/// The GPU state of the UI, owned by the module so that it is destroyed when the engine shuts down.
+struct UiRenderer(Mutex<Option<UiRendererInner>>);
+
+struct UiRendererInner {
+	graph: RenderGraph,
+	renderer: Renderer,
+}
+
 pub struct UiApp<T> {
 	inner: T,
 	arena: Arena,
-	graph: ManuallyDrop<RenderGraph>,
-	renderer: ManuallyDrop<Renderer>,
 	state: Option<State>,
 }
 
@@ -52,13 +73,13 @@ pub trait App {
 impl<T: App> UiApp<T> {
 	pub fn new(inner: T) -> Result<Self> {
 		let arena = Arena::new();
-		let graph = RenderGraph::new(Engine::get().global())?;
-		let renderer = Renderer::new(Engine::get().global())?;
+		*Engine::get().global::<UiRenderer>().0.lock().unwrap() = Some(UiRendererInner {
+			graph: RenderGraph::new(Engine::get().global())?,
+			renderer: Renderer::new(Engine::get().global())?,
+		});
 		Ok(Self {
 			inner,
 			arena,
-			graph: ManuallyDrop::new(graph),
-			renderer: ManuallyDrop::new(renderer),
 			state: None,
 		})
 	}
@@ -82,7 +103,12 @@ impl<T: App> rad_window::App for UiApp<T> {
 		let ctx = Engine::get().global::<Context>();
 		self.arena.reset();
 
-		let mut frame = self.graph.frame(Engine::get().global(), &self.arena)?;
+		// Only locked for the frame, so that the module can still be shut down while the app is alive.
+		let mut ui = Engine::get().global::<UiRenderer>().0.lock().unwrap();
+		let Some(ui) = ui.as_mut() else {
+			return Ok(());
+		};
+		let mut frame = ui.graph.frame(Engine::get().global(), &self.arena)?;
 
 		ctx.begin_pass(self.state.as_mut().unwrap().take_egui_input(window));
 		self.inner.render(window, &mut frame, ctx)?;
@@ -94,7 +120,7 @@ impl<T: App> rad_window::App for UiApp<T> {
 			.handle_platform_output(window, output.platform_output);
 		let tris = ctx.tessellate(output.shapes, pixels_per_point(ctx, window));
 
-		self.renderer.run(
+		ui.renderer.run(
 			&mut frame,
 			tris,
 			output.textures_delta,
@@ -116,12 +142,3 @@ impl<T: App> rad_window::App for UiApp<T> {
 		Ok(())
 	}
 }
-
-impl<T> Drop for UiApp<T> {
-	fn drop(&mut self) {
-		unsafe {
-			ManuallyDrop::take(&mut self.graph).destroy(Engine::get().global());
-			ManuallyDrop::take(&mut self.renderer).destroy();
-		}
-	}
-}
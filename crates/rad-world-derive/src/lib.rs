@@ -2,12 +2,15 @@
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, LitStr};
+use syn::{parse_macro_input, parse_quote, punctuated::Punctuated, Data, DeriveInput, LitStr, Token};
 
-#[proc_macro_derive(RadComponent, attributes(uuid))]
+/// `#[renamed_from("old_name", ...)]` on a field lets worlds saved before the field was renamed still load.
+#[proc_macro_derive(RadComponent, attributes(uuid, reflect, renamed_from))]
 pub fn component(input: TokenStream) -> TokenStream {
-	let inp: proc_macro2::TokenStream = input.clone().into();
-	let i = parse_macro_input!(input as DeriveInput);
+	let mut i = parse_macro_input!(input as DeriveInput);
+	if let Err(e) = renamed_fields(&mut i) {
+		return e.into_compile_error().into();
+	}
 
 	let uuid = i
 		.attrs
@@ -21,34 +24,71 @@ pub fn component(input: TokenStream) -> TokenStream {
 		})
 		.expect("no uuid attribute found");
 
-	let name = i.ident;
+	let name = &i.ident;
 	let (im, ty, wh) = i.generics.split_for_impl();
 
 	let path: TokenStream = quote! { std::module_path!() }.into();
 	let path: proc_macro2::TokenStream = path.expand_expr().unwrap().into();
 
+	// Everything is in an anonymous const, so the imports the reflection attributes need don't clash with the ones in
+	// the deriving module.
 	quote! {
-		use rad_world::{ReflectRadComponent, bevy_ecs::{prelude::Component, reflect::ReflectComponent}};
-		use rad_world::bevy_reflect as bevy_reflect;
-
-		rad_world::bevy_reflect::impl_reflect! {
-			#[reflect(RadComponent, Component)]
-			#[reflect(no_field_bounds)]
-			#[type_path = #path]
-			#inp
-		}
+		const _: () = {
+			use ::rad_world::{ReflectRadComponent, bevy_ecs::{prelude::Component, reflect::ReflectComponent}};
+			use ::rad_world::bevy_reflect as bevy_reflect;
+			#[allow(unused_imports)]
+			use ::rad_world::bevy_reflect::std_traits::ReflectDefault;
 
-		impl #im Component for #name #ty #wh {
-			const STORAGE_TYPE: rad_world::bevy_ecs::component::StorageType = rad_world::bevy_ecs::component::StorageType::Table;
-		}
+			::rad_world::bevy_reflect::impl_reflect! {
+				#[reflect(RadComponent, Component)]
+				#[reflect(no_field_bounds)]
+				#[type_path = #path]
+				#i
+			}
 
-		impl #im rad_world::RadComponent for #name #ty #wh {
-			fn uuid() -> rad_world::Uuid
-			where
-				Self: Sized { rad_world::uuid!(#uuid) }
+			impl #im Component for #name #ty #wh {
+				const STORAGE_TYPE: ::rad_world::bevy_ecs::component::StorageType =
+					::rad_world::bevy_ecs::component::StorageType::Table;
+			}
 
-			fn uuid_dyn(&self) -> rad_world::Uuid { rad_world::uuid!(#uuid) }
-		}
+			impl #im ::rad_world::RadComponent for #name #ty #wh {
+				fn uuid() -> ::rad_world::Uuid
+				where
+					Self: Sized { ::rad_world::uuid!(#uuid) }
+
+				fn uuid_dyn(&self) -> ::rad_world::Uuid { ::rad_world::uuid!(#uuid) }
+			}
+		};
 	}
 	.into()
 }
+
+fn renamed_fields(i: &mut DeriveInput) -> syn::Result<()> {
+	let Data::Struct(s) = &mut i.data else {
+		return Ok(());
+	};
+
+	for field in s.fields.iter_mut() {
+		let mut names = Vec::new();
+		let mut err = Ok(());
+		field.attrs.retain(|x| {
+			if !x.path().is_ident("renamed_from") {
+				return true;
+			}
+			match x.parse_args_with(Punctuated::<LitStr, Token![,]>::parse_terminated) {
+				Ok(x) => names.extend(x),
+				Err(e) => err = Err(e),
+			}
+			false
+		});
+		err?;
+
+		if !names.is_empty() {
+			field
+				.attrs
+				.push(parse_quote! { #[reflect(@::rad_world::serde::RenamedFrom(&[#(#names),*]))] });
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,650 @@
+//! Undoable edits to a world.
+//!
+//! Edits made through a `History` are recorded with what they replaced, so they can be undone and redone. Components
+//! are referred to by their UUID and stored reflected, so only `RadComponent`s can be edited this way. Entities that
+//! are despawned are recorded along with their descendants, leaving out runtime entities that are not saved, which are
+//! expected to be rebuilt by the systems that spawned them.
+
+use std::{collections::VecDeque, io};
+
+use bevy_ecs::{
+	bundle::Bundle,
+	entity::Entity,
+	world::{EntityWorldMut, World as EcsWorld},
+};
+use bevy_reflect::{GetPath, PartialReflect, Reflect, ReflectMut};
+use rad_core::asset::Uuid;
+use rustc_hash::FxHashMap;
+
+use crate::{
+	serde::{component_registration, reflect_component, saved_components, DoNotSerialize},
+	transform::{despawn_recursive, set_parent, Children, Parent},
+	ty_reg,
+	ReflectRadComponent,
+	World,
+};
+
+/// A record of edits to a world that can be undone and redone.
+pub struct History {
+	undo: VecDeque<Command>,
+	redo: Vec<Command>,
+	depth: usize,
+	/// Whether the next edit may merge with the last one.
+	merge: bool,
+}
+
+enum Command {
+	Spawn(Snapshot),
+	Despawn(Snapshot),
+	/// Insert, replace, or remove a component.
+	Component {
+		entity: Entity,
+		component: Uuid,
+		before: Option<Box<dyn PartialReflect>>,
+		after: Option<Box<dyn PartialReflect>>,
+	},
+	Field {
+		entity: Entity,
+		component: Uuid,
+		path: String,
+		before: Box<dyn PartialReflect>,
+		after: Box<dyn PartialReflect>,
+	},
+}
+
+/// The saved components of an entity and its descendants, root first.
+struct Snapshot {
+	parent: Option<Entity>,
+	entities: Vec<(Entity, Components)>,
+}
+
+type Components = Vec<(Uuid, Box<dyn PartialReflect>)>;
+
+impl History {
+	/// Create a history that remembers the last `depth` edits.
+	pub fn new(depth: usize) -> Self {
+		Self {
+			undo: VecDeque::new(),
+			redo: Vec::new(),
+			depth,
+			merge: false,
+		}
+	}
+
+	pub fn depth(&self) -> usize { self.depth }
+
+	/// Change how many edits are remembered, forgetting the oldest ones if there are too many.
+	pub fn set_depth(&mut self, depth: usize) {
+		self.depth = depth;
+		self.trim();
+	}
+
+	pub fn can_undo(&self) -> bool { !self.undo.is_empty() }
+
+	pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+	/// Forget all edits, such as when a different world is opened.
+	pub fn clear(&mut self) {
+		self.undo.clear();
+		self.redo.clear();
+		self.merge = false;
+	}
+
+	/// Stop the last edit from merging with the next one. Call this when a continuous edit, such as dragging a value,
+	/// ends.
+	pub fn seal(&mut self) { self.merge = false; }
+
+	/// Spawn an entity with `bundle`. Like [`World::spawn_empty`], the entity starts out with an identity `Transform`
+	/// and a new `PrefabId`, which `bundle` may replace.
+	pub fn spawn(&mut self, world: &mut World, bundle: impl Bundle) -> Entity {
+		let mut e = world.spawn_empty();
+		e.insert(bundle);
+		let entity = e.id();
+		let snapshot = Snapshot::take(world, entity);
+		self.push(Command::Spawn(snapshot), false);
+		entity
+	}
+
+	/// Despawn an entity along with all of its descendants.
+	pub fn despawn(&mut self, world: &mut World, entity: Entity) {
+		let snapshot = Snapshot::take(world, entity);
+		despawn_recursive(world, entity);
+		self.push(Command::Despawn(snapshot), false);
+	}
+
+	/// Insert a component into an entity, replacing the one it has.
+	pub fn insert(&mut self, world: &mut World, entity: Entity, component: &dyn Reflect) -> Result<(), io::Error> {
+		let uuid = ty_reg()
+			.get_type_data::<ReflectRadComponent>(component.type_id())
+			.and_then(|x| x.get(component))
+			.ok_or_else(|| invalid("value is not a registered `RadComponent`"))?
+			.uuid_dyn();
+		self.set_component(world, entity, uuid, Some(component.clone_value()))
+	}
+
+	/// Remove the component with `component` as its UUID from an entity.
+	pub fn remove(&mut self, world: &mut World, entity: Entity, component: Uuid) -> Result<(), io::Error> {
+		self.set_component(world, entity, component, None)
+	}
+
+	/// Set a field of a component of an entity. `path` is the reflection path of the field in the component with
+	/// `component` as its UUID, such as `position.x`.
+	///
+	/// Consecutive edits of the same field are merged into one until `seal` is called.
+	pub fn set_field(
+		&mut self, world: &mut World, entity: Entity, component: Uuid, path: &str, value: &dyn PartialReflect,
+	) -> Result<(), io::Error> {
+		if self.merge
+			&& let Some(Command::Field {
+				entity: e,
+				component: c,
+				path: p,
+				after,
+				..
+			}) = self.undo.back_mut()
+			&& (*e, *c, p.as_str()) == (entity, component, path)
+		{
+			set_field(world, entity, component, path, value)?;
+			*after = value.clone_value();
+			self.redo.clear();
+			return Ok(());
+		}
+
+		let before = get_field(world, entity, component, path)?;
+		set_field(world, entity, component, path, value)?;
+		self.push(
+			Command::Field {
+				entity,
+				component,
+				path: path.to_string(),
+				before,
+				after: value.clone_value(),
+			},
+			true,
+		);
+		Ok(())
+	}
+
+	/// Revert the last edit. Returns `false` if there is nothing to undo. If reverting fails, the world is left as it
+	/// was and the edit stays in the history.
+	pub fn undo(&mut self, world: &mut World) -> Result<bool, io::Error> {
+		let Some(command) = self.undo.pop_back() else {
+			return Ok(false);
+		};
+		self.merge = false;
+		let map = match command.apply(world, false) {
+			Ok(x) => x,
+			Err(e) => {
+				self.undo.push_back(command);
+				return Err(e);
+			},
+		};
+		self.redo.push(command);
+		self.remap(&map);
+		Ok(true)
+	}
+
+	/// Apply the last undone edit again. Returns `false` if there is nothing to redo. If applying fails, the world is
+	/// left as it was and the edit can still be redone.
+	pub fn redo(&mut self, world: &mut World) -> Result<bool, io::Error> {
+		let Some(command) = self.redo.pop() else {
+			return Ok(false);
+		};
+		self.merge = false;
+		let map = match command.apply(world, true) {
+			Ok(x) => x,
+			Err(e) => {
+				self.redo.push(command);
+				return Err(e);
+			},
+		};
+		self.undo.push_back(command);
+		self.remap(&map);
+		Ok(true)
+	}
+
+	fn set_component(
+		&mut self, world: &mut World, entity: Entity, component: Uuid, value: Option<Box<dyn PartialReflect>>,
+	) -> Result<(), io::Error> {
+		if self.merge
+			&& let Some(Command::Component {
+				entity: e,
+				component: c,
+				after,
+				..
+			}) = self.undo.back_mut()
+			&& (*e, *c) == (entity, component)
+		{
+			set_component(world, entity, component, value.as_deref())?;
+			*after = value;
+			self.redo.clear();
+			return Ok(());
+		}
+
+		let before = get_component(world, entity, component)?;
+		set_component(world, entity, component, value.as_deref())?;
+		self.push(
+			Command::Component {
+				entity,
+				component,
+				before,
+				after: value,
+			},
+			true,
+		);
+		Ok(())
+	}
+
+	fn push(&mut self, command: Command, merge: bool) {
+		self.undo.push_back(command);
+		self.redo.clear();
+		self.merge = merge;
+		self.trim();
+	}
+
+	fn trim(&mut self) {
+		while self.undo.len() > self.depth {
+			self.undo.pop_front();
+		}
+	}
+
+	/// Point the recorded edits at entities that had to be respawned with a different id.
+	fn remap(&mut self, map: &FxHashMap<Entity, Entity>) {
+		if map.is_empty() {
+			return;
+		}
+		for command in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+			command.remap(map);
+		}
+	}
+}
+
+impl Default for History {
+	fn default() -> Self { Self::new(100) }
+}
+
+impl Command {
+	/// Apply the command, or revert it if `!forward`. Returns the entities that were respawned with a different id.
+	fn apply(&self, world: &mut EcsWorld, forward: bool) -> Result<FxHashMap<Entity, Entity>, io::Error> {
+		match self {
+			Self::Spawn(snapshot) if forward => return snapshot.restore(world),
+			Self::Despawn(snapshot) if !forward => return snapshot.restore(world),
+			Self::Spawn(snapshot) | Self::Despawn(snapshot) => {
+				let root = entity_mut(world, snapshot.entities[0].0)?.id();
+				despawn_recursive(world, root);
+			},
+			Self::Component {
+				entity,
+				component,
+				before,
+				after,
+			} => {
+				let value = if forward { after } else { before };
+				set_component(world, *entity, *component, value.as_deref())?;
+			},
+			Self::Field {
+				entity,
+				component,
+				path,
+				before,
+				after,
+			} => {
+				let value = if forward { after } else { before };
+				set_field(world, *entity, *component, path, value.as_ref())?;
+			},
+		}
+		Ok(FxHashMap::default())
+	}
+
+	fn remap(&mut self, map: &FxHashMap<Entity, Entity>) {
+		let remap = |e: &mut Entity| *e = map.get(e).copied().unwrap_or(*e);
+		match self {
+			Self::Spawn(snapshot) | Self::Despawn(snapshot) => {
+				if let Some(parent) = &mut snapshot.parent {
+					remap(parent);
+				}
+				for (e, comps) in snapshot.entities.iter_mut() {
+					remap(e);
+					for (_, value) in comps.iter_mut() {
+						remap_entities(value.as_mut(), map);
+					}
+				}
+			},
+			Self::Component {
+				entity, before, after, ..
+			} => {
+				remap(entity);
+				for value in before.iter_mut().chain(after.iter_mut()) {
+					remap_entities(value.as_mut(), map);
+				}
+			},
+			Self::Field {
+				entity, before, after, ..
+			} => {
+				remap(entity);
+				remap_entities(before.as_mut(), map);
+				remap_entities(after.as_mut(), map);
+			},
+		}
+	}
+}
+
+impl Snapshot {
+	fn take(world: &EcsWorld, root: Entity) -> Self {
+		let mut entities = Vec::new();
+		let mut stack = vec![root];
+		while let Some(e) = stack.pop() {
+			let en = world.entity(e);
+			if e != root && en.contains::<DoNotSerialize>() {
+				continue;
+			}
+			if let Some(children) = en.get::<Children>() {
+				stack.extend(children.0.iter().rev());
+			}
+
+			let comps = saved_components(world, en)
+				.map(|info| reflect_component(en, info).map(|(uuid, x)| (uuid, x.clone_value())))
+				.collect::<Result<_, _>>()
+				.expect("saved components must be reflectable");
+			entities.push((e, comps));
+		}
+
+		Self {
+			parent: world.get::<Parent>(root).map(|x| x.0),
+			entities,
+		}
+	}
+
+	/// Spawn the entities again, with the same ids if they are still free. Everything that can fail is checked before
+	/// anything is spawned.
+	fn restore(&self, world: &mut EcsWorld) -> Result<FxHashMap<Entity, Entity>, io::Error> {
+		if let Some(parent) = self.parent
+			&& world.get_entity(parent).is_err()
+		{
+			return Err(invalid("parent entity does not exist"));
+		}
+		let comps = self
+			.entities
+			.iter()
+			.map(|(_, comps)| {
+				comps
+					.iter()
+					.map(|(uuid, value)| Ok((component_registration(*uuid)?.1, value)))
+					.collect::<Result<Vec<_>, io::Error>>()
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let mut map = FxHashMap::default();
+		for &(e, _) in self.entities.iter() {
+			#[allow(deprecated)]
+			if world.get_or_spawn(e).is_none() {
+				map.insert(e, world.spawn_empty().id());
+			}
+		}
+
+		for ((e, _), comps) in self.entities.iter().zip(comps) {
+			let e = map.get(e).copied().unwrap_or(*e);
+			for (refl, value) in comps {
+				let mut value = value.clone_value();
+				remap_entities(value.as_mut(), &map);
+				refl.insert(&mut world.entity_mut(e), value.as_ref(), ty_reg());
+			}
+		}
+
+		// Runtime children were not recorded, so drop them from the restored `Children`.
+		for &(e, _) in self.entities.iter() {
+			let e = map.get(&e).copied().unwrap_or(e);
+			if let Some(children) = world.get::<Children>(e) {
+				let children: Vec<_> = children
+					.0
+					.iter()
+					.copied()
+					.filter(|&x| world.get::<Parent>(x).is_some_and(|x| x.0 == e))
+					.collect();
+				world.entity_mut(e).insert(Children(children));
+			}
+		}
+
+		let root = self.entities[0].0;
+		set_parent(world, map.get(&root).copied().unwrap_or(root), self.parent)?;
+		Ok(map)
+	}
+}
+
+fn get_component(
+	world: &EcsWorld, entity: Entity, component: Uuid,
+) -> Result<Option<Box<dyn PartialReflect>>, io::Error> {
+	let (_, refl) = component_registration(component)?;
+	let en = world.get_entity(entity).map_err(|_| invalid("entity does not exist"))?;
+	Ok(refl.reflect(en).map(|x| x.clone_value()))
+}
+
+fn set_component(
+	world: &mut EcsWorld, entity: Entity, component: Uuid, value: Option<&dyn PartialReflect>,
+) -> Result<(), io::Error> {
+	let (_, refl) = component_registration(component)?;
+	let mut en = entity_mut(world, entity)?;
+	match value {
+		Some(value) => refl.apply_or_insert(&mut en, value, ty_reg()),
+		None => refl.remove(&mut en),
+	}
+	Ok(())
+}
+
+fn get_field(
+	world: &EcsWorld, entity: Entity, component: Uuid, path: &str,
+) -> Result<Box<dyn PartialReflect>, io::Error> {
+	let (_, refl) = component_registration(component)?;
+	let en = world.get_entity(entity).map_err(|_| invalid("entity does not exist"))?;
+	let comp = refl
+		.reflect(en)
+		.ok_or_else(|| invalid("entity does not have the component"))?;
+	let field = comp.reflect_path(path).map_err(|e| invalid(e.to_string()))?;
+	Ok(field.clone_value())
+}
+
+fn set_field(
+	world: &mut EcsWorld, entity: Entity, component: Uuid, path: &str, value: &dyn PartialReflect,
+) -> Result<(), io::Error> {
+	let (_, refl) = component_registration(component)?;
+	let mut comp = refl
+		.reflect_mut(entity_mut(world, entity)?)
+		.ok_or_else(|| invalid("entity does not have the component"))?;
+	let field = comp.reflect_path_mut(path).map_err(|e| invalid(e.to_string()))?;
+	field.try_apply(value).map_err(|e| invalid(e.to_string()))
+}
+
+fn entity_mut(world: &mut EcsWorld, entity: Entity) -> Result<EntityWorldMut<'_>, io::Error> {
+	world
+		.get_entity_mut(entity)
+		.map_err(|_| invalid("entity does not exist"))
+}
+
+/// Replace the `Entity` values in `value` that are in `map`.
+fn remap_entities(value: &mut dyn PartialReflect, map: &FxHashMap<Entity, Entity>) {
+	if let Some(e) = value.try_downcast_mut::<Entity>() {
+		*e = map.get(e).copied().unwrap_or(*e);
+		return;
+	}
+
+	match value.reflect_mut() {
+		ReflectMut::Struct(x) => (0..x.field_len()).for_each(|i| remap_entities(x.field_at_mut(i).unwrap(), map)),
+		ReflectMut::TupleStruct(x) => (0..x.field_len()).for_each(|i| remap_entities(x.field_mut(i).unwrap(), map)),
+		ReflectMut::Tuple(x) => (0..x.field_len()).for_each(|i| remap_entities(x.field_mut(i).unwrap(), map)),
+		ReflectMut::List(x) => (0..x.len()).for_each(|i| remap_entities(x.get_mut(i).unwrap(), map)),
+		ReflectMut::Array(x) => (0..x.len()).for_each(|i| remap_entities(x.get_mut(i).unwrap(), map)),
+		ReflectMut::Map(x) => (0..x.len()).for_each(|i| remap_entities(x.get_at_mut(i).unwrap().1, map)),
+		ReflectMut::Enum(x) => (0..x.field_len()).for_each(|i| remap_entities(x.field_at_mut(i).unwrap(), map)),
+		_ => {},
+	}
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg.into()) }
+
+#[cfg(test)]
+mod tests {
+	use bevy_ecs::query::With;
+
+	use super::*;
+	use crate::{test_engine, transform::Transform, RadComponent};
+
+	fn x(world: &World, e: Entity) -> f32 { world.get::<Transform>(e).unwrap().position.x }
+
+	fn set_x(history: &mut History, world: &mut World, e: Entity, x: f32) {
+		history
+			.set_field(world, e, Transform::uuid(), "position.x", &x)
+			.unwrap();
+	}
+
+	#[test]
+	fn spawn_despawn() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let mut history = History::default();
+		let a = history.spawn(&mut world, ());
+		assert_eq!(world.get::<Transform>(a), Some(&Transform::identity()));
+		let b = world.spawn_empty().id();
+		let runtime = world.spawn(DoNotSerialize).id();
+		world.set_parent(b, Some(a)).unwrap();
+		world.set_parent(runtime, Some(a)).unwrap();
+		set_x(&mut history, &mut world, b, 1.0);
+		history.despawn(&mut world, a);
+		assert_eq!(world.entities().len(), 0);
+
+		// Take the freed ids, so the entities have to be respawned with new ones.
+		let taken: Vec<_> = (0..3).map(|_| world.spawn_empty().id()).collect();
+		assert!(history.undo(&mut world).unwrap());
+		let a2 = world.query_filtered::<Entity, With<Children>>().single(&world);
+		let [b2] = world.get::<Children>(a2).unwrap().0[..] else {
+			panic!("runtime children should not be restored");
+		};
+		assert!(!taken.contains(&a2) && !taken.contains(&b2));
+		assert_eq!(world.get::<Parent>(b2), Some(&Parent(a2)));
+		assert_eq!(x(&world, b2), 1.0);
+
+		// Edits recorded before the respawn now refer to the new entities.
+		assert!(history.undo(&mut world).unwrap());
+		assert_eq!(x(&world, b2), 0.0);
+		assert!(history.redo(&mut world).unwrap());
+		assert_eq!(x(&world, b2), 1.0);
+		assert!(history.redo(&mut world).unwrap());
+		assert!(world.get_entity(a2).is_err() && world.get_entity(b2).is_err());
+		assert!(!history.redo(&mut world).unwrap());
+
+		history.undo(&mut world).unwrap();
+		history.undo(&mut world).unwrap();
+		let a3 = world.query_filtered::<Entity, With<Children>>().single(&world);
+		assert!(history.undo(&mut world).unwrap());
+		assert!(world.get_entity(a3).is_err());
+		assert_eq!(world.entities().len(), 3);
+		assert!(!history.can_undo());
+	}
+
+	#[test]
+	fn insert_remove() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let mut history = History::default();
+		let a = world.spawn_empty().id();
+		let moved = Transform {
+			position: vek::Vec3::new(1.0, 2.0, 3.0),
+			..Transform::identity()
+		};
+		history.insert(&mut world, a, &moved).unwrap();
+		history.insert(&mut world, a, &Children(Vec::new())).unwrap();
+		history.remove(&mut world, a, Transform::uuid()).unwrap();
+		assert!(world.get::<Transform>(a).is_none());
+
+		history.undo(&mut world).unwrap();
+		assert_eq!(world.get::<Transform>(a), Some(&moved));
+		history.undo(&mut world).unwrap();
+		assert!(world.get::<Children>(a).is_none());
+		history.undo(&mut world).unwrap();
+		assert_eq!(world.get::<Transform>(a), Some(&Transform::identity()));
+
+		history.redo(&mut world).unwrap();
+		history.redo(&mut world).unwrap();
+		assert_eq!(world.get::<Transform>(a), Some(&moved));
+		assert_eq!(world.get::<Children>(a), Some(&Children(Vec::new())));
+
+		let err = history.insert(&mut world, a, &1.0f32).unwrap_err();
+		assert!(err.to_string().contains("not a registered"), "{err}");
+	}
+
+	#[test]
+	fn merge() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let mut history = History::default();
+		let a = world.spawn_empty().id();
+
+		// A drag is one edit until it is sealed.
+		set_x(&mut history, &mut world, a, 1.0);
+		set_x(&mut history, &mut world, a, 2.0);
+		history.seal();
+		set_x(&mut history, &mut world, a, 3.0);
+		history
+			.set_field(&mut world, a, Transform::uuid(), "position.y", &1.0f32)
+			.unwrap();
+		set_x(&mut history, &mut world, a, 4.0);
+
+		history.undo(&mut world).unwrap();
+		assert_eq!(x(&world, a), 3.0);
+		history.undo(&mut world).unwrap();
+		history.undo(&mut world).unwrap();
+		assert_eq!(x(&world, a), 2.0);
+		history.undo(&mut world).unwrap();
+		assert_eq!(x(&world, a), 0.0);
+		assert!(!history.can_undo());
+	}
+
+	#[test]
+	fn depth() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let mut history = History::new(2);
+		let a = world.spawn_empty().id();
+		for i in 1..=3 {
+			set_x(&mut history, &mut world, a, i as f32);
+			history.seal();
+		}
+
+		history.undo(&mut world).unwrap();
+		history.undo(&mut world).unwrap();
+		assert!(!history.undo(&mut world).unwrap());
+		assert_eq!(x(&world, a), 1.0);
+
+		history.redo(&mut world).unwrap();
+		history.redo(&mut world).unwrap();
+		history.set_depth(1);
+		history.undo(&mut world).unwrap();
+		assert!(!history.can_undo());
+		assert_eq!(x(&world, a), 2.0);
+	}
+
+	#[test]
+	fn failed_undo() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let mut history = History::default();
+		let a = world.spawn_empty().id();
+		let b = world.spawn_empty().id();
+		world.set_parent(b, Some(a)).unwrap();
+		history.despawn(&mut world, b);
+		world.despawn_recursive(a);
+
+		// The edit is kept, and nothing is spawned.
+		let err = history.undo(&mut world).unwrap_err();
+		assert_eq!(err.to_string(), "parent entity does not exist");
+		assert!(history.can_undo() && !history.can_redo());
+		assert_eq!(world.entities().len(), 0);
+
+		let a = history.spawn(&mut world, ());
+		set_x(&mut history, &mut world, a, 1.0);
+		history.undo(&mut world).unwrap();
+		world.despawn(a);
+		let err = history.redo(&mut world).unwrap_err();
+		assert_eq!(err.to_string(), "entity does not exist");
+		assert!(history.can_undo() && history.can_redo());
+	}
+}
@@ -10,18 +10,23 @@ pub use bevy_reflect;
 use bevy_reflect::{reflect_trait, FromType, GetTypeRegistration, Reflect, ReflectFromReflect, TypePath};
 pub use rad_core::{asset::Uuid, uuid};
 use rad_core::{
-	asset::{map_dec_err, map_enc_err, Asset, AssetRead, AssetWrite},
+	asset::{aref::UntypedAssetId, Asset, AssetRead, AssetWrite},
 	Engine,
 	EngineBuilder,
 	Module,
 };
 pub use rad_world_derive::RadComponent;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 pub use crate::tick::TickStage;
-use crate::{self as rad_world};
 
+// Lets the `RadComponent` derive refer to this crate as `::rad_world` from inside it too.
+extern crate self as rad_world;
+
+pub mod history;
+pub mod prefab;
 pub mod serde;
+pub mod text;
 pub mod tick;
 pub mod transform;
 
@@ -65,8 +70,18 @@ impl Module for WorldModule {
 		});
 
 		engine.asset::<World>();
+		engine.migration::<World>(0, serde::migrate_v0);
+		engine.migration::<World>(1, serde::migrate_v1);
 
 		engine.component::<transform::Transform>();
+		engine.component::<transform::Parent>();
+		engine.component::<transform::Children>();
+		engine.component_dep_type::<Vec<bevy_ecs::entity::Entity>>();
+		engine.component::<prefab::PrefabComponent>();
+		engine.component::<prefab::PrefabId>();
+		engine.component_dep_type::<prefab::PrefabOverride>();
+		engine.component_dep_type::<Vec<prefab::PrefabOverride>>();
+		engine.asset_view::<prefab::PrefabView>();
 	}
 }
 
@@ -98,9 +113,19 @@ impl World {
 
 	pub fn spawn_empty(&mut self) -> EntityWorldMut<'_> {
 		let mut e = self.inner.spawn_empty();
-		e.insert(transform::Transform::identity());
+		e.insert((transform::Transform::identity(), prefab::PrefabId(Uuid::new_v4())));
 		e
 	}
+
+	/// Load a world from its text form. See [`text`] for the format.
+	pub fn from_text(text: &str) -> Result<Self, io::Error> {
+		let mut inner = bevy_ecs::world::World::new();
+		text::deserialize_world(text, &mut inner)?;
+		Ok(Self { inner })
+	}
+
+	/// Write the world in its text form.
+	pub fn to_text(&self) -> Result<String, io::Error> { text::serialize_world(&self.inner) }
 }
 
 impl Deref for World {
@@ -113,33 +138,38 @@ impl DerefMut for World {
 }
 
 impl Asset for World {
+	const REFERENCES_ASSETS: bool = true;
 	const UUID: Uuid = uuid!("aac9bce6-582b-422b-b56c-2048cc0c4a2f");
+	const VERSION: u32 = 2;
 
 	fn load(mut data: Box<dyn AssetRead>) -> Result<Self, io::Error> {
-		let c = bincode::config::standard();
-
-		let count: u32 = bincode::decode_from_std_read(&mut data, c).map_err(map_dec_err)?;
 		let mut inner = bevy_ecs::world::World::new();
-		for _ in 0..count {
-			serde::deserialize_entity(&mut data, &mut inner)?;
-		}
-
+		serde::deserialize_world(&mut data, &mut inner)?;
 		Ok(Self { inner })
 	}
 
-	fn save(&self, mut to: &mut dyn AssetWrite) -> Result<(), io::Error> {
-		let c = bincode::config::standard();
-		let count = self.inner.entities().len();
-		bincode::encode_into_std_write(count, &mut to, c).map_err(map_enc_err)?;
+	fn save(&self, to: &mut dyn AssetWrite) -> Result<(), io::Error> { serde::serialize_world(to, &self.inner) }
 
+	fn dependencies(&self) -> Vec<(UntypedAssetId, Uuid)> {
+		let mut out = Vec::new();
 		for en in self.inner.iter_entities() {
-			serde::serialize_entity(&mut to, &self.inner, en)?;
+			serde::entity_dependencies(&self.inner, en, &mut out);
 		}
-
-		Ok(())
+		let mut seen = FxHashSet::default();
+		out.retain(|&x| seen.insert(x));
+		out
 	}
 }
 
 fn ty_reg() -> &'static bevy_reflect::TypeRegistry { &Engine::get().global::<TypeRegistry>().inner }
 
 fn uuid_to_ty(uuid: Uuid) -> Option<TypeId> { Engine::get().global::<TypeRegistry>().uuid_map.get(&uuid).copied() }
+
+/// An engine with this module and an in-memory asset source, entered on the test's thread.
+#[cfg(test)]
+fn test_engine(init: impl FnOnce(&mut EngineBuilder)) -> rad_core::ScopedEngine {
+	let mut engine = Engine::builder().module::<WorldModule>();
+	engine.asset_source(rad_core::asset::memory::MemoryAssetSource::new());
+	init(&mut engine);
+	engine.build_scoped()
+}
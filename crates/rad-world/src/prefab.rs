@@ -0,0 +1,431 @@
+//! Prefabs: instancing one world inside another.
+//!
+//! An entity with a `PrefabComponent` gets a copy of every entity in the prefab world, with the prefab's root entities
+//! parented to it. The copies are runtime state and are not saved, so edits to the prefab show up in every world that
+//! uses it the next time it is loaded, or immediately with hot reloading. Per-instance changes are saved as
+//! overrides on the `PrefabComponent` instead, which find the entity they change by its `PrefabId`.
+
+use std::{io, sync::RwLock};
+
+use bevy_ecs::{entity::Entity, query::Without, world::World as EcsWorld};
+use bevy_reflect::{GetPath, Reflect};
+use rad_core::asset::{
+	aref::{ARef, AssetId, LoadState},
+	AssetView,
+	Uuid,
+};
+use rustc_hash::FxHashMap;
+use tracing::warn;
+
+use crate::{
+	serde::{component_registration, deserialize_world, serialize_world, DoNotSerialize, EntityMap},
+	text,
+	transform::{despawn_recursive, set_parent, Parent},
+	ty_reg,
+	RadComponent,
+	World,
+};
+
+/// Spawns the entities of a prefab world under this entity.
+#[derive(Clone, Debug, Default, PartialEq, RadComponent)]
+#[uuid("8b1f7d52-3c6e-4e0a-9f2d-5a4c1e7b9d03")]
+pub struct PrefabComponent {
+	pub prefab: AssetId<World>,
+	/// Applied in order after the prefab is spawned.
+	pub overrides: Vec<PrefabOverride>,
+}
+
+/// A saved id for an entity, so prefab overrides keep finding it when other entities are added to or removed from the
+/// prefab. `World::spawn_empty` gives every entity a new one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, RadComponent)]
+#[uuid("e7a2c4d9-1b3f-4a68-8c5e-9f0d2b6a4e71")]
+pub struct PrefabId(pub Uuid);
+
+/// A change to a field of a component in one instance of a prefab.
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
+pub struct PrefabOverride {
+	/// The `PrefabId` of the entity in the prefab world.
+	pub entity: Uuid,
+	/// The UUID of the component.
+	pub component: Uuid,
+	/// The reflection path of the field in the component, such as `position.x`. Empty to replace the whole component.
+	pub path: String,
+	/// The value of the field in the text form of worlds, with `Entity` fields referring to entities in the prefab by
+	/// the order they are saved in.
+	pub value: String,
+}
+
+/// The saved form of a prefab world, shared by all of its instances.
+pub struct PrefabView {
+	data: RwLock<Vec<u8>>,
+}
+
+impl AssetView for PrefabView {
+	type Base = World;
+	type Ctx = ();
+
+	fn load(_: &'static Self::Ctx, base: Self::Base) -> Result<Self, io::Error> {
+		let mut data = Vec::new();
+		serialize_world(&mut data, &base)?;
+		Ok(Self {
+			data: RwLock::new(data),
+		})
+	}
+
+	fn size(&self) -> usize { self.data.read().unwrap().len() }
+
+	fn reload(&self, _: &'static Self::Ctx, base: Self::Base) -> Result<(), io::Error> {
+		let mut data = Vec::new();
+		serialize_world(&mut data, &base)?;
+		*self.data.write().unwrap() = data;
+		Ok(())
+	}
+}
+
+/// Added to entities spawned from a prefab.
+#[derive(Copy, Clone, Debug, bevy_ecs::component::Component)]
+pub struct PrefabMember {
+	/// The entity with the `PrefabComponent`.
+	pub owner: Entity,
+}
+
+/// The spawned state of a `PrefabComponent`.
+#[derive(bevy_ecs::component::Component)]
+struct PrefabInstance {
+	view: ARef<PrefabView>,
+	/// The component and prefab generation the entities were spawned from.
+	spawned: Option<(PrefabComponent, u64)>,
+	roots: Vec<Entity>,
+	entities: Vec<Entity>,
+}
+
+impl World {
+	/// Override a field of an entity spawned from a prefab with its current value, so it is kept when the owning world
+	/// is saved. `path` is the reflection path of the field in the component with `component` as its UUID.
+	pub fn override_prefab_field(&mut self, entity: Entity, component: Uuid, path: &str) -> Result<(), io::Error> {
+		let member = *self
+			.get::<PrefabMember>(entity)
+			.ok_or_else(|| invalid("entity was not spawned from a prefab"))?;
+		let PrefabId(id) = *self
+			.get::<PrefabId>(entity)
+			.ok_or_else(|| invalid("entity has no `PrefabId` in the prefab"))?;
+		let instance = self.get::<PrefabInstance>(member.owner).unwrap();
+		let entities = EntityMap::from_entities(instance.entities.clone());
+
+		let (_, refl) = component_registration(component)?;
+		let comp = refl
+			.reflect(self.entity(entity))
+			.ok_or_else(|| invalid("entity does not have the component"))?;
+		let field = comp.reflect_path(path).map_err(|e| invalid(e.to_string()))?;
+		let value = text::serialize_value(field, &entities)?;
+
+		let mut owner = self.entity_mut(member.owner);
+		let mut prefab = owner.get_mut::<PrefabComponent>().unwrap();
+		prefab
+			.overrides
+			.retain(|x| !(x.entity == id && x.component == component && x.path == path));
+		prefab.overrides.push(PrefabOverride {
+			entity: id,
+			component,
+			path: path.to_string(),
+			value,
+		});
+		let prefab = prefab.clone();
+
+		// The instance already looks like this, so don't respawn it.
+		let mut instance = owner.get_mut::<PrefabInstance>().unwrap();
+		if let Some((spawned, _)) = &mut instance.spawned {
+			*spawned = prefab;
+		}
+
+		Ok(())
+	}
+}
+
+/// Spawn the entities of new prefab instances, and respawn instances whose component or prefab changed.
+pub(crate) fn update_prefabs(world: &mut EcsWorld) {
+	let new: Vec<_> = world
+		.query_filtered::<(Entity, &PrefabComponent), Without<PrefabInstance>>()
+		.iter(world)
+		.map(|(e, c)| (e, c.prefab))
+		.collect();
+	for (e, prefab) in new {
+		let view = ARef::unloaded(prefab);
+		view.request();
+		world.entity_mut(e).insert(PrefabInstance {
+			view,
+			spawned: None,
+			roots: Vec::new(),
+			entities: Vec::new(),
+		});
+	}
+
+	let mut stale = Vec::new();
+	for (e, c, mut instance) in world
+		.query::<(Entity, &PrefabComponent, &mut PrefabInstance)>()
+		.iter_mut(world)
+	{
+		if instance.view.id() != c.prefab {
+			instance.view = ARef::unloaded(c.prefab);
+			instance.view.request();
+		}
+
+		let generation = instance.view.generation();
+		if instance
+			.spawned
+			.as_ref()
+			.is_some_and(|(spawned, spawned_generation)| spawned == c && *spawned_generation == generation)
+		{
+			continue;
+		}
+		match instance.view.state() {
+			LoadState::Loaded => stale.push(e),
+			LoadState::Failed => {
+				warn!("failed to load prefab {}: {:?}", c.prefab, instance.view.error());
+				instance.spawned = Some((c.clone(), generation));
+			},
+			_ => {},
+		}
+	}
+
+	for e in stale {
+		if let Err(err) = spawn_instance(world, e) {
+			warn!("failed to spawn prefab: {:?}", err);
+		}
+	}
+}
+
+fn spawn_instance(world: &mut EcsWorld, owner: Entity) -> Result<(), io::Error> {
+	let c = world.get::<PrefabComponent>(owner).unwrap().clone();
+	let mut instance = world.get_mut::<PrefabInstance>(owner).unwrap();
+	let view = instance.view.try_loaded().unwrap();
+	instance.spawned = Some((c.clone(), view.generation()));
+	instance.entities.clear();
+	let roots = std::mem::take(&mut instance.roots);
+	for root in roots {
+		despawn_recursive(world, root);
+	}
+
+	let mut ancestor = world.get::<Parent>(owner).map(|x| x.0);
+	while let Some(x) = ancestor {
+		if world.get::<PrefabComponent>(x).is_some_and(|x| x.prefab == c.prefab) {
+			return Err(invalid(format!("prefab {} contains itself", c.prefab)));
+		}
+		ancestor = world.get::<Parent>(x).map(|x| x.0);
+	}
+
+	let entities = deserialize_world(&mut view.data.read().unwrap().as_slice(), world)?;
+	let mut roots = Vec::new();
+	let mut ids = FxHashMap::default();
+	for &e in entities.iter() {
+		let mut en = world.entity_mut(e);
+		en.insert((DoNotSerialize, PrefabMember { owner }));
+		if let Some(&PrefabId(id)) = en.get::<PrefabId>() {
+			ids.insert(id, e);
+		}
+		if !en.contains::<Parent>() {
+			roots.push(e);
+		}
+	}
+	for &root in roots.iter() {
+		set_parent(world, root, Some(owner))?;
+	}
+
+	for o in c.overrides.iter() {
+		if let Err(err) = apply_override(world, &ids, &entities, o) {
+			warn!(
+				"failed to apply prefab override of `{}` on entity {}: {:?}",
+				o.path, o.entity, err
+			);
+		}
+	}
+
+	let mut instance = world.get_mut::<PrefabInstance>(owner).unwrap();
+	instance.roots = roots;
+	instance.entities = entities;
+	Ok(())
+}
+
+fn apply_override(
+	world: &mut EcsWorld, ids: &FxHashMap<Uuid, Entity>, entities: &[Entity], o: &PrefabOverride,
+) -> Result<(), io::Error> {
+	let &e = ids
+		.get(&o.entity)
+		.ok_or_else(|| invalid("entity is not in the prefab"))?;
+	let (_, refl) = component_registration(o.component)?;
+	let mut comp = refl
+		.reflect_mut(world.entity_mut(e))
+		.ok_or_else(|| invalid("entity does not have the component"))?;
+	let field = comp
+		.reflect_path_mut(o.path.as_str())
+		.map_err(|e| invalid(e.to_string()))?;
+	let reg = field
+		.get_represented_type_info()
+		.and_then(|x| ty_reg().get(x.type_id()))
+		.ok_or_else(|| invalid("field type is not registered"))?;
+	let value = text::deserialize_value(reg, &o.value, entities)?;
+	field.try_apply(value.as_ref()).map_err(|e| invalid(e.to_string()))
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg.into()) }
+
+#[cfg(test)]
+mod tests {
+	use bevy_ecs::query::With;
+	use rad_core::{asset::memory::MemoryAssetSource, Engine};
+	use vek::Vec3;
+
+	use super::*;
+	use crate::{
+		test_engine,
+		tick::Tick,
+		transform::{Children, Transform},
+	};
+
+	fn source() -> &'static MemoryAssetSource { Engine::get().asset_source() }
+
+	fn at(world: &mut World, x: f32) -> Entity {
+		world
+			.spawn_empty()
+			.insert(Transform {
+				position: Vec3::new(x, 0.0, 0.0),
+				..Transform::identity()
+			})
+			.id()
+	}
+
+	fn x(world: &World, e: Entity) -> f32 { world.get::<Transform>(e).unwrap().position.x }
+
+	fn instance(world: &World, owner: Entity) -> Vec<Entity> {
+		world.get::<PrefabInstance>(owner).unwrap().entities.clone()
+	}
+
+	/// A prefab with a root at `x = 1`, and a child of it at `x = 2`. Returns the prefab, and the root and child.
+	fn prefab() -> (World, Entity, Entity) {
+		let mut world = World::new();
+		let root = at(&mut world, 1.0);
+		let child = at(&mut world, 2.0);
+		world.set_parent(child, Some(root)).unwrap();
+		(world, root, child)
+	}
+
+	fn spawn_owner(world: &mut World, prefab: AssetId<World>) -> Entity {
+		world
+			.spawn_empty()
+			.insert(PrefabComponent {
+				prefab,
+				overrides: Vec::new(),
+			})
+			.id()
+	}
+
+	#[test]
+	fn spawn() {
+		let _engine = test_engine(|_| {});
+		let id = source().add(&prefab().0).unwrap();
+		let _view = ARef::<PrefabView>::loaded(id).unwrap();
+
+		let mut world = World::new();
+		let mut tick = Tick::new();
+		let owner = spawn_owner(&mut world, id);
+		tick.tick(&mut world);
+
+		let [root] = world.get::<Children>(owner).unwrap().0[..] else {
+			panic!("expected one root");
+		};
+		let [child] = world.get::<Children>(root).unwrap().0[..] else {
+			panic!("expected one child");
+		};
+		assert_eq!(instance(&world, owner), [root, child]);
+		assert_eq!((x(&world, root), x(&world, child)), (1.0, 2.0));
+		for e in [root, child] {
+			assert_eq!(world.get::<PrefabMember>(e).unwrap().owner, owner);
+			assert!(world.get::<DoNotSerialize>(e).is_some());
+		}
+
+		// Only the owner is saved, and it isn't respawned without changes.
+		let mut data = Vec::new();
+		serialize_world(&mut data, &world).unwrap();
+		let loaded = deserialize_world(&mut data.as_slice(), &mut World::new()).unwrap();
+		assert_eq!(loaded.len(), 1);
+		tick.tick(&mut world);
+		assert_eq!(instance(&world, owner), [root, child]);
+	}
+
+	#[test]
+	fn overrides() {
+		let _engine = test_engine(|_| {});
+		let id = AssetId::new();
+		let mut p = World::new();
+		// Saved first, so removing it later moves the other entities in the saved prefab.
+		let first = p.spawn_empty().id();
+		let root = at(&mut p, 1.0);
+		let child = at(&mut p, 2.0);
+		p.set_parent(child, Some(root)).unwrap();
+		source().insert(id, &p).unwrap();
+		let view = ARef::<PrefabView>::loaded(id).unwrap();
+
+		let mut world = World::new();
+		let mut tick = Tick::new();
+		let owner = spawn_owner(&mut world, id);
+		tick.tick(&mut world);
+		let spawned = instance(&world, owner);
+		let copy = *spawned
+			.iter()
+			.find(|&&e| world.get::<PrefabId>(e) == p.get::<PrefabId>(child))
+			.unwrap();
+		world.get_mut::<Transform>(copy).unwrap().position.x = 5.0;
+		world
+			.override_prefab_field(copy, Transform::uuid(), "position.x")
+			.unwrap();
+		world.get_mut::<Transform>(copy).unwrap().position.x = 6.0;
+		world
+			.override_prefab_field(copy, Transform::uuid(), "position.x")
+			.unwrap();
+		let overrides = &world.get::<PrefabComponent>(owner).unwrap().overrides;
+		assert_eq!(overrides.len(), 1);
+		assert_eq!(Some(&PrefabId(overrides[0].entity)), p.get::<PrefabId>(child));
+		tick.tick(&mut world);
+		assert_eq!(instance(&world, owner), spawned);
+
+		// Reloading the prefab respawns the instance with the override.
+		p.despawn(first);
+		source().insert(id, &p).unwrap();
+		Engine::get().reload_asset(id.to_untyped()).unwrap();
+		assert_eq!(view.generation(), 1);
+		tick.tick(&mut world);
+		let respawned = instance(&world, owner);
+		assert_eq!(respawned.len(), 2);
+		assert!(spawned.iter().all(|&e| world.get_entity(e).is_err()));
+		let [root, copy] = respawned[..] else { unreachable!() };
+		assert_eq!((x(&world, root), x(&world, copy)), (1.0, 6.0));
+	}
+
+	#[test]
+	fn contains_itself() {
+		let _engine = test_engine(|_| {});
+		let id = AssetId::new();
+		let mut p = World::new();
+		spawn_owner(&mut p, id);
+		source().insert(id, &p).unwrap();
+		let _view = ARef::<PrefabView>::loaded(id).unwrap();
+
+		let mut world = World::new();
+		let mut tick = Tick::new();
+		let owner = spawn_owner(&mut world, id);
+		for _ in 0..3 {
+			tick.tick(&mut world);
+		}
+		let [nested] = instance(&world, owner)[..] else {
+			panic!("expected one entity");
+		};
+		assert!(instance(&world, nested).is_empty());
+		assert_eq!(
+			world
+				.query_filtered::<Entity, With<PrefabMember>>()
+				.iter(&world)
+				.count(),
+			1
+		);
+	}
+}
@@ -7,6 +7,7 @@ use bevy_ecs::{
 	world::{EntityRef, EntityWorldMut, World},
 };
 use bevy_reflect::{
+	std_traits::ReflectDefault,
 	DynamicArray,
 	DynamicEnum,
 	DynamicList,
@@ -17,25 +18,38 @@ use bevy_reflect::{
 	DynamicTupleStruct,
 	DynamicVariant,
 	Map,
+	NamedField,
 	PartialReflect,
+	Reflect,
 	ReflectDeserialize,
 	ReflectFromReflect,
 	ReflectRef,
 	ReflectSerialize,
 	Set,
+	StructInfo,
 	TypeInfo,
 	TypeRegistration,
 	VariantInfo,
 };
 use bincode::{
-	de::Decoder,
+	de::{
+		read::{Reader, SliceReader},
+		Decoder,
+		DecoderImpl,
+	},
 	enc::Encoder,
 	error::{DecodeError, EncodeError},
 	serde::Compat,
 	Decode,
 	Encode,
 };
-use rad_core::asset::{map_dec_err, map_enc_err, Uuid};
+use rad_core::asset::{
+	aref::{ReflectAssetRef, UntypedAssetId},
+	map_dec_err,
+	map_enc_err,
+	Uuid,
+};
+use rustc_hash::FxHashMap;
 use serde::{
 	de::{DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor},
 	Deserializer,
@@ -46,30 +60,166 @@ use crate::{ty_reg, uuid_to_ty, ReflectRadComponent};
 #[derive(Copy, Clone, Component)]
 pub struct DoNotSerialize;
 
-pub fn serialize_entity(mut into: &mut dyn io::Write, world: &World, en: EntityRef) -> Result<(), io::Error> {
-	if en.contains::<DoNotSerialize>() {
-		return Ok(());
+/// The file-local ids entities are saved with, which `Entity` fields are written as. Entities are numbered in index
+/// order, so saving the same world twice gives the same ids.
+pub(crate) struct EntityMap {
+	entities: Vec<Entity>,
+	ids: FxHashMap<Entity, u32>,
+}
+
+impl EntityMap {
+	pub fn new(world: &World) -> Self {
+		let mut entities: Vec<_> = world
+			.iter_entities()
+			.filter(|x| !x.contains::<DoNotSerialize>())
+			.map(|x| x.id())
+			.collect();
+		entities.sort_unstable_by_key(|x| x.index());
+		Self::from_entities(entities)
+	}
+
+	/// Number `entities` in the order given.
+	pub fn from_entities(entities: Vec<Entity>) -> Self {
+		let ids = entities.iter().enumerate().map(|(i, &x)| (x, i as u32)).collect();
+		Self { entities, ids }
+	}
+
+	/// The entities to save, in id order.
+	pub fn entities(&self) -> &[Entity] { &self.entities }
+
+	/// The id of `entity`, or `None` if it is dead or not saved.
+	pub fn get(&self, entity: Entity) -> Option<u32> { self.ids.get(&entity).copied() }
+
+	/// Whether `value` is kept when saving a list, set, or map key, which is anything but an `Entity` that is not
+	/// saved.
+	pub fn is_saved(&self, value: &dyn PartialReflect) -> bool {
+		value
+			.try_downcast_ref::<Entity>()
+			.is_none_or(|&x| self.ids.contains_key(&x))
+	}
+}
+
+/// How `Entity` fields are decoded.
+#[derive(Copy, Clone)]
+enum EntityIds<'a> {
+	/// File-local ids, indexing the entities spawned for the file.
+	Local(&'a [Entity]),
+	/// Raw entity indices, from before entities were remapped.
+	Raw,
+}
+
+/// How the fields of structs are laid out.
+#[derive(Copy, Clone)]
+enum FieldLayout {
+	/// Every field is written with its name and length, so fields can be added, removed, reordered, or renamed
+	/// without breaking saved worlds.
+	Tagged,
+	/// Fields are written in declaration order, from before version 2.
+	Positional,
+}
+
+/// The old names of a struct field, so values saved with them still load. `RadComponent`s set this with
+/// `#[renamed_from("old_name", ...)]` on the field, other types with `#[reflect(@RenamedFrom(&["old_name", ...]))]`.
+#[derive(Copy, Clone, Debug, Reflect)]
+#[reflect(opaque)]
+pub struct RenamedFrom(pub &'static [&'static str]);
+
+/// The field of a struct called `name`, or that used to be called `name`.
+pub(crate) fn find_field<'a>(info: &'a StructInfo, name: &str) -> Option<&'a NamedField> {
+	info.field(name).or_else(|| {
+		info.iter()
+			.find(|x| x.get_attribute::<RenamedFrom>().is_some_and(|x| x.0.contains(&name)))
+	})
+}
+
+/// Convert a value built from saved data into the concrete type of `reg`. Fields missing from `value` are filled in
+/// by `FromReflect` if they are marked `#[reflect(default)]`, or else from the `Default` of the type if it registers
+/// `ReflectDefault`.
+pub(crate) fn from_dynamic(reg: &TypeRegistration, value: &dyn PartialReflect) -> Result<Box<dyn Reflect>, io::Error> {
+	let from = reg.data::<ReflectFromReflect>();
+	if let Some(x) = from.and_then(|x| x.from_reflect(value)) {
+		return Ok(x);
+	}
+	if let Some(x) = reg.data::<ReflectDefault>() {
+		let mut out = x.default();
+		out.try_apply(value)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+		return Ok(out);
 	}
 
+	let path = reg.type_info().type_path();
+	Err(io::Error::new(
+		io::ErrorKind::InvalidData,
+		match from {
+			Some(_) => format!("value (`{path}`) is missing fields, and has no default"),
+			None => format!("component (`{path}`) not unreflectable"),
+		},
+	))
+}
+
+/// Save the entities of a world. `Entity` fields that refer to entities that are not saved are written as null, and
+/// are loaded as `Entity::PLACEHOLDER`. Lists and sets leave them out instead, as do maps for their keys, so the
+/// `Children` of an entity don't pick up placeholders for runtime entities such as prefab instances. Arrays have a
+/// fixed length, so they keep the nulls.
+pub fn serialize_world(into: &mut dyn io::Write, world: &World) -> Result<(), io::Error> {
+	write_world(into, world, FieldLayout::Tagged)
+}
+
+fn write_world(mut into: &mut dyn io::Write, world: &World, layout: FieldLayout) -> Result<(), io::Error> {
 	let c = bincode::config::standard();
-	bincode::encode_into_std_write(en.id().index(), &mut into, c).map_err(map_enc_err)?;
-	let count = en.archetype().component_count() as u32;
+	let entities = EntityMap::new(world);
+	let count = entities.entities().len() as u32;
 	bincode::encode_into_std_write(count, &mut into, c).map_err(map_enc_err)?;
 
-	for comp in en.archetype().components() {
-		let info = world.components().get_info(comp).unwrap();
-		serialize_component(&mut into, en, info)?;
+	for &en in entities.entities() {
+		serialize_entity(&mut into, world, world.entity(en), &entities, layout)?;
 	}
 
 	Ok(())
 }
 
-fn serialize_component(mut into: &mut dyn io::Write, en: EntityRef, info: &ComponentInfo) -> Result<(), io::Error> {
+fn serialize_entity(
+	mut into: &mut dyn io::Write, world: &World, en: EntityRef, entities: &EntityMap, layout: FieldLayout,
+) -> Result<(), io::Error> {
 	let c = bincode::config::standard();
+	// Sorted like the text form, so converting between the two gives the same bytes.
+	let mut comps = saved_components(world, en)
+		.map(|info| reflect_component(en, info))
+		.collect::<Result<Vec<_>, _>>()?;
+	comps.sort_unstable_by_key(|&(uuid, _)| uuid);
+	bincode::encode_into_std_write(comps.len() as u32, &mut into, c).map_err(map_enc_err)?;
+
+	for (uuid, comp) in comps {
+		let enc = ComponentEncoder {
+			uuid,
+			comp: comp.as_partial_reflect(),
+			entities,
+			layout,
+		};
+		bincode::encode_into_std_write(enc, &mut into, c).map_err(map_enc_err)?;
+	}
+
+	Ok(())
+}
 
+/// The components of an entity that are saved. Only `RadComponent`s are, anything else is runtime state.
+pub(crate) fn saved_components<'a>(world: &'a World, en: EntityRef<'a>) -> impl Iterator<Item = &'a ComponentInfo> {
+	world.archetypes()[en.location().archetype_id]
+		.components()
+		.map(|comp| world.components().get_info(comp).unwrap())
+		.filter(|info| {
+			info.type_id()
+				.and_then(|ty| ty_reg().get_type_data::<ReflectRadComponent>(ty))
+				.is_some()
+		})
+}
+
+/// Reflect a component of an entity, along with its UUID.
+pub(crate) fn reflect_component<'a>(
+	en: EntityRef<'a>, info: &ComponentInfo,
+) -> Result<(Uuid, &'a dyn Reflect), io::Error> {
 	let Some(ty) = info.type_id() else {
 		panic!("component (`{}`) not reflectable", info.name());
-		return Ok(());
 	};
 	let reg = ty_reg().get(ty).ok_or_else(|| {
 		io::Error::new(
@@ -80,7 +230,6 @@ fn serialize_component(mut into: &mut dyn io::Write, en: EntityRef, info: &Compo
 
 	let Some(ref_rad) = reg.data::<ReflectRadComponent>() else {
 		panic!("component (`{}`) not reflectable", info.name());
-		return Ok(());
 	};
 	let refl = reg
 		.data::<ReflectComponent>()
@@ -94,131 +243,265 @@ fn serialize_component(mut into: &mut dyn io::Write, en: EntityRef, info: &Compo
 		.unwrap();
 	let uuid = (ref_rad.get_func)(refl).unwrap().uuid_dyn();
 
-	bincode::encode_into_std_write(ComponentEncoder { uuid, comp: refl }, &mut into, c).map_err(map_enc_err)?;
+	Ok((uuid, refl))
+}
 
-	Ok(())
+/// Find the registration of the component with `uuid`.
+pub(crate) fn component_registration(
+	uuid: Uuid,
+) -> Result<(&'static TypeRegistration, &'static ReflectComponent), io::Error> {
+	let id = uuid_to_ty(uuid).ok_or_else(|| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("unknown component UUID (`{uuid}`) not registered"),
+		)
+	})?;
+	let reg = ty_reg().get(id).unwrap();
+	let refl = reg.data::<ReflectComponent>().ok_or_else(|| {
+		io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!("component (`{}`) not reflectable", reg.type_info().type_path()),
+		)
+	})?;
+
+	Ok((reg, refl))
 }
 
-pub fn deserialize_entity(mut from: &mut dyn io::Read, world: &mut World) -> Result<(), io::Error> {
-	let c = bincode::config::standard();
-	let id = bincode::decode_from_std_read(&mut from, c).map_err(map_dec_err)?;
-	#[allow(deprecated)]
-	let mut en = world.get_or_spawn(bevy_ecs::entity::Entity::from_raw(id)).unwrap();
-	let count: u32 = bincode::decode_from_std_read(&mut from, c).map_err(map_dec_err)?;
+/// Find the assets referenced by the components of an entity.
+pub fn entity_dependencies(world: &World, en: EntityRef, out: &mut Vec<(UntypedAssetId, Uuid)>) {
+	if en.contains::<DoNotSerialize>() {
+		return;
+	}
+
+	for comp in en.archetype().components() {
+		let info = world.components().get_info(comp).unwrap();
+		let Some(refl) = info
+			.type_id()
+			.and_then(|ty| ty_reg().get_type_data::<ReflectComponent>(ty))
+			.and_then(|x| x.reflect(en))
+		else {
+			continue;
+		};
+		find_asset_refs(refl.as_partial_reflect(), out);
+	}
+}
+
+fn find_asset_refs(value: &dyn PartialReflect, out: &mut Vec<(UntypedAssetId, Uuid)>) {
+	match value.reflect_ref() {
+		ReflectRef::Struct(x) => x.iter_fields().for_each(|x| find_asset_refs(x, out)),
+		ReflectRef::TupleStruct(x) => x.iter_fields().for_each(|x| find_asset_refs(x, out)),
+		ReflectRef::Tuple(x) => x.iter_fields().for_each(|x| find_asset_refs(x, out)),
+		ReflectRef::List(x) => x.iter().for_each(|x| find_asset_refs(x, out)),
+		ReflectRef::Array(x) => x.iter().for_each(|x| find_asset_refs(x, out)),
+		ReflectRef::Map(x) => x.iter().for_each(|(k, v)| {
+			find_asset_refs(k, out);
+			find_asset_refs(v, out);
+		}),
+		ReflectRef::Set(x) => x.iter().for_each(|x| find_asset_refs(x, out)),
+		ReflectRef::Enum(x) => x.iter_fields().for_each(|x| find_asset_refs(x.value(), out)),
+		ReflectRef::Opaque(x) => {
+			let Some(x) = x.try_as_reflect() else {
+				return;
+			};
+			if let Some(asset) = ty_reg()
+				.get_type_data::<ReflectAssetRef>(x.as_any().type_id())
+				.and_then(|r| r.get(x))
+			{
+				out.push(asset.asset());
+			}
+		},
+	}
+}
 
+/// Load entities saved with `serialize_world` into `world`. Fresh entities are spawned for them, so `world` does not
+/// have to be empty. Returns the spawned entities, in the order they were saved.
+pub fn deserialize_world(from: &mut dyn io::Read, world: &mut World) -> Result<Vec<Entity>, io::Error> {
+	read_world(from, world, FieldLayout::Tagged)
+}
+
+fn read_world(from: &mut dyn io::Read, world: &mut World, layout: FieldLayout) -> Result<Vec<Entity>, io::Error> {
+	let mut decoder = DecoderImpl::new(IoReader { from }, bincode::config::standard());
+	let count: u32 = Decode::decode(&mut decoder).map_err(map_dec_err)?;
+	let entities: Vec<_> = (0..count).map(|_| world.spawn_empty().id()).collect();
+
+	for &en in entities.iter() {
+		let en = &mut world.entity_mut(en);
+		deserialize_entity(&mut decoder, en, EntityIds::Local(&entities), layout)?;
+	}
+
+	Ok(entities)
+}
+
+/// Migrate a world saved before entities were remapped, where every entity was written with its index and `Entity`
+/// fields held raw indices.
+pub(crate) fn migrate_v0(data: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+	let mut decoder = DecoderImpl::new(SliceReader::new(&data), bincode::config::standard());
+	let count: u32 = Decode::decode(&mut decoder).map_err(map_dec_err)?;
+	let mut world = World::new();
 	for _ in 0..count {
-		deserialize_component(&mut from, &mut en)?;
+		// `DoNotSerialize` entities were counted, but not written.
+		if decoder.reader().peek_read(1).is_none() {
+			break;
+		}
+
+		let id = Decode::decode(&mut decoder).map_err(map_dec_err)?;
+		#[allow(deprecated)]
+		let mut en = world.get_or_spawn(Entity::from_raw(id)).unwrap();
+		deserialize_entity(&mut decoder, &mut en, EntityIds::Raw, FieldLayout::Positional)?;
+	}
+
+	let mut out = Vec::new();
+	write_world(&mut out, &world, FieldLayout::Positional)?;
+	Ok(out)
+}
+
+/// Migrate a world saved before struct fields were tagged with their names.
+pub(crate) fn migrate_v1(data: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+	let mut world = World::new();
+	read_world(&mut data.as_slice(), &mut world, FieldLayout::Positional)?;
+
+	let mut out = Vec::new();
+	write_world(&mut out, &world, FieldLayout::Tagged)?;
+	Ok(out)
+}
+
+fn deserialize_entity(
+	decoder: &mut impl Decoder, en: &mut EntityWorldMut, entities: EntityIds, layout: FieldLayout,
+) -> Result<(), io::Error> {
+	let count: u32 = Decode::decode(decoder).map_err(map_dec_err)?;
+
+	for _ in 0..count {
+		deserialize_component(decoder, en, entities, layout)?;
 	}
 
 	Ok(())
 }
 
-fn deserialize_component(mut from: &mut dyn io::Read, en: &mut EntityWorldMut) -> Result<(), io::Error> {
-	let c = bincode::config::standard();
-	let comp: CompenentDecoder = bincode::decode_from_std_read(&mut from, c).map_err(map_dec_err)?;
-	comp.refl.insert(en, comp.obj.as_partial_reflect(), ty_reg());
+fn deserialize_component(
+	decoder: &mut impl Decoder, en: &mut EntityWorldMut, entities: EntityIds, layout: FieldLayout,
+) -> Result<(), io::Error> {
+	let uuid = Uuid::from_bytes(Decode::decode(decoder).map_err(map_dec_err)?);
+	let (reg, refl) = component_registration(uuid)?;
+	let obj = DynDecoder { reg, entities, layout }
+		.decode(decoder)
+		.map_err(map_dec_err)?;
+	refl.insert(en, obj.as_partial_reflect(), ty_reg());
 
 	Ok(())
 }
 
+/// Lets a single decoder read a whole world from an `io::Read`.
+struct IoReader<'a> {
+	from: &'a mut dyn io::Read,
+}
+
+impl Reader for IoReader<'_> {
+	fn read(&mut self, bytes: &mut [u8]) -> Result<(), DecodeError> {
+		self.from.read_exact(bytes).map_err(|inner| DecodeError::Io {
+			inner,
+			additional: bytes.len(),
+		})
+	}
+}
+
 struct ComponentEncoder<'a> {
 	uuid: Uuid,
 	comp: &'a dyn PartialReflect,
+	entities: &'a EntityMap,
+	layout: FieldLayout,
 }
 
 impl Encode for ComponentEncoder<'_> {
 	fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
 		Encode::encode(self.uuid.as_bytes(), encoder)?;
-		Encode::encode(&DynEncoder { val: self.comp }, encoder)?;
+		Encode::encode(
+			&DynEncoder {
+				val: self.comp,
+				entities: self.entities,
+				layout: self.layout,
+			},
+			encoder,
+		)?;
 
 		Ok(())
 	}
 }
 
-struct CompenentDecoder {
-	refl: &'static ReflectComponent,
-	obj: Box<dyn PartialReflect>,
-}
-
-impl Decode for CompenentDecoder {
-	fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
-		let uuid = Uuid::from_bytes(Decode::decode(decoder)?);
-		let id = uuid_to_ty(uuid).ok_or_else(|| DecodeError::Io {
-			inner: io::Error::new(
-				io::ErrorKind::InvalidData,
-				format!("unknown component UUID (`{uuid}`) not registered"),
-			),
-			additional: 0,
-		})?;
-		let reg = ty_reg().get(id).unwrap();
-		let refl = reg.data::<ReflectComponent>().ok_or_else(|| DecodeError::Io {
-			inner: io::Error::new(
-				io::ErrorKind::InvalidData,
-				format!("component (`{}`) not reflectable", reg.type_info().type_path()),
-			),
-			additional: 0,
-		})?;
-		let obj = DynDecoder { reg }.decode(decoder)?;
-
-		Ok(Self { refl, obj })
-	}
-}
-
 struct DynEncoder<'a> {
 	val: &'a dyn PartialReflect,
+	entities: &'a EntityMap,
+	layout: FieldLayout,
 }
 
 impl Encode for DynEncoder<'_> {
 	fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
-		// TODO: this is incorrect if the entity points to a dead one.
 		if let Some::<&Entity>(e) = self.val.try_downcast_ref() {
-			return Encode::encode(&e.index(), encoder);
+			return Encode::encode(&self.entities.get(*e), encoder);
 		}
 
 		match self.val.reflect_ref() {
-			ReflectRef::Struct(x) => {
-				for x in x.iter_fields() {
-					Encode::encode(&DynEncoder { val: x }, encoder)?;
-				}
+			ReflectRef::Struct(x) => match self.layout {
+				FieldLayout::Tagged => {
+					Encode::encode(&x.field_len(), encoder)?;
+					for (i, val) in x.iter_fields().enumerate() {
+						Encode::encode(x.name_at(i).unwrap(), encoder)?;
+						let data = bincode::encode_to_vec(DynEncoder { val, ..*self }, bincode::config::standard())?;
+						Encode::encode(&data, encoder)?;
+					}
+				},
+				FieldLayout::Positional => {
+					for x in x.iter_fields() {
+						Encode::encode(&DynEncoder { val: x, ..*self }, encoder)?;
+					}
+				},
 			},
 			ReflectRef::TupleStruct(x) => {
 				for x in x.iter_fields() {
-					Encode::encode(&DynEncoder { val: x }, encoder)?;
+					Encode::encode(&DynEncoder { val: x, ..*self }, encoder)?;
 				}
 			},
 			ReflectRef::Tuple(x) => {
 				for x in x.iter_fields() {
-					Encode::encode(&DynEncoder { val: x }, encoder)?;
+					Encode::encode(&DynEncoder { val: x, ..*self }, encoder)?;
 				}
 			},
 			ReflectRef::List(x) => {
-				Encode::encode(&x.len(), encoder)?;
-				for x in x.iter() {
-					Encode::encode(&DynEncoder { val: x }, encoder)?;
+				let saved = |x: &&dyn PartialReflect| self.entities.is_saved(*x);
+				Encode::encode(&x.iter().filter(saved).count(), encoder)?;
+				for x in x.iter().filter(saved) {
+					Encode::encode(&DynEncoder { val: x, ..*self }, encoder)?;
 				}
 			},
 			ReflectRef::Array(x) => {
 				for x in x.iter() {
-					Encode::encode(&DynEncoder { val: x }, encoder)?;
+					Encode::encode(&DynEncoder { val: x, ..*self }, encoder)?;
 				}
 			},
 			ReflectRef::Map(x) => {
-				Encode::encode(&x.len(), encoder)?;
-				for (k, v) in x.iter() {
-					Encode::encode(&DynEncoder { val: k }, encoder)?;
-					Encode::encode(&DynEncoder { val: v }, encoder)?;
+				let saved = |(k, _): &(&dyn PartialReflect, &dyn PartialReflect)| self.entities.is_saved(*k);
+				Encode::encode(&x.iter().filter(saved).count(), encoder)?;
+				for (k, v) in x.iter().filter(saved) {
+					Encode::encode(&DynEncoder { val: k, ..*self }, encoder)?;
+					Encode::encode(&DynEncoder { val: v, ..*self }, encoder)?;
 				}
 			},
 			ReflectRef::Set(x) => {
-				Encode::encode(&x.len(), encoder)?;
-				for x in x.iter() {
-					Encode::encode(&DynEncoder { val: x }, encoder)?;
+				let saved = |x: &&dyn PartialReflect| self.entities.is_saved(*x);
+				Encode::encode(&x.iter().filter(saved).count(), encoder)?;
+				for x in x.iter().filter(saved) {
+					Encode::encode(&DynEncoder { val: x, ..*self }, encoder)?;
 				}
 			},
 			ReflectRef::Enum(x) => {
 				Encode::encode(&x.variant_index(), encoder)?;
 				for x in x.iter_fields() {
-					Encode::encode(&DynEncoder { val: x.value() }, encoder)?;
+					Encode::encode(
+						&DynEncoder {
+							val: x.value(),
+							..*self
+						},
+						encoder,
+					)?;
 				}
 			},
 			ReflectRef::Opaque(_) => {
@@ -238,111 +521,75 @@ impl Encode for DynEncoder<'_> {
 	}
 }
 
-struct DynDecoder {
+struct DynDecoder<'a> {
 	reg: &'static TypeRegistration,
+	entities: EntityIds<'a>,
+	layout: FieldLayout,
 }
 
-impl DynDecoder {
+impl DynDecoder<'_> {
 	fn decode(&self, decoder: &mut impl Decoder) -> Result<Box<dyn PartialReflect>, DecodeError> {
 		if self.reg.type_info().is::<Entity>() {
-			return Ok(Box::new(Entity::from_raw(Decode::decode(decoder)?)));
+			return Ok(Box::new(self.decode_entity(decoder)?));
 		}
 
 		Ok(match self.reg.type_info() {
+			TypeInfo::Struct(x) if matches!(self.layout, FieldLayout::Tagged) => {
+				let mut s = DynamicStruct::default();
+				let len: usize = Decode::decode(decoder)?;
+				for _ in 0..len {
+					let name: String = Decode::decode(decoder)?;
+					let data: Vec<u8> = Decode::decode(decoder)?;
+					// Fields that were removed are skipped, and missing fields are filled in by `from_dynamic`.
+					let Some(field) = find_field(x, &name) else {
+						continue;
+					};
+					let reg = ty_reg().get(field.ty().id()).unwrap();
+					let mut decoder = DecoderImpl::new(SliceReader::new(&data), bincode::config::standard());
+					s.insert_boxed(field.name(), Self { reg, ..*self }.decode(&mut decoder)?);
+				}
+				self.finish(&s)?
+			},
 			TypeInfo::Struct(x) => {
 				let mut s = DynamicStruct::default();
 				for i in 0..x.field_len() {
 					let reg = ty_reg().get(x.field_at(i).unwrap().ty().id()).unwrap();
-					s.insert_boxed(x.field_names()[i], Self { reg }.decode(decoder)?);
+					s.insert_boxed(x.field_names()[i], Self { reg, ..*self }.decode(decoder)?);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::TupleStruct(x) => {
 				let mut s = DynamicTupleStruct::default();
 				for i in 0..x.field_len() {
 					let reg = ty_reg().get(x.field_at(i).unwrap().ty().id()).unwrap();
-					s.insert_boxed(Self { reg }.decode(decoder)?);
+					s.insert_boxed(Self { reg, ..*self }.decode(decoder)?);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::Tuple(x) => {
 				let mut s = DynamicTuple::default();
 				for i in 0..x.field_len() {
 					let reg = ty_reg().get(x.field_at(i).unwrap().ty().id()).unwrap();
-					s.insert_boxed(Self { reg }.decode(decoder)?);
+					s.insert_boxed(Self { reg, ..*self }.decode(decoder)?);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::List(x) => {
 				let mut s = DynamicList::default();
 				let reg = ty_reg().get(x.item_ty().id()).unwrap();
 				let len: usize = Decode::decode(decoder)?;
 				for _ in 0..len {
-					s.push_box(Self { reg }.decode(decoder)?);
+					s.push_box(Self { reg, ..*self }.decode(decoder)?);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!(
-								"component (`{}`) not unreflectable ({:?})",
-								self.reg.type_info().type_path(),
-								self.reg.type_id()
-							),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::Array(x) => {
 				let mut s = Vec::with_capacity(x.capacity());
 				let reg = ty_reg().get(x.item_ty().id()).unwrap();
 				for _ in 0..x.capacity() {
-					s.push(Self { reg }.decode(decoder)?);
+					s.push(Self { reg, ..*self }.decode(decoder)?);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&DynamicArray::new(s.into_boxed_slice()))
-					.unwrap()
+				self.finish(&DynamicArray::new(s.into_boxed_slice()))?
 			},
 			TypeInfo::Map(x) => {
 				let mut s = DynamicMap::default();
@@ -350,40 +597,20 @@ impl DynDecoder {
 				let val_reg = ty_reg().get(x.value_ty().id()).unwrap();
 				let len: usize = Decode::decode(decoder)?;
 				for _ in 0..len {
-					let key = Self { reg: key_reg }.decode(decoder)?;
-					let val = Self { reg: val_reg }.decode(decoder)?;
+					let key = Self { reg: key_reg, ..*self }.decode(decoder)?;
+					let val = Self { reg: val_reg, ..*self }.decode(decoder)?;
 					s.insert_boxed(key, val);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::Set(x) => {
 				let mut s = DynamicSet::default();
-				let reg = ty_reg().get(x.ty().id()).unwrap();
+				let reg = ty_reg().get(x.value_ty().id()).unwrap();
 				let len: usize = Decode::decode(decoder)?;
 				for _ in 0..len {
-					s.insert_boxed(Self { reg }.decode(decoder)?);
+					s.insert_boxed(Self { reg, ..*self }.decode(decoder)?);
 				}
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::Enum(x) => {
 				let i: usize = Decode::decode(decoder)?;
@@ -392,7 +619,7 @@ impl DynDecoder {
 						let mut s = DynamicStruct::default();
 						for i in 0..x.field_len() {
 							let reg = ty_reg().get(x.field_at(i).unwrap().ty().id()).unwrap();
-							s.insert_boxed(x.field_names()[i], Self { reg }.decode(decoder)?);
+							s.insert_boxed(x.field_names()[i], Self { reg, ..*self }.decode(decoder)?);
 						}
 						DynamicVariant::Struct(s)
 					},
@@ -400,24 +627,14 @@ impl DynDecoder {
 						let mut s = DynamicTuple::default();
 						for i in 0..x.field_len() {
 							let reg = ty_reg().get(x.field_at(i).unwrap().ty().id()).unwrap();
-							s.insert_boxed(Self { reg }.decode(decoder)?);
+							s.insert_boxed(Self { reg, ..*self }.decode(decoder)?);
 						}
 						DynamicVariant::Tuple(s)
 					},
 					VariantInfo::Unit(_) => DynamicVariant::Unit,
 				};
 				let s = DynamicEnum::new_with_index(i, x.variant_names()[i], v);
-				self.reg
-					.data::<ReflectFromReflect>()
-					.ok_or_else(|| DecodeError::Io {
-						inner: io::Error::new(
-							io::ErrorKind::InvalidData,
-							format!("component (`{}`) not unreflectable", self.reg.type_info().type_path()),
-						),
-						additional: 0,
-					})?
-					.from_reflect(&s)
-					.unwrap()
+				self.finish(&s)?
 			},
 			TypeInfo::Opaque(_) => {
 				let de = ty_reg()
@@ -427,6 +644,28 @@ impl DynDecoder {
 			},
 		})
 	}
+
+	/// Build the concrete value of a decoded dynamic one.
+	fn finish(&self, value: &dyn PartialReflect) -> Result<Box<dyn Reflect>, DecodeError> {
+		from_dynamic(self.reg, value).map_err(|inner| DecodeError::Io { inner, additional: 0 })
+	}
+
+	fn decode_entity(&self, decoder: &mut impl Decoder) -> Result<Entity, DecodeError> {
+		let entities = match self.entities {
+			EntityIds::Local(x) => x,
+			EntityIds::Raw => return Ok(Entity::from_raw(Decode::decode(decoder)?)),
+		};
+		let Some(id) = Option::<u32>::decode(decoder)? else {
+			return Ok(Entity::PLACEHOLDER);
+		};
+		entities.get(id as usize).copied().ok_or_else(|| DecodeError::Io {
+			inner: io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("entity {id} out of range ({} entities)", entities.len()),
+			),
+			additional: 0,
+		})
+	}
 }
 
 use bincode::serde::DecodeError as SerdeDecodeError;
@@ -800,3 +1039,224 @@ impl<'de, 'a, DE: Decoder> VariantAccess<'de> for SerdeDecoder<'a, DE> {
 		Deserializer::deserialize_tuple(self, fields.len(), visitor)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashSet;
+
+	use super::*;
+	use crate::{test_engine, RadComponent, WorldBuilderExt};
+
+	#[derive(Clone, Debug, PartialEq, RadComponent)]
+	#[uuid("0f4e3b7a-9c1d-4e52-8a6b-3d2c1b0a9f87")]
+	struct Links {
+		target: Entity,
+		list: Vec<Entity>,
+		set: HashSet<Entity>,
+		pair: [Entity; 2],
+	}
+
+	/// `Renamed` as it was saved, before its fields were changed.
+	#[derive(Clone, Debug, PartialEq, RadComponent)]
+	#[uuid("5b8e2d41-7a3c-4f96-b1e0-2c9d8f4a6e13")]
+	struct Original {
+		count: u32,
+		label: String,
+		removed: f32,
+	}
+
+	#[derive(Clone, Debug, Default, PartialEq, RadComponent)]
+	#[uuid("5b8e2d41-7a3c-4f96-b1e0-2c9d8f4a6e13")]
+	#[reflect(Default)]
+	struct Renamed {
+		#[renamed_from("label")]
+		name: String,
+		added: u32,
+		count: u32,
+	}
+
+	fn original() -> Original {
+		Original {
+			count: 3,
+			label: "three".into(),
+			removed: 1.5,
+		}
+	}
+
+	fn links(target: Entity, list: &[Entity], pair: [Entity; 2]) -> Links {
+		Links {
+			target,
+			list: list.to_vec(),
+			set: list.iter().copied().collect(),
+			pair,
+		}
+	}
+
+	fn engine() -> rad_core::ScopedEngine {
+		test_engine(|e| {
+			e.component::<Links>();
+			e.component_dep_type::<HashSet<Entity>>();
+			e.component_dep_type::<[Entity; 2]>();
+		})
+	}
+
+	fn save(world: &World) -> Vec<u8> {
+		let mut data = Vec::new();
+		serialize_world(&mut data, world).unwrap();
+		data
+	}
+
+	#[test]
+	fn remap() {
+		let _engine = engine();
+		let mut world = World::new();
+		let junk = world.spawn_empty().id();
+		let a = world.spawn_empty().id();
+		let runtime = world.spawn(DoNotSerialize).id();
+		let dead = world.spawn_empty().id();
+		let b = world.spawn_empty().id();
+		world.entity_mut(a).insert(links(dead, &[], [a, a]));
+		world
+			.entity_mut(b)
+			.insert(links(a, &[a, runtime, dead, b], [runtime, a]));
+		world.despawn(junk);
+		world.despawn(dead);
+		let data = save(&world);
+
+		let text = crate::text::serialize_world(&world).unwrap();
+
+		// Loading doesn't touch entities that are already there.
+		let mut loaded = World::new();
+		let existing = loaded
+			.spawn(links(Entity::PLACEHOLDER, &[], [Entity::PLACEHOLDER; 2]))
+			.id();
+		let binary = deserialize_world(&mut data.as_slice(), &mut loaded).unwrap();
+		let text = crate::text::deserialize_world(&text, &mut loaded).unwrap();
+		for entities in [binary, text] {
+			let [a2, b2] = entities[..] else {
+				panic!("expected 2 entities, got {}", entities.len());
+			};
+			assert!(!entities.contains(&existing));
+			assert_eq!(
+				loaded.get::<Links>(a2),
+				Some(&links(Entity::PLACEHOLDER, &[], [a2, a2]))
+			);
+			assert_eq!(
+				loaded.get::<Links>(b2),
+				Some(&links(a2, &[a2, b2], [Entity::PLACEHOLDER, a2]))
+			);
+		}
+		assert_eq!(loaded.get::<Links>(existing).unwrap().target, Entity::PLACEHOLDER);
+	}
+
+	#[test]
+	fn out_of_range() {
+		let _engine = engine();
+		let mut world = World::new();
+		let a = world.spawn_empty().id();
+		let b = world.spawn_empty().id();
+		world.entity_mut(a).insert(links(b, &[], [a, b]));
+		let mut data = save(&world);
+
+		// Only load the first entity, which refers to the second.
+		data[0] = 1;
+		let err = deserialize_world(&mut data.as_slice(), &mut World::new()).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(err.to_string().contains("out of range"), "{err}");
+
+		let text = crate::text::serialize_world(&world)
+			.unwrap()
+			.replace("\"target\": 1", "\"target\": 5");
+		let err = crate::text::deserialize_world(&text, &mut World::new()).unwrap_err();
+		assert!(err.to_string().contains("no entity with id 5"), "{err}");
+	}
+
+	#[test]
+	fn migrate() {
+		let _engine = engine();
+		let c = bincode::config::standard();
+		let mut v0 = Vec::new();
+		let mut put = |x: &dyn Fn() -> Vec<u8>| v0.extend(x());
+		put(&|| bincode::encode_to_vec(2u32, c).unwrap());
+		for (id, target) in [(3u32, 7u32), (7, 3)] {
+			put(&|| bincode::encode_to_vec((id, 1u32), c).unwrap());
+			put(&|| Links::uuid().as_bytes().to_vec());
+			put(&|| bincode::encode_to_vec((target, vec![id, target], vec![target], [id, id]), c).unwrap());
+		}
+
+		let data = migrate_v1(migrate_v0(v0).unwrap()).unwrap();
+		let mut world = World::new();
+		let entities = deserialize_world(&mut data.as_slice(), &mut world).unwrap();
+		let [a, b] = entities[..] else {
+			panic!("expected 2 entities, got {}", entities.len());
+		};
+		let mut expected = links(b, &[a, b], [a, a]);
+		expected.set = HashSet::from([b]);
+		assert_eq!(world.get::<Links>(a), Some(&expected));
+	}
+
+	#[test]
+	fn changed_fields() {
+		let (data, text) = {
+			let _engine = test_engine(|e| {
+				e.component::<Original>();
+			});
+			let mut world = World::new();
+			world.spawn(original());
+			(save(&world), crate::text::serialize_world(&world).unwrap())
+		};
+
+		// `removed` is skipped, `added` is defaulted, `label` is read into `name`, and `count` is found though it
+		// moved.
+		let _engine = test_engine(|e| {
+			e.component::<Renamed>();
+		});
+		let expected = Renamed {
+			name: "three".into(),
+			added: 0,
+			count: 3,
+		};
+		let mut world = World::new();
+		let binary = deserialize_world(&mut data.as_slice(), &mut world).unwrap();
+		let text = crate::text::deserialize_world(&text, &mut world).unwrap();
+		for e in binary.into_iter().chain(text) {
+			assert_eq!(world.get::<Renamed>(e), Some(&expected));
+		}
+	}
+
+	#[test]
+	fn missing_fields_without_default() {
+		let data = {
+			let _engine = test_engine(|e| {
+				e.component::<Renamed>();
+			});
+			let mut world = World::new();
+			world.spawn(Renamed::default());
+			save(&world)
+		};
+
+		let _engine = test_engine(|e| {
+			e.component::<Original>();
+		});
+		let err = deserialize_world(&mut data.as_slice(), &mut World::new()).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		assert!(err.to_string().contains("missing fields"), "{err}");
+	}
+
+	#[test]
+	fn migrate_positional() {
+		let _engine = test_engine(|e| {
+			e.component::<Original>();
+		});
+		let mut world = World::new();
+		world.spawn(original());
+		let mut v1 = Vec::new();
+		write_world(&mut v1, &world, FieldLayout::Positional).unwrap();
+
+		let data = migrate_v1(v1).unwrap();
+		assert_eq!(data, save(&world));
+		let mut world = World::new();
+		let entities = deserialize_world(&mut data.as_slice(), &mut world).unwrap();
+		assert_eq!(world.get::<Original>(entities[0]), Some(&original()));
+	}
+}
@@ -0,0 +1,383 @@
+//! A JSON form of worlds, to keep them diffable in version control. It holds the same data as the binary asset
+//! format, so worlds can be converted between the two without loss.
+//!
+//! ```json
+//! {
+//!   "entities": [
+//!     {
+//!       "id": 0,
+//!       "components": {
+//!         "<component uuid>": { ... }
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Components are keyed by their UUID, and written with the reflection serializer. Entity ids are local to the file,
+//! and `Entity` fields are written as the id of the entity they refer to, or null if it is not saved. Lists and sets of
+//! entities leave out the ones that are not saved, as do maps keyed by entities. Entities and components are sorted so
+//! saving the same world twice gives the same text.
+
+use std::{any::TypeId, io};
+
+use bevy_ecs::{
+	entity::Entity,
+	world::{EntityRef, World},
+};
+use bevy_reflect::{
+	serde::{
+		ReflectDeserializerProcessor,
+		ReflectSerializerProcessor,
+		TypedReflectDeserializer,
+		TypedReflectSerializer,
+	},
+	PartialReflect,
+	Reflect,
+	ReflectRef,
+	TypeInfo,
+	TypeRegistration,
+	TypeRegistry,
+};
+use rad_core::asset::Uuid;
+use rustc_hash::FxHashMap;
+use serde::{
+	de::{self, DeserializeSeed},
+	ser::{self, SerializeMap, SerializeStruct},
+	Deserialize,
+	Deserializer,
+	Serialize,
+	Serializer,
+};
+use serde_json::Value;
+
+use crate::{
+	serde::{component_registration, find_field, from_dynamic, reflect_component, saved_components, EntityMap},
+	ty_reg,
+};
+
+pub fn serialize_world(world: &World) -> Result<String, io::Error> {
+	let entities = EntityMap::new(world);
+	Ok(serde_json::to_string_pretty(&WorldSer {
+		world,
+		entities: &entities,
+	})?)
+}
+
+/// Load a world written with `serialize_world` into `world`, spawning fresh entities for it. Returns the spawned
+/// entities, in the order they were written.
+pub fn deserialize_world(text: &str, world: &mut World) -> Result<Vec<Entity>, io::Error> {
+	let root: Value = serde_json::from_str(text)?;
+	let entities = root
+		.get("entities")
+		.and_then(Value::as_array)
+		.ok_or_else(|| invalid("world has no `entities` array"))?;
+
+	let mut ids = EntityIds::default();
+	let mut spawned = Vec::with_capacity(entities.len());
+	for en in entities {
+		let id = en
+			.get("id")
+			.and_then(Value::as_u64)
+			.and_then(|x| u32::try_from(x).ok())
+			.ok_or_else(|| invalid("entity has no valid `id`"))?;
+		let e = world.spawn_empty().id();
+		if ids.map.insert(id, e).is_some() {
+			return Err(invalid(format!("duplicate entity id {id}")));
+		}
+		spawned.push(e);
+	}
+
+	for (en, &e) in entities.iter().zip(spawned.iter()) {
+		deserialize_entity(en, &mut ids, world, e)?;
+	}
+
+	Ok(spawned)
+}
+
+/// Write a single reflected value, with `Entity` fields written as their ids in `entities`.
+pub(crate) fn serialize_value(value: &dyn PartialReflect, entities: &EntityMap) -> Result<String, io::Error> {
+	Ok(serde_json::to_string(&TypedReflectSerializer::with_processor(
+		value,
+		ty_reg(),
+		entities,
+	))?)
+}
+
+/// Read a value written with `serialize_value`, where ids refer to `entities`.
+pub(crate) fn deserialize_value(
+	reg: &TypeRegistration, text: &str, entities: &[Entity],
+) -> Result<Box<dyn PartialReflect>, io::Error> {
+	let mut ids = EntityIds {
+		map: entities.iter().enumerate().map(|(i, &e)| (i as u32, e)).collect(),
+	};
+	let mut de = serde_json::Deserializer::from_str(text);
+	TypedReflectDeserializer::with_processor(reg, ty_reg(), &mut ids)
+		.deserialize(&mut de)
+		.map_err(|e| invalid(format!("value (`{}`): {e}", reg.type_info().type_path())))
+}
+
+fn deserialize_entity(en: &Value, ids: &mut EntityIds, world: &mut World, e: Entity) -> Result<(), io::Error> {
+	let comps = en
+		.get("components")
+		.and_then(Value::as_object)
+		.ok_or_else(|| invalid(format!("entity {} has no `components` object", en["id"])))?;
+
+	let mut e = world.entity_mut(e);
+	for (uuid, value) in comps {
+		let uuid = Uuid::parse_str(uuid).map_err(|e| invalid(format!("invalid component UUID (`{uuid}`): {e}")))?;
+		let (reg, refl) = component_registration(uuid)?;
+		let mut value = value.clone();
+		match_fields(&mut value, reg.type_info());
+		let obj = TypedReflectDeserializer::with_processor(reg, ty_reg(), ids)
+			.deserialize(&value)
+			.map_err(|e| invalid(format!("component (`{}`): {e}", reg.type_info().type_path())))?;
+		let obj = from_dynamic(reg, obj.as_partial_reflect())?;
+		refl.insert(&mut e, obj.as_partial_reflect(), ty_reg());
+	}
+
+	Ok(())
+}
+
+/// Rename struct fields that were saved under an old name, and drop fields that no longer exist. Missing fields are
+/// filled in by `from_dynamic`.
+fn match_fields(value: &mut Value, info: &TypeInfo) {
+	let field_info = |ty: TypeId| ty_reg().get(ty).map(|x| x.type_info());
+	match (info, value) {
+		(TypeInfo::Struct(info), Value::Object(fields)) => {
+			for (name, mut value) in std::mem::take(fields) {
+				let Some(field) = find_field(info, &name) else {
+					continue;
+				};
+				if let Some(info) = field_info(field.type_id()) {
+					match_fields(&mut value, info);
+				}
+				fields.insert(field.name().to_string(), value);
+			}
+		},
+		// Single field tuple structs are written as their field.
+		(TypeInfo::TupleStruct(info), value) if info.field_len() == 1 => {
+			if let Some(info) = field_info(info.field_at(0).unwrap().type_id()) {
+				match_fields(value, info);
+			}
+		},
+		(TypeInfo::TupleStruct(info), Value::Array(items)) => {
+			for (field, value) in info.iter().zip(items.iter_mut()) {
+				if let Some(info) = field_info(field.type_id()) {
+					match_fields(value, info);
+				}
+			}
+		},
+		(TypeInfo::Tuple(info), Value::Array(items)) => {
+			for (field, value) in info.iter().zip(items.iter_mut()) {
+				if let Some(info) = field_info(field.type_id()) {
+					match_fields(value, info);
+				}
+			}
+		},
+		(TypeInfo::List(info), Value::Array(items)) => {
+			if let Some(info) = field_info(info.item_ty().id()) {
+				items.iter_mut().for_each(|x| match_fields(x, info));
+			}
+		},
+		(TypeInfo::Array(info), Value::Array(items)) => {
+			if let Some(info) = field_info(info.item_ty().id()) {
+				items.iter_mut().for_each(|x| match_fields(x, info));
+			}
+		},
+		_ => {},
+	}
+}
+
+fn invalid(msg: impl Into<String>) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg.into()) }
+
+struct WorldSer<'a> {
+	world: &'a World,
+	entities: &'a EntityMap,
+}
+
+impl Serialize for WorldSer<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let entities: Vec<_> = self
+			.entities
+			.entities()
+			.iter()
+			.map(|&en| EntitySer {
+				world: self.world,
+				en: self.world.entity(en),
+				entities: self.entities,
+			})
+			.collect();
+
+		let mut s = serializer.serialize_struct("World", 1)?;
+		s.serialize_field("entities", &entities)?;
+		s.end()
+	}
+}
+
+struct EntitySer<'a> {
+	world: &'a World,
+	en: EntityRef<'a>,
+	entities: &'a EntityMap,
+}
+
+impl Serialize for EntitySer<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut comps = saved_components(self.world, self.en)
+			.map(|info| reflect_component(self.en, info))
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(ser::Error::custom)?;
+		comps.sort_unstable_by_key(|&(uuid, _)| uuid);
+
+		let mut s = serializer.serialize_struct("Entity", 2)?;
+		s.serialize_field("id", &self.entities.get(self.en.id()).unwrap())?;
+		s.serialize_field(
+			"components",
+			&ComponentsSer {
+				comps,
+				entities: self.entities,
+			},
+		)?;
+		s.end()
+	}
+}
+
+struct ComponentsSer<'a> {
+	comps: Vec<(Uuid, &'a dyn Reflect)>,
+	entities: &'a EntityMap,
+}
+
+impl Serialize for ComponentsSer<'_> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut s = serializer.serialize_map(Some(self.comps.len()))?;
+		for &(uuid, comp) in self.comps.iter() {
+			s.serialize_entry(
+				&uuid,
+				&TypedReflectSerializer::with_processor(comp.as_partial_reflect(), ty_reg(), self.entities),
+			)?;
+		}
+		s.end()
+	}
+}
+
+impl ReflectSerializerProcessor for EntityMap {
+	fn try_serialize<S: Serializer>(
+		&self, value: &dyn PartialReflect, _: &TypeRegistry, serializer: S,
+	) -> Result<Result<S::Ok, S>, S::Error> {
+		if let Some(&e) = value.try_downcast_ref::<Entity>() {
+			return self.get(e).serialize(serializer).map(Ok);
+		}
+
+		// Only collections of entities can have unsaved elements.
+		let ids = |x: &dyn PartialReflect| x.try_downcast_ref::<Entity>().and_then(|&e| self.get(e));
+		match value.reflect_ref() {
+			ReflectRef::List(x) if x.iter().any(|x| !self.is_saved(x)) => {
+				serializer.collect_seq(x.iter().filter_map(ids)).map(Ok)
+			},
+			ReflectRef::Set(x) if x.iter().any(|x| !self.is_saved(x)) => {
+				serializer.collect_seq(x.iter().filter_map(ids)).map(Ok)
+			},
+			ReflectRef::Map(x) if x.iter().any(|(k, _)| !self.is_saved(k)) => {
+				serializer
+					.collect_map(x.iter().filter_map(|(k, v)| {
+						Some((ids(k)?, TypedReflectSerializer::with_processor(v, ty_reg(), self)))
+					}))
+					.map(Ok)
+			},
+			_ => Ok(Err(serializer)),
+		}
+	}
+}
+
+/// Maps the entity ids in the file to the entities spawned for them.
+#[derive(Default)]
+struct EntityIds {
+	map: FxHashMap<u32, Entity>,
+}
+
+impl ReflectDeserializerProcessor for EntityIds {
+	fn try_deserialize<'de, D: Deserializer<'de>>(
+		&mut self, registration: &TypeRegistration, _: &TypeRegistry, deserializer: D,
+	) -> Result<Result<Box<dyn PartialReflect>, D>, D::Error> {
+		if registration.type_id() != TypeId::of::<Entity>() {
+			return Ok(Err(deserializer));
+		}
+
+		let e = match Option::<u32>::deserialize(deserializer)? {
+			Some(id) => *self
+				.map
+				.get(&id)
+				.ok_or_else(|| de::Error::custom(format!("no entity with id {id}")))?,
+			None => Entity::PLACEHOLDER,
+		};
+		Ok(Ok(Box::new(e)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rad_core::asset::{aref::AssetId, Uuid};
+	use vek::Vec3;
+
+	use crate::{
+		prefab::{PrefabComponent, PrefabOverride},
+		serde::DoNotSerialize,
+		test_engine,
+		transform::{Children, Parent, Transform},
+		RadComponent,
+		World,
+	};
+
+	fn binary(world: &World) -> Vec<u8> {
+		let mut data = Vec::new();
+		crate::serde::serialize_world(&mut data, world).unwrap();
+		data
+	}
+
+	fn from_binary(data: &[u8]) -> World {
+		let mut world = World::new();
+		crate::serde::deserialize_world(&mut &data[..], &mut world).unwrap();
+		world
+	}
+
+	#[test]
+	fn roundtrip() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let prefab = PrefabComponent {
+			prefab: AssetId::new(),
+			overrides: vec![PrefabOverride {
+				entity: Uuid::new_v4(),
+				component: Transform::uuid(),
+				path: "position.x".into(),
+				value: "1.0".into(),
+			}],
+		};
+		// Components are inserted in different orders, so the entities end up in different archetypes.
+		let a = world.spawn_empty().insert(prefab.clone()).id();
+		let b = world
+			.spawn_empty()
+			.insert((Children(Vec::new()), Parent(a)))
+			.insert(Transform {
+				position: Vec3::new(1.0, 2.0, 3.0),
+				..Transform::identity()
+			})
+			.id();
+		let mut c = world.spawn_empty();
+		c.remove::<Transform>();
+		c.insert((prefab, Parent(b)));
+		c.insert(Transform::identity());
+		let c = c.id();
+		world.entity_mut(a).insert(Children(vec![b]));
+		world.entity_mut(b).insert(Children(vec![c]));
+		world.spawn((DoNotSerialize, Parent(a)));
+
+		let data = binary(&world);
+		let text = from_binary(&data).to_text().unwrap();
+		assert_eq!(binary(&World::from_text(&text).unwrap()), data);
+
+		let text = world.to_text().unwrap();
+		let data = binary(&World::from_text(&text).unwrap());
+		assert_eq!(from_binary(&data).to_text().unwrap(), text);
+	}
+}
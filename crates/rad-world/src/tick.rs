@@ -1,6 +1,6 @@
 use bevy_ecs::schedule::{IntoSystemConfigs, IntoSystemSetConfigs, Schedule, SystemSet};
 
-use crate::World;
+use crate::{prefab, transform, World};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, SystemSet)]
 pub enum TickStage {
@@ -30,6 +30,12 @@ impl Tick {
 			TickStage::PreRender.before(TickStage::Render),
 			TickStage::Render.before(TickStage::PostRender),
 		));
+		inner.add_systems(prefab::update_prefabs.in_set(TickStage::PreUpdate));
+		inner.add_systems(
+			(transform::insert_global_transforms, transform::propagate_transforms)
+				.chain()
+				.in_set(TickStage::PreRender),
+		);
 		Self { inner }
 	}
 
@@ -1,9 +1,22 @@
+use std::{
+	io,
+	ops::{Deref, Mul},
+};
+
+use bevy_ecs::{
+	change_detection::DetectChangesMut,
+	entity::Entity,
+	query::{With, Without},
+	system::{Commands, Query},
+};
 use vek::{Mat4, Quaternion, Vec3};
 
-use crate::{rad_world, RadComponent};
+use crate::{RadComponent, World};
 
+/// The transform of an entity, relative to its `Parent` if it has one.
 #[derive(Copy, Clone, Debug, PartialEq, RadComponent)]
 #[uuid("efcddf51-d15c-434b-bff4-1a0fe18ba53b")]
+#[reflect(Default)]
 pub struct Transform {
 	pub position: Vec3<f32>,
 	pub rotation: Quaternion<f32>,
@@ -31,3 +44,270 @@ impl Transform {
 			.translated_3d(self.position)
 	}
 }
+
+/// Apply a child transform in the space of `self`. Scale is only carried through exactly if it is uniform, or the child
+/// is not rotated.
+impl Mul for Transform {
+	type Output = Self;
+
+	fn mul(self, child: Self) -> Self {
+		Self {
+			position: self.position + self.rotation * (self.scale * child.position),
+			rotation: self.rotation * child.rotation,
+			scale: self.scale * child.scale,
+		}
+	}
+}
+
+/// The world-space transform of an entity, computed from its `Transform` and those of its ancestors in
+/// `TickStage::PreRender`. It is runtime state and is not saved.
+#[derive(Copy, Clone, Debug, Default, PartialEq, bevy_ecs::component::Component)]
+pub struct GlobalTransform(pub Transform);
+
+impl Deref for GlobalTransform {
+	type Target = Transform;
+
+	fn deref(&self) -> &Self::Target { &self.0 }
+}
+
+/// The entity whose space this entity's `Transform` is in. Use `World::set_parent` to change it, so `Children` stays in
+/// sync.
+#[derive(Copy, Clone, Debug, PartialEq, RadComponent)]
+#[uuid("5a0e8a5c-46e4-4a3b-9d0e-7f43d1b2c8e1")]
+pub struct Parent(pub Entity);
+
+/// The entities with this entity as their `Parent`.
+#[derive(Clone, Debug, Default, PartialEq, RadComponent)]
+#[uuid("c3f1d0b7-8e0a-4f5c-a1b6-2d9e4c7a3f10")]
+pub struct Children(pub Vec<Entity>);
+
+impl World {
+	/// Make `child`'s transform relative to `parent`, or to the world if `None`. The local `Transform` is kept as is.
+	/// Fails if either entity does not exist, or if `parent` is `child` or one of its descendants.
+	pub fn set_parent(&mut self, child: Entity, parent: Option<Entity>) -> Result<(), io::Error> {
+		set_parent(self, child, parent)
+	}
+
+	/// Despawn an entity along with all of its descendants.
+	pub fn despawn_recursive(&mut self, entity: Entity) { despawn_recursive(self, entity) }
+}
+
+pub(crate) fn set_parent(
+	world: &mut bevy_ecs::world::World, child: Entity, parent: Option<Entity>,
+) -> Result<(), io::Error> {
+	let invalid = |msg| io::Error::new(io::ErrorKind::InvalidInput, msg);
+	if world.get_entity(child).is_err() || parent.is_some_and(|x| world.get_entity(x).is_err()) {
+		return Err(invalid("entity does not exist"));
+	}
+	let mut ancestor = parent;
+	while let Some(x) = ancestor {
+		if x == child {
+			return Err(invalid("cannot parent an entity to itself or its descendant"));
+		}
+		ancestor = world.get::<Parent>(x).map(|x| x.0);
+	}
+
+	if let Some(Parent(old)) = world.entity_mut(child).take::<Parent>() {
+		remove_child(world, old, child);
+	}
+	if let Some(parent) = parent {
+		world.entity_mut(child).insert(Parent(parent));
+		let mut parent = world.entity_mut(parent);
+		match parent.get_mut::<Children>() {
+			Some(mut children) => children.0.push(child),
+			None => {
+				parent.insert(Children(vec![child]));
+			},
+		}
+	}
+	Ok(())
+}
+
+pub(crate) fn despawn_recursive(world: &mut bevy_ecs::world::World, entity: Entity) {
+	if let Some(&Parent(parent)) = world.get::<Parent>(entity) {
+		remove_child(world, parent, entity);
+	}
+
+	let mut stack = vec![entity];
+	while let Some(e) = stack.pop() {
+		if let Some(children) = world.get::<Children>(e) {
+			stack.extend_from_slice(&children.0);
+		}
+		world.despawn(e);
+	}
+}
+
+fn remove_child(world: &mut bevy_ecs::world::World, parent: Entity, child: Entity) {
+	let Ok(mut parent) = world.get_entity_mut(parent) else {
+		return;
+	};
+	let Some(mut children) = parent.get_mut::<Children>() else {
+		return;
+	};
+	children.0.retain(|&x| x != child);
+	if children.0.is_empty() {
+		parent.remove::<Children>();
+	}
+}
+
+pub(crate) fn insert_global_transforms(
+	mut cmd: Commands, q: Query<Entity, (With<Transform>, Without<GlobalTransform>)>,
+) {
+	for e in q.iter() {
+		cmd.entity(e).insert(GlobalTransform::default());
+	}
+}
+
+pub(crate) fn propagate_transforms(
+	roots: Query<(Entity, &Transform, Option<&Children>, Option<&Parent>)>,
+	children: Query<(&Transform, &Parent, Option<&Children>)>, parents: Query<&Children, With<Transform>>,
+	mut globals: Query<&mut GlobalTransform>,
+) {
+	for (e, &t, c, p) in roots.iter() {
+		// Entities whose parent is gone, such as one that was not saved, can't be reached from a root, so are roots
+		// themselves.
+		let attached = p.is_some_and(|&Parent(p)| parents.get(p).is_ok_and(|x| x.0.contains(&e)));
+		if !attached {
+			propagate(e, t, c, &children, &mut globals);
+		}
+	}
+}
+
+fn propagate(
+	e: Entity, global: Transform, c: Option<&Children>, children: &Query<(&Transform, &Parent, Option<&Children>)>,
+	globals: &mut Query<&mut GlobalTransform>,
+) {
+	if let Ok(mut g) = globals.get_mut(e) {
+		g.set_if_neq(GlobalTransform(global));
+	}
+
+	for &child in c.into_iter().flat_map(|x| x.0.iter()) {
+		// Only follow children that agree on their parent, so a broken hierarchy can't cause a cycle.
+		if let Ok((&t, &Parent(parent), c)) = children.get(child)
+			&& parent == e
+		{
+			propagate(child, global * t, c, children, globals);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{serde::DoNotSerialize, test_engine, tick::Tick};
+
+	fn at(x: f32, y: f32, z: f32) -> Transform {
+		Transform {
+			position: Vec3::new(x, y, z),
+			..Transform::identity()
+		}
+	}
+
+	#[test]
+	fn parenting() {
+		let mut world = World::new();
+		let a = world.spawn_empty().id();
+		let b = world.spawn_empty().id();
+		let c = world.spawn_empty().id();
+		world.set_parent(b, Some(a)).unwrap();
+		world.set_parent(c, Some(b)).unwrap();
+		assert_eq!(world.get::<Children>(a).unwrap().0, [b]);
+		assert_eq!(world.get::<Parent>(c), Some(&Parent(b)));
+
+		assert!(world.set_parent(a, Some(c)).is_err());
+		assert!(world.set_parent(a, Some(a)).is_err());
+		assert!(world.set_parent(a, Some(Entity::PLACEHOLDER)).is_err());
+		assert!(world.get::<Parent>(a).is_none());
+
+		world.set_parent(c, Some(a)).unwrap();
+		assert_eq!(world.get::<Children>(a).unwrap().0, [b, c]);
+		assert!(world.get::<Children>(b).is_none());
+
+		world.set_parent(c, None).unwrap();
+		assert!(world.get::<Parent>(c).is_none());
+		assert_eq!(world.get::<Children>(a).unwrap().0, [b]);
+	}
+
+	#[test]
+	fn despawn() {
+		let mut world = World::new();
+		let a = world.spawn_empty().id();
+		let b = world.spawn_empty().id();
+		let c = world.spawn_empty().id();
+		let d = world.spawn_empty().id();
+		world.set_parent(b, Some(a)).unwrap();
+		world.set_parent(c, Some(b)).unwrap();
+		world.set_parent(d, Some(a)).unwrap();
+
+		world.despawn_recursive(b);
+		assert!(world.get_entity(b).is_err());
+		assert!(world.get_entity(c).is_err());
+		assert_eq!(world.get::<Children>(a).unwrap().0, [d]);
+
+		world.despawn_recursive(a);
+		assert_eq!(world.entities().len(), 0);
+	}
+
+	#[test]
+	fn propagation() {
+		let mut world = World::new();
+		let mut tick = Tick::new();
+		let a = world
+			.spawn_empty()
+			.insert(Transform {
+				scale: Vec3::broadcast(2.0),
+				..at(1.0, 0.0, 0.0)
+			})
+			.id();
+		let b = world.spawn_empty().insert(at(0.0, 1.0, 0.0)).id();
+		let c = world.spawn_empty().insert(at(0.0, 0.0, 1.0)).id();
+		world.set_parent(b, Some(a)).unwrap();
+		world.set_parent(c, Some(b)).unwrap();
+
+		tick.tick(&mut world);
+		let global = |world: &World, e| world.get::<GlobalTransform>(e).unwrap().position;
+		assert_eq!(global(&world, b), Vec3::new(1.0, 2.0, 0.0));
+		assert_eq!(global(&world, c), Vec3::new(1.0, 2.0, 2.0));
+
+		world.get_mut::<Transform>(a).unwrap().position.x = 3.0;
+		tick.tick(&mut world);
+		assert_eq!(global(&world, c), Vec3::new(3.0, 2.0, 2.0));
+
+		// Children of a parent that is gone are updated as roots.
+		world.despawn(a);
+		let d = world
+			.spawn_empty()
+			.insert((at(0.0, 0.0, 4.0), Parent(Entity::PLACEHOLDER)))
+			.id();
+		world.get_mut::<Transform>(b).unwrap().position.x = 5.0;
+		tick.tick(&mut world);
+		assert_eq!(global(&world, b), Vec3::new(5.0, 1.0, 0.0));
+		assert_eq!(global(&world, c), Vec3::new(5.0, 1.0, 1.0));
+		assert_eq!(global(&world, d), Vec3::new(0.0, 0.0, 4.0));
+	}
+
+	#[test]
+	fn save_load() {
+		let _engine = test_engine(|_| {});
+		let mut world = World::new();
+		let a = world.spawn_empty().id();
+		let b = world.spawn_empty().insert(at(1.0, 0.0, 0.0)).id();
+		let runtime = world.spawn((DoNotSerialize,)).id();
+		world.set_parent(b, Some(a)).unwrap();
+		world.set_parent(runtime, Some(a)).unwrap();
+
+		let mut data = Vec::new();
+		crate::serde::serialize_world(&mut data, &world).unwrap();
+		let mut loaded = World::new();
+		let entities = crate::serde::deserialize_world(&mut data.as_slice(), &mut loaded).unwrap();
+		let [a, b] = entities[..] else {
+			panic!("expected 2 entities, got {}", entities.len());
+		};
+		assert_eq!(loaded.get::<Children>(a).unwrap().0, [b]);
+		assert_eq!(loaded.get::<Parent>(b), Some(&Parent(a)));
+		assert_eq!(loaded.get::<Transform>(b), Some(&at(1.0, 0.0, 0.0)));
+
+		let text = World::from_text(&world.to_text().unwrap()).unwrap();
+		assert_eq!(text.to_text().unwrap(), loaded.to_text().unwrap());
+	}
+}